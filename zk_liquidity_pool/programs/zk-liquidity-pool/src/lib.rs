@@ -1,639 +1,5955 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use std::io::prelude::*;
+use anchor_lang::solana_program::alt_bn128::prelude::{
+    alt_bn128_addition, alt_bn128_multiplication, alt_bn128_pairing,
+};
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::poseidon::{self, Endianness, Parameters};
+use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use solana_zk_token_sdk::curve25519::ristretto::{
+    add_ristretto, multiply_ristretto, subtract_ristretto, PodRistrettoPoint,
+};
+use solana_zk_token_sdk::curve25519::scalar::PodScalar;
+use solana_zk_token_sdk::instruction::{
+    BatchedRangeProofContext, BatchedRangeProofU128Data, BatchedRangeProofU256Data,
+    BatchedRangeProofU64Data, ProofType, TransferProofContext,
+};
+use solana_zk_token_sdk::zk_token_proof_instruction::ProofInstruction;
+use solana_zk_token_sdk::zk_token_proof_program;
+use solana_zk_token_sdk::zk_token_proof_state::{ProofContextState, ProofContextStateMeta};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
 
 declare_id!("9GAC41pniqSKXbGmQ5jzGrbZDgVTz3U7Mt2jmQ3hePyv");
 
 // ---------------------------------------------------------------------
-// Dummy types & helper modules for SPL Confidential Tokens and ZK proofs
+// Helper types & modules for Token-2022 confidential transfers and ZK proofs
 // ---------------------------------------------------------------------
 
-/// A marker type for the Confidential Token Program.
+/// A marker type for Token-2022, whose confidential transfer extension holds the pool's tokens.
 #[derive(Clone)]
 pub struct ConfidentialTokenProgram;
 impl anchor_lang::Id for ConfidentialTokenProgram {
     fn id() -> Pubkey {
-        // Replace with the actual SPL Confidential Token Program ID.
-        Pubkey::new_from_array([0u8; 32])
+        anchor_spl::token_2022::ID
     }
 }
 
-/// Compression utilities for large ZK proofs.
-fn compress_proof(proof: Vec<u8>) -> Result<Vec<u8>> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(&proof).map_err(|_| ErrorCode::CompressionError)?;
-    let compressed_data = encoder.finish().map_err(|_| ErrorCode::CompressionError)?;
-    Ok(compressed_data)
+/// Grows `account` to `new_len` bytes, topping up rent from `payer` first.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_len: usize,
+) -> Result<()> {
+    if new_len <= account.data_len() {
+        return Ok(());
+    }
+    let required = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(new_len, false)?;
+    Ok(())
+}
+
+/// Creates the program-owned PDA `account` with `space` bytes.
+///
+/// Works even if lamports were already sent to the address, so nobody can block the
+/// account's creation by pre-funding it.
+fn create_pda_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let signer = &[signer_seeds];
+    let required = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return anchor_lang::system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                anchor_lang::system_program::CreateAccount {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+                signer,
+            ),
+            required,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    anchor_lang::system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            signer,
+        ),
+        space as u64,
+    )?;
+    anchor_lang::system_program::assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            anchor_lang::system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            signer,
+        ),
+        &crate::ID,
+    )
+}
+
+/// Marks a nullifier as spent by creating its PDA, failing with `NullifierAlreadyUsed` if
+/// it already exists.
+///
+/// `signer_seeds` are the nullifier PDA's seeds including the bump. The account stores nothing
+/// about the payer, so a vote's nullifier is never linked to the identity commitment.
+pub fn spend_nullifier<'info>(
+    nullifier: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    if *nullifier.owner == crate::ID {
+        return Err(ErrorCode::NullifierAlreadyUsed.into());
+    }
+    create_pda_account(
+        nullifier,
+        payer,
+        system_program,
+        8 + Nullifier::LEN,
+        signer_seeds,
+    )?;
+    nullifier.try_borrow_mut_data()?[..8].copy_from_slice(&Nullifier::DISCRIMINATOR);
+    Ok(())
 }
 
-fn decompress_proof(compressed: Vec<u8>) -> Result<Vec<u8>> {
-    // TODO: Implement decompression logic using flate2 if needed.
-    Ok(compressed) // Placeholder: return input directly.
+/// Deserializes the program account at `info`, or returns `None` if it has not been created.
+///
+/// Used for PDAs whose absence is meaningful, such as a voter that never delegated.
+pub fn load_optional_account<T: AccountDeserialize>(info: &AccountInfo) -> Result<Option<T>> {
+    if *info.owner != crate::ID {
+        return Ok(None);
+    }
+    let data = info.try_borrow_data()?;
+    T::try_deserialize(&mut &data[..]).map(Some)
 }
 
 pub mod zk_utils {
     use super::*;
+    use miniz_oxide::inflate::TINFLStatus;
 
-    /// Verifies a zero-knowledge proof for balance updates.
-    pub fn verify_confidential_balance(
-        zk_proof: Vec<u8>,
-        amount: u64,
-        old_balance: [u8; 64],
-        new_balance: [u8; 64],
-    ) -> Result<()> {
-        // TODO: Integrate a zk-SNARK/zk-STARK verifier (e.g., halo2, gnark, or circom)
-        // to process proofs off-chain. Ensure that old_balance + amount = new_balance without revealing values.
-        if zk_proof.is_empty() {
-            return Err(ErrorCode::InvalidZKProof.into());
-        }
-        Ok(())
-    }
+    /// Pedersen base for committed values (the Ristretto basepoint).
+    pub const PEDERSEN_G: PodRistrettoPoint = PodRistrettoPoint([
+        226, 242, 174, 10, 106, 188, 78, 113, 168, 132, 169, 97, 197, 0, 81, 95, 88, 227, 11, 106,
+        165, 130, 221, 141, 182, 166, 89, 69, 224, 141, 45, 118,
+    ]);
 
-    /// Verifies a zero-knowledge proof for confidential transfers.
-    pub fn verify_transfer_proof(zk_proof: Vec<u8>) -> Result<()> {
-        // TODO: Implement range proofs using Bulletproofs or Groth16 to validate transfers without leaking amounts.
-        if zk_proof.is_empty() {
-            return Err(ErrorCode::InvalidZKProof.into());
-        }
-        Ok(())
+    /// Pedersen base for openings, `H = hash_to_point(G)` as used by the SPL confidential tokens.
+    pub const PEDERSEN_H: PodRistrettoPoint = PodRistrettoPoint([
+        140, 146, 64, 180, 86, 169, 230, 220, 101, 195, 119, 161, 4, 141, 116, 95, 148, 160, 140,
+        219, 127, 68, 203, 205, 123, 70, 243, 64, 72, 135, 17, 52,
+    ]);
+
+    /// Envelope format version this program accepts.
+    pub const PROOF_ENVELOPE_VERSION: u8 = 2;
+
+    /// Tag hashed into every envelope domain separator.
+    pub const PROOF_ENVELOPE_DOMAIN: &[u8] = b"zklp-proof-envelope-v1";
+
+    /// Tag hashed into every public-input hash.
+    pub const PUBLIC_INPUT_DOMAIN: &[u8] = b"zklp-public-inputs-v1";
+
+    /// Domain separator binding a proof to one instruction of this program.
+    ///
+    /// `scope` is the pool the proof is for. Instructions that are not tied to a pool pass the
+    /// account that scopes them instead (the governance account, or the caller's stake account).
+    pub fn domain_separator(instruction: &str, scope: &Pubkey) -> [u8; 32] {
+        hashv(&[
+            PROOF_ENVELOPE_DOMAIN,
+            instruction.as_bytes(),
+            scope.as_ref(),
+            crate::ID.as_ref(),
+        ])
+        .to_bytes()
     }
 
-    /// Verifies a zero-knowledge identity proof to prevent Sybil attacks.
-    pub fn verify_identity_proof(zk_identity_proof: Vec<u8>) -> Result<()> {
-        // TODO: Store Merkle tree roots for user identity commitments and use zk-proofs (zk-SNARK/zk-STARK)
-        // to ensure unique identity, preventing multi-account farming and fraud.
-        if zk_identity_proof.is_empty() {
-            return Err(ErrorCode::SybilAttackDetected.into());
+    /// Hashes the public inputs of a statement, length-prefixing each one so that
+    /// different splits of the same bytes hash differently.
+    pub fn public_input_hash(inputs: &[&[u8]]) -> [u8; 32] {
+        let lengths: Vec<[u8; 4]> = inputs
+            .iter()
+            .map(|input| (input.len() as u32).to_le_bytes())
+            .collect();
+        let mut parts: Vec<&[u8]> = vec![PUBLIC_INPUT_DOMAIN];
+        for (input, length) in inputs.iter().zip(lengths.iter()) {
+            parts.push(length);
+            parts.push(input);
         }
-        Ok(())
+        hashv(&parts).to_bytes()
     }
-}
 
-/// Dummy confidential transfer call using CPI to the confidential token program.
-/// In production, replace this stub with a call to spl_confidential_token::instruction::confidential_transfer(),
-/// ensuring that the from, to, and authority accounts are validated.
-fn confidential_transfer(
-    ct_program: &Program<ConfidentialTokenProgram>,
-    from: &AccountInfo,
-    to: &AccountInfo,
-    authority: &AccountInfo,
-    amount: u64,
-) -> Result<()> {
-    // TODO: Invoke spl_confidential_token::instruction::confidential_transfer() here,
-    // ensuring the destination balance commitment is securely updated.
-    Ok(())
-}
+    /// Largest proof a compressed envelope may inflate to. Well above every proof format the
+    /// program verifies, and small enough that a decompression bomb cannot exhaust the heap.
+    pub const MAX_INFLATED_PROOF_LEN: usize = 4096;
 
-/// Dummy confidential transfer call that accepts a PDA signer.
-/// In production, modify this function to use invoke_signed() with proper PDA authorization.
-fn confidential_transfer_with_signer(
-    ct_program: &Program<ConfidentialTokenProgram>,
-    from: &AccountInfo,
-    to: &AccountInfo,
-    authority: &AccountInfo,
-    amount: u64,
-    signer_seeds: &[&[&[u8]]],
-) -> Result<()> {
-    // TODO: Use invoke_signed() with seeds-based PDAs to ensure only legitimate pool contracts can execute this transfer.
-    Ok(())
-}
+    /// Inflates a zlib-compressed proof, failing with `ProofTooLarge` rather than producing
+    /// more than `MAX_INFLATED_PROOF_LEN` bytes and with `MalformedProof` if it is not valid
+    /// zlib.
+    ///
+    /// Uses `miniz_oxide` directly: its one-shot decoder keeps the output as its window
+    /// instead of allocating a separate 32 KiB one, which would not fit in the program heap.
+    pub fn decompress_proof(compressed: &[u8]) -> Result<Vec<u8>> {
+        miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(compressed, MAX_INFLATED_PROOF_LEN)
+            .map_err(|error| match error.status {
+                TINFLStatus::HasMoreOutput => ErrorCode::ProofTooLarge.into(),
+                _ => ErrorCode::MalformedProof.into(),
+            })
+    }
 
-/// Dummy confidential mint instruction for reward distribution.
-/// In production, call spl_confidential_token::instruction::confidential_mint() to confidentially mint tokens.
-fn confidential_mint(
-    ct_program: &Program<ConfidentialTokenProgram>,
-    to: &AccountInfo,
-    amount: u64,
-) -> Result<()> {
-    // TODO: Call the confidential mint instruction from the SPL Confidential Token program.
-    Ok(())
-}
+    /// Checks an envelope's framing against what the calling instruction expects and
+    /// returns the inner proof bytes, inflated if the envelope carries them compressed.
+    /// Compression is opt-in per envelope, and the public input hash covers the statement,
+    /// not the encoding, so the same proof verifies whether or not it was compressed.
+    pub fn open_envelope<'a>(
+        envelope: &'a ProofEnvelope,
+        system: ProofSystem,
+        circuit: Circuit,
+        domain: &[u8; 32],
+        public_input_hash: &[u8; 32],
+    ) -> Result<Cow<'a, [u8]>> {
+        if envelope.version != PROOF_ENVELOPE_VERSION {
+            return Err(ErrorCode::UnsupportedProofVersion.into());
+        }
+        if envelope.system != system {
+            return Err(ErrorCode::ProofSystemMismatch.into());
+        }
+        if envelope.circuit != circuit {
+            return Err(ErrorCode::ProofCircuitMismatch.into());
+        }
+        if envelope.domain != *domain {
+            return Err(ErrorCode::ProofDomainMismatch.into());
+        }
+        if envelope.public_input_hash != *public_input_hash {
+            return Err(ErrorCode::PublicInputHashMismatch.into());
+        }
+        match envelope.compression {
+            ProofCompression::None => Ok(Cow::Borrowed(&envelope.proof)),
+            ProofCompression::Zlib => decompress_proof(&envelope.proof).map(Cow::Owned),
+        }
+    }
 
-// ---------------------------------------------------------------------
-// Program Declaration & Instruction Handlers
-// ---------------------------------------------------------------------
+    /// Domain separator for the balance-update challenge.
+    pub const BALANCE_PROOF_DOMAIN: &[u8] = b"zklp-balance-update-v1";
 
-#[program]
-pub mod zk_liquidity_pool {
-    use super::*;
+    /// Byte length of a balance-update proof: `Y_P (32) || Y_D (32) || z (32)`.
+    pub const BALANCE_PROOF_LEN: usize = 96;
 
-    /// Initializes the liquidity pool.
-    pub fn initialize_pool(ctx: Context<InitializePool>, bump: u8) -> Result<()> {
-        let pool = &mut ctx.accounts.pool;
-        pool.authority = *ctx.accounts.authority.key;
-        pool.token_mint = ctx.accounts.token_mint.key();
-        pool.total_staked = 0;
-        pool.bump = bump;
-        Ok(())
+    /// Direction of a confidential balance update.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum BalanceUpdate {
+        /// `new = old + amount` (stake).
+        Credit,
+        /// `new = old - amount` (withdraw / exit).
+        Debit,
     }
 
-    /// Stake tokens into the pool using confidential transfers with multi-asset support.
-    /// Stores the asset type and stake timestamp for flash loan protection.
-    pub fn stake(
-        ctx: Context<StakeAccounts>,
-        amount: u64,
-        zk_proof: Vec<u8>,
-        new_confidential_balance: [u8; 64],
-    ) -> Result<()> {
-        // Verify the provided ZK proof for the stake.
-        zk_utils::verify_confidential_balance(
-            zk_proof.clone(),
-            amount,
-            ctx.accounts.user_stake.confidential_balance,
-            new_confidential_balance,
-        )?;
-
-        // Optionally compress the proof to save space.
-        let _compressed_proof = compress_proof(zk_proof.clone()).ok();
+    /// Encodes a `u64` amount as a little-endian curve25519 scalar.
+    pub fn amount_scalar(amount: u64) -> PodScalar {
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&amount.to_le_bytes());
+        PodScalar(bytes)
+    }
 
-        // Perform a confidential transfer from the user's account to the pool's account.
-        confidential_transfer(
-            &ctx.accounts.confidential_token_program,
-            ctx.accounts.user_token_account.to_account_info().as_ref(),
-            ctx.accounts.pool_token_account.to_account_info().as_ref(),
-            ctx.accounts.user.to_account_info().as_ref(),
-            amount,
-        )?;
+    /// Splits a 64-byte twisted ElGamal ciphertext into its Pedersen commitment and decrypt handle.
+    pub fn split_ciphertext(ciphertext: &[u8; 64]) -> (PodRistrettoPoint, PodRistrettoPoint) {
+        let mut commitment = [0u8; 32];
+        let mut handle = [0u8; 32];
+        commitment.copy_from_slice(&ciphertext[..32]);
+        handle.copy_from_slice(&ciphertext[32..]);
+        (PodRistrettoPoint(commitment), PodRistrettoPoint(handle))
+    }
 
-        // Update the user's confidential balance commitment.
-        ctx.accounts.user_stake.confidential_balance = new_confidential_balance;
-        // Record the current timestamp for flash loan resistance.
-        ctx.accounts.user_stake.stake_timestamp = Clock::get()?.unix_timestamp;
-        // Set the asset type for multi-asset staking.
-        ctx.accounts.user_stake.asset_mint = ctx.accounts.token_mint.key();
+    /// Fiat–Shamir challenge for a balance-update proof.
+    ///
+    /// The hash is truncated to 252 bits so it is always a canonical scalar without
+    /// needing scalar reduction on-chain. The off-chain prover must call this same function.
+    /// Hashing in the envelope's `domain` keeps a proof from being replayed in another
+    /// instruction even when it is re-wrapped in a fresh envelope.
+    #[allow(clippy::too_many_arguments)]
+    pub fn balance_proof_challenge(
+        domain: &[u8; 32],
+        elgamal_pubkey: &[u8; 32],
+        old_balance: &[u8; 64],
+        new_balance: &[u8; 64],
+        amount: u64,
+        direction: BalanceUpdate,
+        y_p: &[u8; 32],
+        y_d: &[u8; 32],
+    ) -> PodScalar {
+        let direction_tag = [direction as u8];
+        let hash = hashv(&[
+            BALANCE_PROOF_DOMAIN,
+            domain,
+            elgamal_pubkey,
+            old_balance,
+            new_balance,
+            &amount.to_le_bytes(),
+            &direction_tag,
+            y_p,
+            y_d,
+        ]);
+        let mut challenge = hash.to_bytes();
+        challenge[31] &= 0x0f;
+        PodScalar(challenge)
+    }
 
-        // Update the pool's total staked amount (kept in plaintext for reward calculation).
-        let pool = &mut ctx.accounts.pool;
-        pool.total_staked = pool
-            .total_staked
-            .checked_add(amount)
-            .ok_or(ErrorCode::MathOverflow)?;
-        Ok(())
+    /// Public-input hash of a balance-update statement.
+    pub fn balance_input_hash(
+        elgamal_pubkey: &[u8; 32],
+        old_balance: &[u8; 64],
+        new_balance: &[u8; 64],
+        amount: u64,
+        direction: BalanceUpdate,
+    ) -> [u8; 32] {
+        public_input_hash(&[
+            elgamal_pubkey,
+            old_balance,
+            new_balance,
+            &amount.to_le_bytes(),
+            &[direction as u8],
+        ])
     }
 
-    /// Withdraw staked tokens.
-    /// Ensures that the stake has been held for a minimum duration to prevent flash loan attacks.
-    pub fn withdraw(
-        ctx: Context<Withdraw>,
+    /// Verifies a zero-knowledge proof for balance updates.
+    ///
+    /// `old_balance` and `new_balance` are twisted ElGamal ciphertexts `(C, D) = (v·G + r·H, r·P)`
+    /// under the owner's key `P = s⁻¹·H`. The proof is a Chaum–Pedersen proof that
+    /// `new - old ∓ amount·G` encrypts zero, i.e. that there is an `s` with `H = s·P` and
+    /// `ΔC ∓ amount·G = s·ΔD`. This lets the owner re-randomize the ciphertext on every update
+    /// while the program still checks `new = old ± amount` without learning either balance.
+    /// It fixes what `new_balance` decrypts to but not the opening of its `C`, so anything
+    /// that bounds the new balance does so through [`verify_bounded_ciphertexts`].
+    pub fn verify_confidential_balance(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
         amount: u64,
-        zk_proof: Vec<u8>,
-        new_confidential_balance: [u8; 64],
+        direction: BalanceUpdate,
+        elgamal_pubkey: [u8; 32],
+        old_balance: [u8; 64],
+        new_balance: [u8; 64],
     ) -> Result<()> {
-        // Check that minimum staking duration has passed.
-        let current_time = Clock::get()?.unix_timestamp;
-        let stake_time = ctx.accounts.user_stake.stake_timestamp;
-        let min_duration: i64 = 60; // e.g., a minimum 60-second lockup (adjust as needed)
-        if current_time - stake_time < min_duration {
-            return Err(ErrorCode::StakeDurationNotMet.into());
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::Balance,
+            domain,
+            &balance_input_hash(
+                &elgamal_pubkey,
+                &old_balance,
+                &new_balance,
+                amount,
+                direction,
+            ),
+        )?;
+        if zk_proof.len() != BALANCE_PROOF_LEN {
+            return Err(ErrorCode::MalformedProof.into());
         }
+        let mut y_p = [0u8; 32];
+        let mut y_d = [0u8; 32];
+        let mut z = [0u8; 32];
+        y_p.copy_from_slice(&zk_proof[..32]);
+        y_d.copy_from_slice(&zk_proof[32..64]);
+        z.copy_from_slice(&zk_proof[64..]);
 
-        // Verify the ZK proof for the withdrawal.
-        zk_utils::verify_confidential_balance(
-            zk_proof,
-            amount,
-            ctx.accounts.user_stake.confidential_balance,
-            new_confidential_balance,
-        )?;
+        let pubkey = PodRistrettoPoint(elgamal_pubkey);
+        let (old_commitment, old_handle) = split_ciphertext(&old_balance);
+        let (new_commitment, new_handle) = split_ciphertext(&new_balance);
 
-        // Derive PDA seeds for the pool authority.
-        let pool = &ctx.accounts.pool;
-        let pool_key = pool.key();
-        let seeds = &[b"pool".as_ref(), pool_key.as_ref(), &[pool.bump]];
-        let signer = &[&seeds[..]];
+        // ΔC' = (C_new - C_old) ∓ amount·G and ΔD = D_new - D_old.
+        let amount_point = multiply_ristretto(&amount_scalar(amount), &PEDERSEN_G)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        let commitment_delta = subtract_ristretto(&new_commitment, &old_commitment)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        let commitment_delta = match direction {
+            BalanceUpdate::Credit => subtract_ristretto(&commitment_delta, &amount_point),
+            BalanceUpdate::Debit => add_ristretto(&commitment_delta, &amount_point),
+        }
+        .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        let handle_delta = subtract_ristretto(&new_handle, &old_handle)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
 
-        // Perform the confidential transfer from the pool back to the user.
-        confidential_transfer_with_signer(
-            &ctx.accounts.confidential_token_program,
-            ctx.accounts.pool_token_account.to_account_info().as_ref(),
-            ctx.accounts.user_token_account.to_account_info().as_ref(),
-            ctx.accounts.pool.to_account_info().as_ref(),
+        let challenge = balance_proof_challenge(
+            domain,
+            &elgamal_pubkey,
+            &old_balance,
+            &new_balance,
             amount,
-            signer,
-        )?;
+            direction,
+            &y_p,
+            &y_d,
+        );
+        let z = PodScalar(z);
+        let y_p = PodRistrettoPoint(y_p);
+        let y_d = PodRistrettoPoint(y_d);
 
-        // Update the user's confidential balance commitment.
-        ctx.accounts.user_stake.confidential_balance = new_confidential_balance;
+        // z·P == Y_P + c·H
+        let lhs = multiply_ristretto(&z, &pubkey).ok_or(ErrorCode::InvalidZKProof)?;
+        let rhs = multiply_ristretto(&challenge, &PEDERSEN_H)
+            .and_then(|point| add_ristretto(&y_p, &point))
+            .ok_or(ErrorCode::InvalidZKProof)?;
+        if lhs != rhs {
+            return Err(ErrorCode::InvalidZKProof.into());
+        }
 
-        // Update the pool's total staked amount.
-        ctx.accounts.pool.total_staked = ctx
-            .accounts
-            .pool
-            .total_staked
-            .checked_sub(amount)
-            .ok_or(ErrorCode::MathOverflow)?;
+        // z·ΔD == Y_D + c·ΔC'
+        let lhs = multiply_ristretto(&z, &handle_delta).ok_or(ErrorCode::InvalidZKProof)?;
+        let rhs = multiply_ristretto(&challenge, &commitment_delta)
+            .and_then(|point| add_ristretto(&y_d, &point))
+            .ok_or(ErrorCode::InvalidZKProof)?;
+        if lhs != rhs {
+            return Err(ErrorCode::InvalidZKProof.into());
+        }
         Ok(())
     }
 
-    /// Commit a swap order using a commit–reveal scheme.
-    /// The trader commits to an order by providing a commitment hash and an encrypted order.
-    pub fn commit_swap(
-        ctx: Context<CommitSwap>,
-        commitment: [u8; 32],
-        encrypted_order: [u8; 64],
-    ) -> Result<()> {
-        let trade_order = &mut ctx.accounts.trade_order;
-        trade_order.commitment = commitment;
-        trade_order.encrypted_order = encrypted_order;
-        trade_order.trade_timestamp = Clock::get()?.unix_timestamp;
-        Ok(())
+    /// Domain separator for the ciphertext–commitment equality challenge.
+    pub const EQUALITY_PROOF_DOMAIN: &[u8] = b"zklp-ciphertext-commitment-equality-v1";
+
+    /// Byte length of a ciphertext–commitment equality proof for one ciphertext:
+    /// `Y₀ || Y₁ || Y₂ || z_s || z_x || z_r`, 32 bytes each.
+    pub const EQUALITY_PROOF_LEN: usize = 192;
+
+    /// Public-input hash of a ciphertext–commitment equality statement.
+    pub fn equality_input_hash(
+        elgamal_pubkey: &[u8; 32],
+        ciphertexts: &[[u8; 64]],
+        commitments: &[[u8; 32]],
+    ) -> [u8; 32] {
+        let mut inputs: Vec<&[u8]> = vec![elgamal_pubkey];
+        inputs.extend(ciphertexts.iter().map(|ciphertext| &ciphertext[..]));
+        inputs.extend(commitments.iter().map(|commitment| &commitment[..]));
+        public_input_hash(&inputs)
     }
 
-    /// Reveal the trade order, verifying the commitment with a provided ZK proof.
-    pub fn reveal_swap(
-        ctx: Context<RevealSwap>,
-        zk_proof: Vec<u8>,
-        order_details: Vec<u8>, // Decrypted order details.
-    ) -> Result<()> {
-        // TODO: Implement a commitment scheme with a time delay (e.g., zk-time locks) to prevent premature reveal.
-        // Verify that hash(order_details + secret nonce) matches the stored commitment.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        ctx.accounts.trade_order.revealed_order = order_details;
-        Ok(())
+    /// Fiat–Shamir challenge for the equality proof of one ciphertext; `commitments` is
+    /// `Y₀ || Y₁ || Y₂`.
+    pub fn equality_proof_challenge(
+        domain: &[u8; 32],
+        elgamal_pubkey: &[u8; 32],
+        ciphertext: &[u8; 64],
+        commitment: &[u8; 32],
+        commitments: &[u8; 96],
+    ) -> PodScalar {
+        challenge_scalar(&[
+            EQUALITY_PROOF_DOMAIN,
+            domain,
+            elgamal_pubkey,
+            ciphertext,
+            commitment,
+            commitments,
+        ])
     }
 
-    /// Distribute rewards to liquidity providers using confidential minting.
-    /// Incorporate zk-SNARK-based reward calculation to preserve privacy.
-    pub fn distribute_rewards(
-        ctx: Context<DistributeRewards>,
-        zk_reward_proof: Vec<u8>,
-        reward_amount: u64,
+    /// Verifies that each Pedersen commitment `C'ⱼ = xⱼ·G + r'ⱼ·H` hides the value the
+    /// matching ciphertext `(Cⱼ, Dⱼ)` decrypts to under the owner's key `P = s⁻¹·H`.
+    ///
+    /// [`verify_confidential_balance`] only fixes what a ciphertext decrypts to; the
+    /// ciphertext's own `C` is not bound to that value, since the owner can shift `D` by
+    /// `s⁻¹·X` to move the plaintext by `X` and leave `C` alone. Range proofs are therefore
+    /// made over the fresh commitments checked here. This is the SPL ciphertext–commitment
+    /// equality proof: knowledge of `(s, x, r')` with `Y₀ = y_s·P`, `Y₁ = y_x·G + y_s·D` and
+    /// `Y₂ = y_x·G + y_r·H`, one proof per ciphertext.
+    pub fn verify_ciphertext_commitment_equality(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        elgamal_pubkey: &[u8; 32],
+        ciphertexts: &[[u8; 64]],
+        commitments: &[[u8; 32]],
     ) -> Result<()> {
-        // Verify the ZK proof for reward distribution.
-        zk_utils::verify_transfer_proof(zk_reward_proof)?;
-        confidential_mint(
-            &ctx.accounts.confidential_token_program,
-            ctx.accounts.pool_token_account.to_account_info().as_ref(),
-            reward_amount,
+        if ciphertexts.len() != commitments.len() {
+            return Err(ErrorCode::InvalidPublicInputs.into());
+        }
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::CiphertextCommitmentEquality,
+            domain,
+            &equality_input_hash(elgamal_pubkey, ciphertexts, commitments),
         )?;
+        if zk_proof.len() != EQUALITY_PROOF_LEN * ciphertexts.len() {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let pubkey = PodRistrettoPoint(*elgamal_pubkey);
+        for (j, (ciphertext, commitment)) in ciphertexts.iter().zip(commitments).enumerate() {
+            let proof = &zk_proof[EQUALITY_PROOF_LEN * j..EQUALITY_PROOF_LEN * (j + 1)];
+            let point = |i: usize| {
+                let mut bytes = [0u8; 32];
+                bytes.copy_from_slice(&proof[32 * i..32 * (i + 1)]);
+                PodRistrettoPoint(bytes)
+            };
+            let scalar = |i: usize| PodScalar(point(i).0);
+            let mut nonce_commitments = [0u8; 96];
+            nonce_commitments.copy_from_slice(&proof[..96]);
+            let challenge = equality_proof_challenge(
+                domain,
+                elgamal_pubkey,
+                ciphertext,
+                commitment,
+                &nonce_commitments,
+            );
+            let (y_0, y_1, y_2) = (point(0), point(1), point(2));
+            let (z_s, z_x, z_r) = (scalar(3), scalar(4), scalar(5));
+            let (c, d) = split_ciphertext(ciphertext);
+
+            let combine =
+                |x: &PodScalar, p: &PodRistrettoPoint, y: &PodScalar, q: &PodRistrettoPoint| {
+                    add_ristretto(&multiply_ristretto(x, p)?, &multiply_ristretto(y, q)?)
+                };
+            let shifted = |y: &PodRistrettoPoint, target: &PodRistrettoPoint| {
+                add_ristretto(y, &multiply_ristretto(&challenge, target)?)
+            };
+            // z_s·P == Y₀ + c·H, z_x·G + z_s·D == Y₁ + c·C and z_x·G + z_r·H == Y₂ + c·C'
+            let checks = [
+                (
+                    multiply_ristretto(&z_s, &pubkey),
+                    shifted(&y_0, &PEDERSEN_H),
+                ),
+                (combine(&z_x, &PEDERSEN_G, &z_s, &d), shifted(&y_1, &c)),
+                (
+                    combine(&z_x, &PEDERSEN_G, &z_r, &PEDERSEN_H),
+                    shifted(&y_2, &PodRistrettoPoint(*commitment)),
+                ),
+            ];
+            for (lhs, rhs) in checks {
+                if lhs.is_none() || lhs != rhs {
+                    return Err(ErrorCode::InvalidZKProof.into());
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Update reward parameters using multi-signature governance.
-    /// Implements zk-SNARK voting where votes remain encrypted and only aggregated totals are revealed.
-    pub fn update_reward_params(
-        ctx: Context<UpdateRewardParams>,
-        new_reward_rate: u64,
-        zk_governance_proof: Vec<u8>,
+    /// Checks that every ciphertext in `ciphertexts` holds a 64-bit value: `equality_proof`
+    /// binds the matching entry of `commitments` to it, and `range_proof` covers
+    /// `commitments` (see [`verify_transfer_proof`]).
+    pub fn verify_bounded_ciphertexts(
+        proof_account: &AccountInfo,
+        equality_proof: &ProofEnvelope,
+        range_proof: &ProofEnvelope,
+        domain: &[u8; 32],
+        elgamal_pubkey: &[u8; 32],
+        ciphertexts: &[[u8; 64]],
+        commitments: &[[u8; 32]],
     ) -> Result<()> {
-        // TODO: Verify multi-signature governance via a zk-enabled tallying system.
-        zk_utils::verify_transfer_proof(zk_governance_proof)?;
-        ctx.accounts.governance.reward_rate = new_reward_rate;
-        Ok(())
+        verify_ciphertext_commitment_equality(
+            equality_proof,
+            domain,
+            elgamal_pubkey,
+            ciphertexts,
+            commitments,
+        )?;
+        verify_transfer_proof(proof_account, range_proof, domain, commitments)
     }
 
-    /// Cast a confidential vote on governance issues.
-    pub fn confidential_vote(
-        ctx: Context<ConfidentialVote>,
-        vote: u8,
-        zk_vote_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement confidential voting logic using zk-SNARK proofs so that individual votes remain hidden.
-        zk_utils::verify_identity_proof(zk_vote_proof)?;
-        ctx.accounts.governance.vote_count = ctx
-            .accounts
-            .governance
-            .vote_count
-            .checked_add(vote as u64)
-            .ok_or(ErrorCode::MathOverflow)?;
-        Ok(())
+    /// Domain separator for the auditor-amount challenge.
+    pub const AUDITOR_AMOUNT_DOMAIN: &[u8] = b"zklp-auditor-amount-v1";
+
+    /// Byte length of an auditor-amount proof: `Y_H (32) || Y_P (32) || z (32)`.
+    pub const AUDITOR_AMOUNT_PROOF_LEN: usize = 96;
+
+    /// Public-input hash of an auditor-amount statement.
+    pub fn auditor_amount_input_hash(
+        auditor_pubkey: &[u8; 32],
+        ciphertext: &[u8; 64],
+        amount: u64,
+    ) -> [u8; 32] {
+        public_input_hash(&[auditor_pubkey, ciphertext, &amount.to_le_bytes()])
     }
 
-    // ---------------------------------------------------------------------
-    // Additional Advanced Features
-    // ---------------------------------------------------------------------
+    /// Fiat–Shamir challenge for an auditor-amount proof.
+    pub fn auditor_amount_challenge(
+        domain: &[u8; 32],
+        auditor_pubkey: &[u8; 32],
+        ciphertext: &[u8; 64],
+        amount: u64,
+        y_h: &[u8; 32],
+        y_p: &[u8; 32],
+    ) -> PodScalar {
+        challenge_scalar(&[
+            AUDITOR_AMOUNT_DOMAIN,
+            domain,
+            auditor_pubkey,
+            ciphertext,
+            &amount.to_le_bytes(),
+            y_h,
+            y_p,
+        ])
+    }
 
-    /// zk-enabled multi-signature transaction.
-    pub fn zk_multisig_transaction(
-        ctx: Context<AdditionalFeatures>,
-        multisig_data: Vec<u8>,
-        zk_proof: Vec<u8>,
+    /// Verifies that `ciphertext = (amount·G + k·H, k·P_A)` encrypts the public `amount` under
+    /// the pool auditor's key `P_A`.
+    ///
+    /// The amount itself is public in the instruction; what this adds is a fresh `k`, so the
+    /// change the ciphertext makes to the pool's auditor total does not reveal it. The proof is
+    /// a Chaum–Pedersen proof that `C - amount·G` and `D` share the discrete log `k` to bases
+    /// `H` and `P_A`.
+    pub fn verify_auditor_amount(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        auditor_pubkey: &[u8; 32],
+        ciphertext: &[u8; 64],
+        amount: u64,
     ) -> Result<()> {
-        // TODO: Implement multi-signature approvals using zk-SNARKs so that multiple parties can sign without revealing their identities.
-        zk_utils::verify_transfer_proof(zk_proof)?;
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::AuditorAmount,
+            domain,
+            &auditor_amount_input_hash(auditor_pubkey, ciphertext, amount),
+        )?;
+        if zk_proof.len() != AUDITOR_AMOUNT_PROOF_LEN {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let mut y_h = [0u8; 32];
+        let mut y_p = [0u8; 32];
+        let mut z = [0u8; 32];
+        y_h.copy_from_slice(&zk_proof[..32]);
+        y_p.copy_from_slice(&zk_proof[32..64]);
+        z.copy_from_slice(&zk_proof[64..]);
+        let challenge =
+            auditor_amount_challenge(domain, auditor_pubkey, ciphertext, amount, &y_h, &y_p);
+        let z = PodScalar(z);
+
+        let (commitment, handle) = split_ciphertext(ciphertext);
+        let blinding = multiply_ristretto(&amount_scalar(amount), &PEDERSEN_G)
+            .and_then(|amount_point| subtract_ristretto(&commitment, &amount_point))
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        // z·H == Y_H + c·(C - amount·G) and z·P_A == Y_P + c·D
+        let checks = [
+            (PEDERSEN_H, PodRistrettoPoint(y_h), blinding),
+            (
+                PodRistrettoPoint(*auditor_pubkey),
+                PodRistrettoPoint(y_p),
+                handle,
+            ),
+        ];
+        for (base, nonce_commitment, target) in checks {
+            let lhs = multiply_ristretto(&z, &base);
+            let rhs = multiply_ristretto(&challenge, &target)
+                .and_then(|point| add_ristretto(&nonce_commitment, &point));
+            if lhs.is_none() || lhs != rhs {
+                return Err(ErrorCode::InvalidZKProof.into());
+            }
+        }
         Ok(())
     }
 
-    /// ZK rollback protection to prevent transaction replay or reversion.
-    pub fn zk_rollback_protection(
-        ctx: Context<AdditionalFeatures>,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement zk-proofs to ensure the transaction is not being replayed or fraudulently reverted.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// Bit length every bound commitment must be range-checked at.
+    pub const RANGE_PROOF_BIT_LENGTH: u8 = 64;
+
+    /// Loads the batched range proof context verified by the ZK Token Proof program.
+    ///
+    /// Bulletproofs are too expensive to verify inside this program, so verification is split off
+    /// into the native proof program (which cannot be invoked via CPI). A zero
+    /// `proof_instruction_offset` means the proof was verified earlier into a context-state
+    /// account passed as `proof_account`; any other value means `proof_account` is the
+    /// instructions sysvar and the `VerifyBatchedRangeProofU{64,128,256}` instruction sits at
+    /// that offset from the current instruction in the same transaction.
+    pub fn load_range_proof_context(
+        proof_account: &AccountInfo,
+        proof_instruction_offset: i8,
+    ) -> Result<BatchedRangeProofContext> {
+        if proof_instruction_offset == 0 {
+            if *proof_account.owner != zk_token_proof_program::id() {
+                return Err(ErrorCode::InvalidProofAccount.into());
+            }
+            let data = proof_account.try_borrow_data()?;
+            let meta = ProofContextStateMeta::try_from_bytes(&data)
+                .map_err(|_| ErrorCode::InvalidProofAccount)?;
+            match ProofType::try_from(meta.proof_type) {
+                Ok(ProofType::BatchedRangeProofU64)
+                | Ok(ProofType::BatchedRangeProofU128)
+                | Ok(ProofType::BatchedRangeProofU256) => {}
+                _ => return Err(ErrorCode::InvalidProofAccount.into()),
+            }
+            let state = ProofContextState::<BatchedRangeProofContext>::try_from_bytes(&data)
+                .map_err(|_| ErrorCode::InvalidProofAccount)?;
+            Ok(state.proof_context)
+        } else {
+            let instruction =
+                get_instruction_relative(proof_instruction_offset as i64, proof_account)?;
+            if instruction.program_id != zk_token_proof_program::id() {
+                return Err(ErrorCode::InvalidProofAccount.into());
+            }
+            let data = &instruction.data;
+            let context = match ProofInstruction::instruction_type(data) {
+                Some(ProofInstruction::VerifyBatchedRangeProofU64) => ProofInstruction::proof_data::<
+                    BatchedRangeProofU64Data,
+                    BatchedRangeProofContext,
+                >(data)
+                .map(|proof| proof.context),
+                Some(ProofInstruction::VerifyBatchedRangeProofU128) => {
+                    ProofInstruction::proof_data::<
+                        BatchedRangeProofU128Data,
+                        BatchedRangeProofContext,
+                    >(data)
+                    .map(|proof| proof.context)
+                }
+                Some(ProofInstruction::VerifyBatchedRangeProofU256) => {
+                    ProofInstruction::proof_data::<
+                        BatchedRangeProofU256Data,
+                        BatchedRangeProofContext,
+                    >(data)
+                    .map(|proof| proof.context)
+                }
+                _ => None,
+            };
+            context.ok_or_else(|| ErrorCode::InvalidProofAccount.into())
+        }
     }
 
-    /// Batch staking multiple assets in a single confidential transaction using zk-proofs.
-    pub fn batch_stake(
-        ctx: Context<AdditionalFeatures>,
-        amounts: Vec<u64>,
-        zk_proofs: Vec<Vec<u8>>,
-        new_confidential_balances: Vec<[u8; 64]>,
-    ) -> Result<()> {
-        // TODO: Loop through each stake, validate each using zk-proofs, and execute confidential transfers.
-        Ok(())
+    /// Loads the `TransferData` proof context the ZK Token Proof program verified into the
+    /// context-state account `proof_account`.
+    pub fn load_transfer_proof_context(
+        proof_account: &AccountInfo,
+    ) -> Result<TransferProofContext> {
+        if *proof_account.owner != zk_token_proof_program::id() {
+            return Err(ErrorCode::InvalidProofAccount.into());
+        }
+        let data = proof_account.try_borrow_data()?;
+        let meta = ProofContextStateMeta::try_from_bytes(&data)
+            .map_err(|_| ErrorCode::InvalidProofAccount)?;
+        if ProofType::try_from(meta.proof_type) != Ok(ProofType::Transfer) {
+            return Err(ErrorCode::InvalidProofAccount.into());
+        }
+        let state = ProofContextState::<TransferProofContext>::try_from_bytes(&data)
+            .map_err(|_| ErrorCode::InvalidProofAccount)?;
+        Ok(state.proof_context)
     }
 
-    /// zk-enabled exit mechanism for LPs to withdraw liquidity without revealing exact shares.
-    pub fn zk_exit(
-        ctx: Context<AdditionalFeatures>,
-        amount: u64,
-        zk_proof: Vec<u8>,
-        new_confidential_balance: [u8; 64],
+    /// Public-input hash of a range-proof statement over `commitments`.
+    pub fn range_input_hash(commitments: &[[u8; 32]]) -> [u8; 32] {
+        let inputs: Vec<&[u8]> = commitments.iter().map(|c| c.as_slice()).collect();
+        public_input_hash(&inputs)
+    }
+
+    /// Verifies an aggregated 64-bit Bulletproofs range proof for confidential transfers.
+    ///
+    /// The envelope's proof carries the one-byte `proof_instruction_offset` (see
+    /// [`load_range_proof_context`]); the proof itself lives in the proof instruction or the
+    /// context-state account. Every commitment in `expected_commitments` must be covered by the
+    /// proof at 64 bits, which is what binds an otherwise free-standing range proof to this
+    /// instruction, so an empty `expected_commitments` is rejected.
+    pub fn verify_transfer_proof(
+        proof_account: &AccountInfo,
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        expected_commitments: &[[u8; 32]],
     ) -> Result<()> {
-        // TODO: Implement a zk-enabled exit that verifies withdrawal without exposing the precise stake.
-        zk_utils::verify_confidential_balance(
-            zk_proof,
-            amount,
-            ctx.accounts.user_stake.confidential_balance,
-            new_confidential_balance,
+        if expected_commitments.is_empty() {
+            return Err(ErrorCode::RangeProofMismatch.into());
+        }
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Bulletproofs,
+            Circuit::Range,
+            domain,
+            &range_input_hash(expected_commitments),
         )?;
+        let offset = match *zk_proof {
+            [offset] => offset as i8,
+            _ => return Err(ErrorCode::MalformedProof.into()),
+        };
+        let context = load_range_proof_context(proof_account, offset)?;
+        for expected in expected_commitments {
+            let covered = context
+                .commitments
+                .iter()
+                .zip(context.bit_lengths.iter())
+                .any(|(commitment, bits)| {
+                    commitment.0 == *expected && *bits == RANGE_PROOF_BIT_LENGTH
+                });
+            if !covered {
+                return Err(ErrorCode::RangeProofMismatch.into());
+            }
+        }
         Ok(())
     }
 
-    /// zk-based automatic liquidity rebalancing.
-    pub fn zk_auto_rebalance(
-        ctx: Context<AdditionalFeatures>,
-        zk_proof: Vec<u8>,
-        liquidity_params: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement automatic liquidity rebalancing using zk-proofs to adjust pool parameters confidentially.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// BN254 base field modulus `p`, big-endian.
+    pub const BN254_FIELD_MODULUS: [u8; 32] = [
+        0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58,
+        0x5d, 0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c,
+        0xfd, 0x47,
+    ];
+
+    /// BN254 scalar field modulus `r`, big-endian. Public inputs must be below it.
+    pub const BN254_SCALAR_MODULUS: [u8; 32] = [
+        0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58,
+        0x5d, 0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00,
+        0x00, 0x01,
+    ];
+
+    /// Byte length of a Groth16 proof: `A (G1, 64) || B (G2, 128) || C (G1, 64)`.
+    pub const GROTH16_PROOF_LEN: usize = 256;
+
+    /// Largest number of public inputs a registered circuit may declare.
+    pub const MAX_PUBLIC_INPUTS: usize = 8;
+
+    /// Maps arbitrary bytes (e.g. a pubkey) into the BN254 scalar field.
+    ///
+    /// The SHA-256 digest is truncated to 253 bits, which is always below `r`.
+    pub fn hash_to_field(parts: &[&[u8]]) -> [u8; 32] {
+        let mut field = hashv(parts).to_bytes();
+        field[0] &= 0x1f;
+        field
     }
 
-    /// zk-time lock unlocking mechanism for liquidity.
-    pub fn zk_time_lock_unlock(
-        ctx: Context<AdditionalFeatures>,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Use zk-time locks to allow liquidity unlocking only after a specified delay.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// Encodes a `u64` as a big-endian BN254 scalar.
+    pub fn u64_to_field(value: u64) -> [u8; 32] {
+        let mut field = [0u8; 32];
+        field[24..].copy_from_slice(&value.to_be_bytes());
+        field
     }
 
-    /// Place a confidential limit order using zk-proofs.
-    pub fn confidential_limit_order(
-        ctx: Context<AdditionalFeatures>,
-        order_data: Vec<u8>,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement confidential limit orders, allowing users to set trade conditions privately.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// Negates a big-endian G1 point, `(x, y) -> (x, p - y)`.
+    pub fn negate_g1(point: &[u8; 64]) -> [u8; 64] {
+        let mut negated = *point;
+        if point[32..].iter().all(|byte| *byte == 0) {
+            return negated;
+        }
+        let mut borrow = 0u16;
+        for i in (0..32).rev() {
+            let lhs = BN254_FIELD_MODULUS[i] as u16;
+            let rhs = point[32 + i] as u16 + borrow;
+            if lhs >= rhs {
+                negated[32 + i] = (lhs - rhs) as u8;
+                borrow = 0;
+            } else {
+                negated[32 + i] = (lhs + 256 - rhs) as u8;
+                borrow = 1;
+            }
+        }
+        negated
     }
 
-    /// zk-secured smart contract upgradability.
-    pub fn zk_upgrade(
-        ctx: Context<AdditionalFeatures>,
-        upgrade_data: Vec<u8>,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement contract upgrade validation using zk-proofs for governance-approved changes.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// Public-input hash of a Groth16 statement.
+    pub fn groth16_input_hash(public_inputs: &[[u8; 32]]) -> [u8; 32] {
+        let inputs: Vec<&[u8]> = public_inputs.iter().map(|i| i.as_slice()).collect();
+        public_input_hash(&inputs)
     }
 
-    /// zk-proof of funds verification to confirm user holds required funds confidentially.
-    pub fn zk_proof_of_funds(
-        ctx: Context<AdditionalFeatures>,
-        zk_proof: Vec<u8>,
+    /// Verifies a Groth16 proof on BN254 against the key registered for `circuit`.
+    ///
+    /// Checks `e(-A, B) · e(α, β) · e(vk_x, γ) · e(C, δ) = 1` with
+    /// `vk_x = IC₀ + Σ inputᵢ · ICᵢ₊₁`, using the alt_bn128 syscalls.
+    /// The circuit itself does not see `domain`; cross-circuit replay is stopped by the
+    /// per-circuit keys, and the envelope check rejects proofs framed for another instruction.
+    pub fn verify_groth16(
+        registry: &VerifyingKeyRegistry,
+        circuit: Circuit,
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        public_inputs: &[[u8; 32]],
     ) -> Result<()> {
-        // TODO: Verify via zk-proofs that the user holds the required funds without exposing the actual balance.
-        zk_utils::verify_transfer_proof(zk_proof)?;
+        let proof = open_envelope(
+            envelope,
+            ProofSystem::Groth16,
+            circuit,
+            domain,
+            &groth16_input_hash(public_inputs),
+        )?;
+        let key = &registry
+            .find(circuit)
+            .ok_or(ErrorCode::VerifyingKeyNotFound)?
+            .key;
+        if key.ic.len() != public_inputs.len() + 1 {
+            return Err(ErrorCode::InvalidPublicInputs.into());
+        }
+        if proof.len() != GROTH16_PROOF_LEN {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let mut proof_a = [0u8; 64];
+        proof_a.copy_from_slice(&proof[..64]);
+        let proof_b = &proof[64..192];
+        let proof_c = &proof[192..];
+
+        let mut vk_x = key.ic[0].to_vec();
+        for (input, ic) in public_inputs.iter().zip(key.ic[1..].iter()) {
+            if *input >= BN254_SCALAR_MODULUS {
+                return Err(ErrorCode::InvalidPublicInputs.into());
+            }
+            let product = alt_bn128_multiplication(&[&ic[..], &input[..]].concat())
+                .map_err(|_| ErrorCode::InvalidVerifyingKey)?;
+            vk_x = alt_bn128_addition(&[&vk_x[..], &product[..]].concat())
+                .map_err(|_| ErrorCode::InvalidVerifyingKey)?;
+        }
+
+        let pairing_input = [
+            &negate_g1(&proof_a)[..],
+            proof_b,
+            &key.alpha_g1[..],
+            &key.beta_g2[..],
+            &vk_x[..],
+            &key.gamma_g2[..],
+            proof_c,
+            &key.delta_g2[..],
+        ]
+        .concat();
+        let result = alt_bn128_pairing(&pairing_input).map_err(|_| ErrorCode::InvalidZKProof)?;
+        if result.last() != Some(&1) || result[..result.len() - 1].iter().any(|b| *b != 0) {
+            return Err(ErrorCode::InvalidZKProof.into());
+        }
         Ok(())
     }
 
-    /// zk-private flash loans where loan details remain confidential until repayment.
-    pub fn zk_private_flash_loan(
-        ctx: Context<AdditionalFeatures>,
-        loan_amount: u64,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Implement private flash loans using zk-proofs to conceal loan amounts and terms until settlement.
-        zk_utils::verify_transfer_proof(zk_proof)?;
-        Ok(())
+    /// Order `ℓ` of the Ristretto group, little-endian.
+    pub const RISTRETTO_GROUP_ORDER: [u8; 32] = [
+        0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde,
+        0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x10,
+    ];
+
+    /// Domain separator for the ballot validity challenge.
+    pub const BALLOT_PROOF_DOMAIN: &[u8] = b"zklp-ballot-v1";
+
+    /// Byte length of a ballot proof: `A₀ || B₀ || A₁ || B₁ || c₀ || z₀ || z₁`, 32 bytes each.
+    pub const BALLOT_PROOF_LEN: usize = 224;
+
+    /// Domain separator for the decryption-share challenge.
+    pub const DECRYPTION_SHARE_DOMAIN: &[u8] = b"zklp-decryption-share-v1";
+
+    /// Domain separator for the weight-commitment challenge.
+    pub const WEIGHT_COMMITMENT_DOMAIN: &[u8] = b"zklp-weight-commitment-v1";
+
+    /// Byte length of a weight-commitment proof: `A₁ || A₂ || A₃ || z_w || z_t || z_r`, 32 bytes
+    /// each.
+    pub const WEIGHT_COMMITMENT_PROOF_LEN: usize = 192;
+
+    /// Encodes a `u128` as a little-endian curve25519 scalar.
+    pub fn u128_scalar(value: u128) -> PodScalar {
+        let mut bytes = [0u8; 32];
+        bytes[..16].copy_from_slice(&value.to_le_bytes());
+        PodScalar(bytes)
     }
 
-    /// Integrate zk-Rollups to batch confidential transactions and reduce transaction fees.
-    pub fn integrate_zk_rollup(ctx: Context<AdditionalFeatures>) -> Result<()> {
-        // TODO: Implement zk-Rollup integration to improve scalability and reduce transaction fees.
+    /// Whether little-endian `scalar` is below `ℓ`.
+    pub fn is_canonical_scalar(scalar: &[u8; 32]) -> bool {
+        for i in (0..32).rev() {
+            if scalar[i] != RISTRETTO_GROUP_ORDER[i] {
+                return scalar[i] < RISTRETTO_GROUP_ORDER[i];
+            }
+        }
+        false
+    }
+
+    /// `a - b mod ℓ` for canonical little-endian scalars.
+    pub fn scalar_sub(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        let mut difference = [0u8; 32];
+        let mut borrow = 0i16;
+        for i in 0..32 {
+            let value = a[i] as i16 - b[i] as i16 - borrow;
+            borrow = (value < 0) as i16;
+            difference[i] = value.rem_euclid(256) as u8;
+        }
+        if borrow == 0 {
+            return difference;
+        }
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let value = difference[i] as u16 + RISTRETTO_GROUP_ORDER[i] as u16 + carry;
+            difference[i] = value as u8;
+            carry = value >> 8;
+        }
+        difference
+    }
+
+    /// SHA-256 of `parts` truncated to 252 bits, which is always a canonical scalar.
+    pub fn challenge_scalar(parts: &[&[u8]]) -> PodScalar {
+        let mut challenge = hashv(parts).to_bytes();
+        challenge[31] &= 0x0f;
+        PodScalar(challenge)
+    }
+
+    fn gcd(a: i128, b: i128) -> i128 {
+        if b == 0 {
+            a.abs()
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    /// Lagrange coefficients for interpolating at `at` from shares at `indices`, scaled by the
+    /// smallest `scale` that makes them all integers.
+    ///
+    /// Returns `(scale·λᵢ, scale)`. Scaling avoids scalar inversion, which has no syscall: instead
+    /// of checking `Σ λᵢ·Pᵢ = P` the program checks `Σ (scale·λᵢ)·Pᵢ = scale·P`.
+    pub fn lagrange_coefficients(indices: &[u64], at: u64) -> Option<(Vec<i128>, i128)> {
+        let mut numerators = Vec::with_capacity(indices.len());
+        let mut denominators = Vec::with_capacity(indices.len());
+        for (position, index) in indices.iter().enumerate() {
+            let mut numerator = 1i128;
+            let mut denominator = 1i128;
+            for (other_position, other) in indices.iter().enumerate() {
+                if position == other_position {
+                    continue;
+                }
+                numerator = numerator.checked_mul(at as i128 - *other as i128)?;
+                denominator = denominator.checked_mul(*index as i128 - *other as i128)?;
+            }
+            if denominator == 0 {
+                return None;
+            }
+            numerators.push(numerator);
+            denominators.push(denominator);
+        }
+        let mut scale = 1i128;
+        for denominator in &denominators {
+            scale = scale.checked_mul(denominator.abs() / gcd(scale, *denominator))?;
+        }
+        let coefficients = numerators
+            .iter()
+            .zip(denominators.iter())
+            .map(|(numerator, denominator)| numerator.checked_mul(scale / denominator))
+            .collect::<Option<Vec<i128>>>()?;
+        Some((coefficients, scale))
+    }
+
+    /// `Σ coefficientᵢ · pointᵢ` for signed integer coefficients.
+    pub fn linear_combination(
+        coefficients: &[i128],
+        points: &[PodRistrettoPoint],
+    ) -> Option<PodRistrettoPoint> {
+        let mut sum = PodRistrettoPoint([0u8; 32]);
+        for (coefficient, point) in coefficients.iter().zip(points.iter()) {
+            let term = multiply_ristretto(&u128_scalar(coefficient.unsigned_abs()), point)?;
+            sum = if *coefficient < 0 {
+                subtract_ristretto(&sum, &term)?
+            } else {
+                add_ristretto(&sum, &term)?
+            };
+        }
+        Some(sum)
+    }
+
+    /// `a + b` for ElGamal ciphertexts, component-wise.
+    pub fn add_ciphertexts(a: &[u8; 64], b: &[u8; 64]) -> Option<[u8; 64]> {
+        let (a_1, a_2) = split_ciphertext(a);
+        let (b_1, b_2) = split_ciphertext(b);
+        let mut sum = [0u8; 64];
+        sum[..32].copy_from_slice(&add_ristretto(&a_1, &b_1)?.0);
+        sum[32..].copy_from_slice(&add_ristretto(&a_2, &b_2)?.0);
+        Some(sum)
+    }
+
+    /// `a - b` for ElGamal ciphertexts, component-wise.
+    pub fn subtract_ciphertexts(a: &[u8; 64], b: &[u8; 64]) -> Option<[u8; 64]> {
+        let (a_1, a_2) = split_ciphertext(a);
+        let (b_1, b_2) = split_ciphertext(b);
+        let mut difference = [0u8; 64];
+        difference[..32].copy_from_slice(&subtract_ristretto(&a_1, &b_1)?.0);
+        difference[32..].copy_from_slice(&subtract_ristretto(&a_2, &b_2)?.0);
+        Some(difference)
+    }
+
+    /// `scalar · ciphertext`, component-wise.
+    pub fn scale_ciphertext(scalar: &PodScalar, ciphertext: &[u8; 64]) -> Option<[u8; 64]> {
+        let (c_1, c_2) = split_ciphertext(ciphertext);
+        let mut product = [0u8; 64];
+        product[..32].copy_from_slice(&multiply_ristretto(scalar, &c_1)?.0);
+        product[32..].copy_from_slice(&multiply_ristretto(scalar, &c_2)?.0);
+        Some(product)
+    }
+
+    /// `ciphertext - value·G` for a balance-layout ciphertext `(C, D)`, which encrypts the
+    /// original value less the public `value`.
+    pub fn subtract_public_value(ciphertext: &[u8; 64], value: u128) -> Option<[u8; 64]> {
+        let (commitment, _) = split_ciphertext(ciphertext);
+        let point = multiply_ristretto(&u128_scalar(value), &PEDERSEN_G)?;
+        let mut difference = *ciphertext;
+        difference[..32].copy_from_slice(&subtract_ristretto(&commitment, &point)?.0);
+        Some(difference)
+    }
+
+    /// The encryption of a public `weight` with zero randomness, `(0, weight·G)`.
+    pub fn public_weight(weight: u64) -> Result<[u8; 64]> {
+        let point = multiply_ristretto(&amount_scalar(weight), &PEDERSEN_G)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let mut ciphertext = [0u8; 64];
+        ciphertext[32..].copy_from_slice(&point.0);
+        Ok(ciphertext)
+    }
+
+    /// Public-input hash of a ballot statement.
+    pub fn ballot_input_hash(
+        election_pubkey: &[u8; 32],
+        ballot: &[u8; 64],
+        weight: &[u8; 64],
+    ) -> [u8; 32] {
+        public_input_hash(&[election_pubkey, ballot, weight])
+    }
+
+    /// Fiat–Shamir challenge for a ballot proof.
+    pub fn ballot_proof_challenge(
+        domain: &[u8; 32],
+        election_pubkey: &[u8; 32],
+        ballot: &[u8; 64],
+        weight: &[u8; 64],
+        commitments: &[u8; 128],
+    ) -> PodScalar {
+        challenge_scalar(&[
+            BALLOT_PROOF_DOMAIN,
+            domain,
+            election_pubkey,
+            ballot,
+            weight,
+            commitments,
+        ])
+    }
+
+    /// Verifies that `ballot` encrypts either 0 or the same value as `weight` under the
+    /// election key `Y`. `weight` is an encryption of the voter's weight, or
+    /// `public_weight(w)` when the weight is public.
+    ///
+    /// The proof is a disjunctive Chaum–Pedersen proof that either `ballot` or
+    /// `ballot - weight` is an encryption of zero, i.e. a Diffie–Hellman tuple `(r·G, r·Y)`.
+    /// The branch challenges satisfy `c₀ + c₁ = c`, so only one branch can be real; `c₁` is
+    /// derived rather than sent.
+    pub fn verify_ballot(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        election_pubkey: &[u8; 32],
+        ballot: &[u8; 64],
+        weight: &[u8; 64],
+    ) -> Result<()> {
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::Vote,
+            domain,
+            &ballot_input_hash(election_pubkey, ballot, weight),
+        )?;
+        if zk_proof.len() != BALLOT_PROOF_LEN {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let word = |i: usize| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&zk_proof[32 * i..32 * (i + 1)]);
+            bytes
+        };
+        let mut commitments = [0u8; 128];
+        commitments.copy_from_slice(&zk_proof[..128]);
+        let c0 = word(4);
+        if !is_canonical_scalar(&c0) {
+            return Err(ErrorCode::InvalidZKProof.into());
+        }
+        let challenge =
+            ballot_proof_challenge(domain, election_pubkey, ballot, weight, &commitments);
+        let c1 = scalar_sub(&challenge.0, &c0);
+
+        let election_pubkey = PodRistrettoPoint(*election_pubkey);
+        let (c_1, c_2) = split_ciphertext(ballot);
+        let (w_1, w_2) = split_ciphertext(weight);
+        let shifted_1 = subtract_ristretto(&c_1, &w_1).ok_or(ErrorCode::InvalidZKProof)?;
+        let shifted_2 = subtract_ristretto(&c_2, &w_2).ok_or(ErrorCode::InvalidZKProof)?;
+        let branches = [
+            (word(0), word(1), PodScalar(c0), word(5), c_1, c_2),
+            (
+                word(2),
+                word(3),
+                PodScalar(c1),
+                word(6),
+                shifted_1,
+                shifted_2,
+            ),
+        ];
+        for (a, b, c, z, m_1, m_2) in branches {
+            let z = PodScalar(z);
+            // z·G == A + c·M₁ and z·Y == B + c·M₂
+            for (base, commitment, target) in [(PEDERSEN_G, a, m_1), (election_pubkey, b, m_2)] {
+                let lhs = multiply_ristretto(&z, &base).ok_or(ErrorCode::InvalidZKProof)?;
+                let rhs = multiply_ristretto(&c, &target)
+                    .and_then(|point| add_ristretto(&PodRistrettoPoint(commitment), &point))
+                    .ok_or(ErrorCode::InvalidZKProof)?;
+                if lhs != rhs {
+                    return Err(ErrorCode::InvalidZKProof.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Public-input hash of a weight-commitment statement.
+    pub fn weight_commitment_input_hash(
+        election_pubkey: &[u8; 32],
+        weight_commitment: &[u8; 32],
+        weight: &[u8; 64],
+    ) -> [u8; 32] {
+        public_input_hash(&[election_pubkey, weight_commitment, weight])
+    }
+
+    /// Fiat–Shamir challenge for a weight-commitment proof.
+    pub fn weight_commitment_challenge(
+        domain: &[u8; 32],
+        election_pubkey: &[u8; 32],
+        weight_commitment: &[u8; 32],
+        weight: &[u8; 64],
+        commitments: &[u8; 96],
+    ) -> PodScalar {
+        challenge_scalar(&[
+            WEIGHT_COMMITMENT_DOMAIN,
+            domain,
+            election_pubkey,
+            weight_commitment,
+            weight,
+            commitments,
+        ])
+    }
+
+    /// Verifies that the Pedersen commitment `C = w·G + t·H` and the election-key ciphertext
+    /// `weight = (r·G, w·G + r·Y)` hide the same `w`.
+    ///
+    /// This carries a weight that was range-checked as a Pedersen commitment into the
+    /// ElGamal form the tally is kept in. The proof shows knowledge of `(w, t, r)` with
+    /// commitments `A₁ = a·G + b·H`, `A₂ = c·G`, `A₃ = a·G + c·Y`.
+    pub fn verify_weight_commitment(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        election_pubkey: &[u8; 32],
+        weight_commitment: &[u8; 32],
+        weight: &[u8; 64],
+    ) -> Result<()> {
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::StakeWeight,
+            domain,
+            &weight_commitment_input_hash(election_pubkey, weight_commitment, weight),
+        )?;
+        if zk_proof.len() != WEIGHT_COMMITMENT_PROOF_LEN {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let point = |i: usize| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&zk_proof[32 * i..32 * (i + 1)]);
+            PodRistrettoPoint(bytes)
+        };
+        let scalar = |i: usize| PodScalar(point(i).0);
+        let mut commitments = [0u8; 96];
+        commitments.copy_from_slice(&zk_proof[..96]);
+        let challenge = weight_commitment_challenge(
+            domain,
+            election_pubkey,
+            weight_commitment,
+            weight,
+            &commitments,
+        );
+        let (a_1, a_2, a_3) = (point(0), point(1), point(2));
+        let (z_w, z_t, z_r) = (scalar(3), scalar(4), scalar(5));
+        let election_pubkey = PodRistrettoPoint(*election_pubkey);
+        let (w_1, w_2) = split_ciphertext(weight);
+
+        let combine =
+            |x: &PodScalar, p: &PodRistrettoPoint, y: &PodScalar, q: &PodRistrettoPoint| {
+                add_ristretto(&multiply_ristretto(x, p)?, &multiply_ristretto(y, q)?)
+            };
+        let shifted = |a: &PodRistrettoPoint, target: &PodRistrettoPoint| {
+            add_ristretto(a, &multiply_ristretto(&challenge, target)?)
+        };
+        // z_w·G + z_t·H == A₁ + c·C, z_r·G == A₂ + c·W₁ and z_w·G + z_r·Y == A₃ + c·W₂
+        let checks = [
+            (
+                combine(&z_w, &PEDERSEN_G, &z_t, &PEDERSEN_H),
+                shifted(&a_1, &PodRistrettoPoint(*weight_commitment)),
+            ),
+            (multiply_ristretto(&z_r, &PEDERSEN_G), shifted(&a_2, &w_1)),
+            (
+                combine(&z_w, &PEDERSEN_G, &z_r, &election_pubkey),
+                shifted(&a_3, &w_2),
+            ),
+        ];
+        for (lhs, rhs) in checks {
+            if lhs.is_none() || lhs != rhs {
+                return Err(ErrorCode::InvalidZKProof.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Byte length of a decryption-share proof over `count` ciphertexts:
+    /// `A || B₁ || … || B_count || z`, 32 bytes each.
+    pub fn decryption_share_proof_len(count: usize) -> usize {
+        32 * (count + 2)
+    }
+
+    /// Public-input hash of a decryption-share statement.
+    pub fn decryption_share_input_hash(
+        share_pubkey: &[u8; 32],
+        ciphertexts_c1: &[[u8; 32]],
+        shares: &[[u8; 32]],
+    ) -> [u8; 32] {
+        let mut inputs: Vec<&[u8]> = vec![share_pubkey];
+        inputs.extend(ciphertexts_c1.iter().map(|c1| &c1[..]));
+        inputs.extend(shares.iter().map(|share| &share[..]));
+        public_input_hash(&inputs)
+    }
+
+    /// Fiat–Shamir challenge for a decryption-share proof; `commitments` is `A || B₁ || …`.
+    pub fn decryption_share_challenge(
+        domain: &[u8; 32],
+        share_pubkey: &[u8; 32],
+        ciphertexts_c1: &[[u8; 32]],
+        shares: &[[u8; 32]],
+        commitments: &[u8],
+    ) -> PodScalar {
+        let mut parts: Vec<&[u8]> = vec![DECRYPTION_SHARE_DOMAIN, domain, share_pubkey];
+        parts.extend(ciphertexts_c1.iter().map(|c1| &c1[..]));
+        parts.extend(shares.iter().map(|share| &share[..]));
+        parts.push(commitments);
+        challenge_scalar(&parts)
+    }
+
+    /// Verifies a committee member's partial decryptions `sharesⱼ = xᵢ·C₁ⱼ` of one or more
+    /// ciphertexts, with a batched DLEQ proof that `log_G(Yᵢ) = log_C₁ⱼ(sharesⱼ)` for every
+    /// `j`, where `Yᵢ = xᵢ·G` is the member's registered share key.
+    pub fn verify_decryption_share(
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        share_pubkey: &[u8; 32],
+        ciphertexts_c1: &[[u8; 32]],
+        shares: &[[u8; 32]],
+    ) -> Result<()> {
+        if ciphertexts_c1.len() != shares.len() {
+            return Err(ErrorCode::InvalidPublicInputs.into());
+        }
+        let zk_proof = open_envelope(
+            envelope,
+            ProofSystem::Sigma,
+            Circuit::DecryptionShare,
+            domain,
+            &decryption_share_input_hash(share_pubkey, ciphertexts_c1, shares),
+        )?;
+        if zk_proof.len() != decryption_share_proof_len(shares.len()) {
+            return Err(ErrorCode::MalformedProof.into());
+        }
+        let point = |i: usize| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&zk_proof[32 * i..32 * (i + 1)]);
+            bytes
+        };
+        let commitments = &zk_proof[..zk_proof.len() - 32];
+        let challenge =
+            decryption_share_challenge(domain, share_pubkey, ciphertexts_c1, shares, commitments);
+        let z = PodScalar(point(shares.len() + 1));
+
+        // z·G == A + c·Yᵢ and z·C₁ⱼ == Bⱼ + c·sharesⱼ
+        let statements = std::iter::once((PEDERSEN_G, point(0), *share_pubkey)).chain(
+            ciphertexts_c1
+                .iter()
+                .zip(shares.iter())
+                .enumerate()
+                .map(|(j, (c1, share))| (PodRistrettoPoint(*c1), point(j + 1), *share)),
+        );
+        for (base, commitment, target) in statements {
+            let lhs = multiply_ristretto(&z, &base).ok_or(ErrorCode::InvalidZKProof)?;
+            let rhs = multiply_ristretto(&challenge, &PodRistrettoPoint(target))
+                .and_then(|point| add_ristretto(&PodRistrettoPoint(commitment), &point))
+                .ok_or(ErrorCode::InvalidZKProof)?;
+            if lhs != rhs {
+                return Err(ErrorCode::InvalidZKProof.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the encrypted `tally` decrypts to `votes_for`, given verified decryption
+    /// shares `(member index, xᵢ·C₁)` from at least `threshold` committee members.
+    ///
+    /// With scaled Lagrange coefficients `s·λᵢ`, `Σ s·λᵢ·(xᵢ·C₁) = s·x·C₁`, so the plaintext
+    /// satisfies `(s·votes_for)·G = s·C₂ - s·x·C₁`.
+    pub fn verify_tally(
+        tally: &[u8; 64],
+        shares: &[(u64, [u8; 32])],
+        votes_for: u64,
+    ) -> Result<()> {
+        let indices: Vec<u64> = shares.iter().map(|(index, _)| *index).collect();
+        let points: Vec<PodRistrettoPoint> = shares
+            .iter()
+            .map(|(_, share)| PodRistrettoPoint(*share))
+            .collect();
+        let (coefficients, scale) =
+            lagrange_coefficients(&indices, 0).ok_or(ErrorCode::InvalidTallyCommittee)?;
+        let (_, c_2) = split_ciphertext(tally);
+        let scaled_mask =
+            linear_combination(&coefficients, &points).ok_or(ErrorCode::InvalidTally)?;
+        let scaled_message = multiply_ristretto(&u128_scalar(scale as u128), &c_2)
+            .and_then(|point| subtract_ristretto(&point, &scaled_mask))
+            .ok_or(ErrorCode::InvalidTally)?;
+        let scaled_votes = (scale as u128)
+            .checked_mul(votes_for as u128)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let expected = multiply_ristretto(&u128_scalar(scaled_votes), &PEDERSEN_G)
+            .ok_or(ErrorCode::InvalidTally)?;
+        if expected != scaled_message {
+            return Err(ErrorCode::InvalidTally.into());
+        }
+        Ok(())
+    }
+
+    /// Poseidon hash of two BN254 field elements, as used by the identity Merkle tree.
+    pub fn poseidon_pair(left: &[u8; 32], right: &[u8; 32]) -> Result<[u8; 32]> {
+        poseidon::hashv(Parameters::Bn254X5, Endianness::BigEndian, &[left, right])
+            .map(|hash| hash.to_bytes())
+            .map_err(|_| ErrorCode::InvalidIdentityCommitment.into())
+    }
+
+    /// Public inputs of the identity circuit, in order: the Merkle `root`, the
+    /// `nullifier_hash`, `hash_to_field(signal)`, and the external nullifier
+    /// `hash_to_field(domain)`, which scopes the nullifier to one instruction and account.
+    pub fn identity_public_inputs(
+        domain: &[u8; 32],
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        signal: &[u8],
+    ) -> [[u8; 32]; 4] {
+        [
+            root,
+            nullifier_hash,
+            hash_to_field(&[signal]),
+            hash_to_field(&[domain]),
+        ]
+    }
+
+    /// Verifies a zero-knowledge identity proof to prevent Sybil attacks.
+    ///
+    /// The Groth16 `Identity` circuit proves knowledge of an identity whose commitment is a
+    /// leaf of the tree with root `root`; `root` must be one of the registry's recent roots.
+    /// `signal` is the payload the proof authorizes (e.g. the vote), so it cannot be swapped.
+    pub fn verify_identity_proof(
+        identity_registry: &IdentityRegistry,
+        vk_registry: &VerifyingKeyRegistry,
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        signal: &[u8],
+    ) -> Result<()> {
+        if !identity_registry.is_known_root(&root) {
+            return Err(ErrorCode::UnknownIdentityRoot.into());
+        }
+        verify_groth16(
+            vk_registry,
+            Circuit::Identity,
+            envelope,
+            domain,
+            &identity_public_inputs(domain, root, nullifier_hash, signal),
+        )
+    }
+
+    /// Leaf of a reward epoch's Merkle tree: the claimant's `commitment` and their reward
+    /// encrypted to them.
+    pub fn reward_leaf(commitment: &[u8; 32], encrypted_reward: &[u8; 64]) -> Result<[u8; 32]> {
+        poseidon_pair(commitment, &hash_to_field(&[encrypted_reward]))
+    }
+
+    /// Public inputs of the reward-claim circuit, in order: the epoch's Merkle `root`, the
+    /// claimed leaf's `nullifier_hash`, the hashed amount ciphertexts of the `transfer` paying
+    /// the reward out, which the circuit proves encrypt the amount the leaf's reward decrypts
+    /// to, `hash_to_field(recipient)` so the claim cannot be redirected, and
+    /// `hash_to_field(domain)`. Neither the leaf nor the amount is revealed.
+    pub fn reward_claim_public_inputs(
+        domain: &[u8; 32],
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        transfer: &TransferProofContext,
+        recipient: &Pubkey,
+    ) -> [[u8; 32]; 5] {
+        [
+            root,
+            nullifier_hash,
+            hash_to_field(&[&transfer.ciphertext_lo.0 .0, &transfer.ciphertext_hi.0 .0]),
+            hash_to_field(&[recipient.as_ref()]),
+            hash_to_field(&[domain]),
+        ]
+    }
+}
+
+/// Update the pool's reward rate. Only reachable by executing a passed governance proposal.
+fn update_reward_params(pool: &mut LiquidityPool, new_reward_rate: u64, now: i64) -> Result<()> {
+    // Rewards up to now accrue at the old rate.
+    pool.accrue_rewards(now)?;
+    pool.set_reward_rate(new_reward_rate, now);
+    Ok(())
+}
+
+/// Accrues the pool's rewards to `now` and settles the position's pending rewards there,
+/// before its balance or tier changes. Each stretch since the last settlement earns at the
+/// multiplier of the tier the position was in (see [`LiquidityPool::reward_per_unit`]).
+fn checkpoint_rewards(
+    pool: &mut LiquidityPool,
+    vault: &AssetVault,
+    user_stake: &mut UserStake,
+    now: i64,
+) -> Result<()> {
+    pool.accrue_rewards(now)?;
+    let reward_per_unit = pool.reward_per_unit(vault, user_stake, now)?;
+    let pending = user_stake.pending_rewards(reward_per_unit)?;
+    user_stake.settle_rewards(now, pool.acc_reward_per_share, &pending)
+}
+
+/// Checks a stake of `amount` into the position in `accounts`, before anything is moved.
+/// Returns the amount's encryption under the pool auditor's key, if the pool has one.
+fn verify_stake(
+    accounts: &StakeAccounts,
+    amount: u64,
+    zk_proof: &ProofEnvelope,
+    equality_proof: &ProofEnvelope,
+    new_confidential_balance: &[u8; 64],
+    balance_commitment: &[u8; 32],
+    auditor_amount: Option<&AuditorAmount>,
+) -> Result<Option<[u8; 64]>> {
+    confidential_token::ConfidentialTokenAccount::load(
+        &accounts.pool_token_account.to_account_info(),
+    )?
+    .require_credits_allowed()?;
+
+    // Verify the provided ZK proof for the stake.
+    let domain = zk_utils::domain_separator("stake", &accounts.pool.key());
+    zk_utils::verify_confidential_balance(
+        zk_proof,
+        &domain,
+        amount,
+        zk_utils::BalanceUpdate::Credit,
+        accounts.user_stake.elgamal_pubkey,
+        accounts.user_stake.confidential_balance,
+        *new_confidential_balance,
+    )?;
+    zk_utils::verify_ciphertext_commitment_equality(
+        equality_proof,
+        &domain,
+        &accounts.user_stake.elgamal_pubkey,
+        &[*new_confidential_balance],
+        &[*balance_commitment],
+    )?;
+
+    accounts
+        .pool
+        .verify_auditor_amount(auditor_amount, &domain, amount)
+}
+
+/// Moves a stake verified by [`verify_stake`] into the pool and records the new balance.
+fn apply_stake(
+    accounts: &mut StakeAccounts,
+    amount: u64,
+    new_confidential_balance: [u8; 64],
+    balance_commitment: [u8; 32],
+    auditor_ciphertext: Option<&[u8; 64]>,
+    clock: &Clock,
+) -> Result<()> {
+    // Move the tokens into the pool's account and on into its pending confidential balance.
+    let token_program = accounts.confidential_token_program.to_account_info();
+    let mint = accounts.token_mint.to_account_info();
+    let decimals = accounts.token_mint.decimals;
+    let pool_token_account = accounts.pool_token_account.to_account_info();
+    confidential_token::transfer_checked(
+        &token_program,
+        &accounts.user_token_account.to_account_info(),
+        &mint,
+        &pool_token_account,
+        &accounts.user.to_account_info(),
+        amount,
+        decimals,
+        &[],
+    )?;
+    let seeds = accounts.pool.signer_seeds();
+    confidential_token::deposit(
+        &token_program,
+        &pool_token_account,
+        &mint,
+        &accounts.pool.to_account_info(),
+        amount,
+        decimals,
+        &[&seeds[..]],
+    )?;
+
+    // Fold the balance change into the pool's totals without revealing the amount.
+    accounts.pool.record_balance_change(
+        &accounts.user_stake.balance_commitment,
+        &balance_commitment,
+        zk_utils::BalanceUpdate::Credit,
+        auditor_ciphertext,
+        accounts.vault.reward_weight,
+    )?;
+
+    // Settle the rewards earned so far; the stake restarts the position's tier.
+    checkpoint_rewards(
+        &mut accounts.pool,
+        &accounts.vault,
+        &mut accounts.user_stake,
+        clock.unix_timestamp,
+    )?;
+
+    // Update the user's confidential balance commitment and restart the lockup.
+    accounts.user_stake.record_stake(
+        new_confidential_balance,
+        balance_commitment,
+        clock.slot,
+        clock.unix_timestamp,
+    );
+    Ok(())
+}
+
+/// Splits the accounts passed to `batch_stake` into the entries' accounts and the proof
+/// buffers after them. Each buffer `proofs` reads from is passed once, however many entries
+/// read from it.
+pub fn split_proof_buffers<'a, 'info, 'p>(
+    accounts: &'a [AccountInfo<'info>],
+    proofs: impl IntoIterator<Item = &'p ProofSource>,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let buffered = proofs
+        .into_iter()
+        .filter_map(|proof| match proof {
+            ProofSource::Buffer(key) => Some(key),
+            ProofSource::Inline(_) => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .len();
+    let split = accounts
+        .len()
+        .checked_sub(buffered)
+        .ok_or(ErrorCode::BatchLengthMismatch)?;
+    Ok(accounts.split_at(split))
+}
+
+/// Loads `count` sets of [`StakeAccounts`] from `accounts`, as passed to `batch_stake`,
+/// with the same checks `stake` makes. Entries may share a pool, but each position (pool and
+/// mint) may appear only once.
+pub fn load_stake_entries<'info>(
+    accounts: &'info [AccountInfo<'info>],
+    count: usize,
+) -> Result<Vec<StakeAccounts<'info>>> {
+    let mut remaining = accounts;
+    let mut entries: Vec<StakeAccounts> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let entry = StakeAccounts::try_accounts(
+            &crate::ID,
+            &mut remaining,
+            &[],
+            &mut StakeAccountsBumps::default(),
+            &mut BTreeSet::new(),
+        )?;
+        if entries
+            .iter()
+            .any(|other| other.user_stake.key() == entry.user_stake.key())
+        {
+            return Err(ErrorCode::DuplicateBatchEntry.into());
+        }
+        entries.push(entry);
+    }
+    if !remaining.is_empty() {
+        return Err(ErrorCode::BatchLengthMismatch.into());
+    }
+    Ok(entries)
+}
+
+/// Replace the pool's lockup policy. Only reachable by executing a passed governance proposal.
+/// Unbonding requests already made keep the penalty they were settled with.
+fn update_lockup_policy(pool: &mut LiquidityPool, lockup: LockupPolicy) -> Result<()> {
+    lockup.validate()?;
+    pool.lockup = lockup;
+    Ok(())
+}
+
+/// Replace the pool's reward tiers. Only reachable by executing a passed governance proposal.
+fn update_reward_tiers(pool: &mut LiquidityPool, reward_tiers: RewardTiers) -> Result<()> {
+    reward_tiers.validate()?;
+    pool.reward_tiers = reward_tiers;
+    Ok(())
+}
+
+/// CPI helpers for Token-2022 and its confidential transfer extension.
+///
+/// Each `*_instruction` builder checks the program id and lays the accounts out the way
+/// Token-2022 expects; the wrapper of the same name invokes it. `signer_seeds` is empty when
+/// `authority` signs the transaction itself and holds the pool PDA's seeds when the pool signs.
+/// `new_decryptable_available_balance` arguments are the account owner's 36-byte authenticated
+/// encryption of the resulting available balance, which only the holder of the account's
+/// encryption key can produce.
+pub mod confidential_token {
+    use super::*;
+    use anchor_lang::solana_program::instruction::Instruction;
+    use anchor_lang::solana_program::program::invoke_signed;
+    use anchor_spl::token_2022::spl_token_2022;
+    use spl_token_2022::extension::confidential_transfer::instruction as confidential;
+    use spl_token_2022::extension::confidential_transfer::ConfidentialTransferAccount;
+    use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+    use spl_token_2022::proof::ProofLocation;
+    use spl_token_2022::solana_zk_token_sdk::zk_token_elgamal::pod::AeCiphertext;
+
+    fn invoke(
+        instruction: &Instruction,
+        accounts: &[AccountInfo],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        invoke_signed(instruction, accounts, signer_seeds).map_err(Into::into)
+    }
+
+    /// Moves `amount` of a public balance from `source` to `destination`.
+    pub fn transfer_checked_instruction(
+        token_program: &Pubkey,
+        source: &Pubkey,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> Result<Instruction> {
+        spl_token_2022::check_program_account(token_program)?;
+        spl_token_2022::instruction::transfer_checked(
+            token_program,
+            source,
+            mint,
+            destination,
+            authority,
+            &[],
+            amount,
+            decimals,
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer_checked<'info>(
+        token_program: &AccountInfo<'info>,
+        source: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        destination: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        amount: u64,
+        decimals: u8,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = transfer_checked_instruction(
+            token_program.key,
+            source.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            amount,
+            decimals,
+        )?;
+        let accounts = [
+            source.clone(),
+            mint.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Mints `amount` to the public balance of `destination`.
+    pub fn mint_to_checked_instruction(
+        token_program: &Pubkey,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> Result<Instruction> {
+        spl_token_2022::check_program_account(token_program)?;
+        spl_token_2022::instruction::mint_to_checked(
+            token_program,
+            mint,
+            destination,
+            authority,
+            &[],
+            amount,
+            decimals,
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn mint_to_checked<'info>(
+        token_program: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        destination: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        amount: u64,
+        decimals: u8,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = mint_to_checked_instruction(
+            token_program.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            amount,
+            decimals,
+        )?;
+        let accounts = [
+            mint.clone(),
+            destination.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Moves `amount` of `token_account`'s public balance into its pending confidential
+    /// balance.
+    pub fn deposit_instruction(
+        token_program: &Pubkey,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> Result<Instruction> {
+        confidential::deposit(
+            token_program,
+            token_account,
+            mint,
+            amount,
+            decimals,
+            authority,
+            &[],
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn deposit<'info>(
+        token_program: &AccountInfo<'info>,
+        token_account: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        amount: u64,
+        decimals: u8,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = deposit_instruction(
+            token_program.key,
+            token_account.key,
+            mint.key,
+            authority.key,
+            amount,
+            decimals,
+        )?;
+        let accounts = [
+            token_account.clone(),
+            mint.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Credits `token_account`'s pending balance to its available balance.
+    /// `expected_pending_balance_credit_counter` is the counter the new decryptable balance
+    /// was computed at, so credits that arrive in between are left pending.
+    pub fn apply_pending_balance_instruction(
+        token_program: &Pubkey,
+        token_account: &Pubkey,
+        authority: &Pubkey,
+        expected_pending_balance_credit_counter: u64,
+        new_decryptable_available_balance: [u8; 36],
+    ) -> Result<Instruction> {
+        confidential::inner_apply_pending_balance(
+            token_program,
+            token_account,
+            expected_pending_balance_credit_counter,
+            AeCiphertext(new_decryptable_available_balance),
+            authority,
+            &[],
+        )
+        .map_err(Into::into)
+    }
+
+    pub fn apply_pending_balance<'info>(
+        token_program: &AccountInfo<'info>,
+        token_account: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        expected_pending_balance_credit_counter: u64,
+        new_decryptable_available_balance: [u8; 36],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = apply_pending_balance_instruction(
+            token_program.key,
+            token_account.key,
+            authority.key,
+            expected_pending_balance_credit_counter,
+            new_decryptable_available_balance,
+        )?;
+        let accounts = [
+            token_account.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Grows `token_account` to hold the confidential-transfer extension, with `payer` paying
+    /// the extra rent.
+    pub fn reallocate_instruction(
+        token_program: &Pubkey,
+        token_account: &Pubkey,
+        payer: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<Instruction> {
+        spl_token_2022::instruction::reallocate(
+            token_program,
+            token_account,
+            payer,
+            authority,
+            &[],
+            &[ExtensionType::ConfidentialTransferAccount],
+        )
+        .map_err(Into::into)
+    }
+
+    pub fn reallocate<'info>(
+        token_program: &AccountInfo<'info>,
+        token_account: &AccountInfo<'info>,
+        payer: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        system_program: &AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = reallocate_instruction(
+            token_program.key,
+            token_account.key,
+            payer.key,
+            authority.key,
+        )?;
+        let accounts = [
+            token_account.clone(),
+            payer.clone(),
+            system_program.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Configures `token_account` for confidential transfers under the ElGamal key whose
+    /// `PubkeyValidityData` proof is verified into `proof_context`. `decryptable_zero_balance`
+    /// is zero encrypted under the account's authenticated-encryption key.
+    pub fn configure_account_instruction(
+        token_program: &Pubkey,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        decryptable_zero_balance: [u8; 36],
+        proof_context: &Pubkey,
+    ) -> Result<Instruction> {
+        confidential::inner_configure_account(
+            token_program,
+            token_account,
+            mint,
+            AeCiphertext(decryptable_zero_balance)
+                .try_into()
+                .map_err(|_| ProgramError::InvalidArgument)?,
+            u64::MAX,
+            authority,
+            &[],
+            ProofLocation::ContextStateAccount(proof_context),
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn configure_account<'info>(
+        token_program: &AccountInfo<'info>,
+        token_account: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        decryptable_zero_balance: [u8; 36],
+        proof_context: &AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = configure_account_instruction(
+            token_program.key,
+            token_account.key,
+            mint.key,
+            authority.key,
+            decryptable_zero_balance,
+            proof_context.key,
+        )?;
+        let accounts = [
+            token_account.clone(),
+            mint.clone(),
+            proof_context.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Transfers a hidden amount between confidential balances. The `TransferData` proof
+    /// must already be verified into the `proof_context` context-state account; the amount
+    /// is only known to the proof, so callers that need a public amount use [`withdraw`] and
+    /// [`transfer_checked`] instead.
+    pub fn transfer_instruction(
+        token_program: &Pubkey,
+        source: &Pubkey,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        new_source_decryptable_available_balance: [u8; 36],
+        proof_context: &Pubkey,
+    ) -> Result<Instruction> {
+        confidential::inner_transfer(
+            token_program,
+            source,
+            mint,
+            destination,
+            AeCiphertext(new_source_decryptable_available_balance),
+            authority,
+            &[],
+            ProofLocation::ContextStateAccount(proof_context),
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer<'info>(
+        token_program: &AccountInfo<'info>,
+        source: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        destination: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        new_source_decryptable_available_balance: [u8; 36],
+        proof_context: &AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = transfer_instruction(
+            token_program.key,
+            source.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            new_source_decryptable_available_balance,
+            proof_context.key,
+        )?;
+        let accounts = [
+            source.clone(),
+            mint.clone(),
+            destination.clone(),
+            proof_context.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// Moves `amount` of `token_account`'s available confidential balance to its public
+    /// balance. The `WithdrawData` proof must already be verified into `proof_context`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw_instruction(
+        token_program: &Pubkey,
+        token_account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+        decimals: u8,
+        new_decryptable_available_balance: [u8; 36],
+        proof_context: &Pubkey,
+    ) -> Result<Instruction> {
+        confidential::inner_withdraw(
+            token_program,
+            token_account,
+            mint,
+            amount,
+            decimals,
+            AeCiphertext(new_decryptable_available_balance),
+            authority,
+            &[],
+            ProofLocation::ContextStateAccount(proof_context),
+        )
+        .map_err(Into::into)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw<'info>(
+        token_program: &AccountInfo<'info>,
+        token_account: &AccountInfo<'info>,
+        mint: &AccountInfo<'info>,
+        authority: &AccountInfo<'info>,
+        amount: u64,
+        decimals: u8,
+        new_decryptable_available_balance: [u8; 36],
+        proof_context: &AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        let instruction = withdraw_instruction(
+            token_program.key,
+            token_account.key,
+            mint.key,
+            authority.key,
+            amount,
+            decimals,
+            new_decryptable_available_balance,
+            proof_context.key,
+        )?;
+        let accounts = [
+            token_account.clone(),
+            mint.clone(),
+            proof_context.clone(),
+            authority.clone(),
+            token_program.clone(),
+        ];
+        invoke(&instruction, &accounts, signer_seeds)
+    }
+
+    /// The `ConfidentialTransferAccount` extension of a Token-2022 account.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct ConfidentialTokenAccount {
+        pub approved: bool,
+        pub elgamal_pubkey: [u8; 32],
+        pub pending_balance_lo: [u8; 64],
+        pub pending_balance_hi: [u8; 64],
+        pub available_balance: [u8; 64],
+        pub decryptable_available_balance: [u8; 36],
+        pub allow_confidential_credits: bool,
+        pub pending_balance_credit_counter: u64,
+    }
+
+    impl ConfidentialTokenAccount {
+        /// Reads the extension from a token account's data. Fails with
+        /// `ConfidentialTransferNotConfigured` if the account was never configured for
+        /// confidential transfers.
+        pub fn unpack(data: &[u8]) -> Result<Self> {
+            let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(data)?;
+            let extension = state
+                .get_extension::<ConfidentialTransferAccount>()
+                .map_err(|_| ErrorCode::ConfidentialTransferNotConfigured)?;
+            Ok(Self {
+                approved: extension.approved.into(),
+                elgamal_pubkey: extension.elgamal_pubkey.0,
+                pending_balance_lo: extension.pending_balance_lo.0,
+                pending_balance_hi: extension.pending_balance_hi.0,
+                available_balance: extension.available_balance.0,
+                decryptable_available_balance: extension.decryptable_available_balance.0,
+                allow_confidential_credits: extension.allow_confidential_credits.into(),
+                pending_balance_credit_counter: extension.pending_balance_credit_counter.into(),
+            })
+        }
+
+        pub fn load(account: &AccountInfo) -> Result<Self> {
+            Self::unpack(&account.try_borrow_data()?)
+        }
+
+        /// Checks the account can receive confidential deposits: the mint authority approved
+        /// it and its owner has not disabled confidential credits.
+        pub fn require_credits_allowed(&self) -> Result<()> {
+            if !self.approved {
+                return Err(ErrorCode::ConfidentialAccountNotApproved.into());
+            }
+            if !self.allow_confidential_credits {
+                return Err(ErrorCode::ConfidentialCreditsDisabled.into());
+            }
+            Ok(())
+        }
+    }
+}
+
+// ---------------------------------------------------------------------
+// Program Declaration & Instruction Handlers
+// ---------------------------------------------------------------------
+
+#[program]
+pub mod zk_liquidity_pool {
+    use super::*;
+
+    /// Initializes the liquidity pool.
+    /// With an `auditor_elgamal_pubkey`, every stake and withdrawal must also encrypt its
+    /// amount to the auditor, so the auditor can decrypt the pool's total.
+    /// Early-withdraw penalties are paid to token accounts owned by `treasury`.
+    /// The pool accepts stakes in the mints added with `add_asset`, including its own.
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        auditor_elgamal_pubkey: Option<[u8; 32]>,
+        lockup: LockupPolicy,
+        reward_tiers: RewardTiers,
+    ) -> Result<()> {
+        lockup.validate()?;
+        reward_tiers.validate()?;
+        let pool = &mut ctx.accounts.pool;
+        pool.lockup = lockup;
+        pool.reward_tiers = reward_tiers;
+        pool.treasury = ctx.accounts.treasury.key();
+        pool.authority = *ctx.accounts.authority.key;
+        pool.token_mint = ctx.accounts.token_mint.key();
+        pool.total_commitment = [0u8; 32];
+        pool.auditor_elgamal_pubkey = auditor_elgamal_pubkey;
+        pool.auditor_total = [0u8; 64];
+        let now = Clock::get()?.unix_timestamp;
+        pool.acc_reward_per_share = 0;
+        pool.last_reward_timestamp = now;
+        pool.reward_rate_history = Vec::new();
+        pool.set_reward_rate(0, now);
+        pool.bump = ctx.bumps.pool;
+        Ok(())
+    }
+
+    /// Creates the pool's (empty) Groth16 verifying-key registry.
+    pub fn initialize_verifying_key_registry(
+        ctx: Context<InitializeVerifyingKeyRegistry>,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.pool = ctx.accounts.pool.key();
+        registry.bump = ctx.bumps.registry;
+        registry.keys = Vec::new();
+        Ok(())
+    }
+
+    /// Registers the Groth16 verifying key for `circuit`.
+    /// Registering a circuit that already has a key rotates it and bumps its version.
+    pub fn register_verifying_key(
+        ctx: Context<RegisterVerifyingKey>,
+        circuit: Circuit,
+        key: Groth16VerifyingKey,
+    ) -> Result<()> {
+        if key.ic.is_empty() || key.ic.len() > zk_utils::MAX_PUBLIC_INPUTS + 1 {
+            return Err(ErrorCode::InvalidVerifyingKey.into());
+        }
+
+        let registry = &mut ctx.accounts.registry;
+        match registry.keys.iter_mut().find(|entry| entry.circuit == circuit) {
+            Some(entry) => {
+                entry.version = entry.version.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+                entry.key = key;
+            }
+            None => registry.keys.push(RegisteredVerifyingKey {
+                circuit,
+                version: 1,
+                key,
+            }),
+        }
+
+        let new_len = 8 + registry.space();
+        grow_account(
+            &ctx.accounts.registry.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            new_len,
+        )?;
+        Ok(())
+    }
+
+    /// Opens a buffer for uploading a serialized `ProofEnvelope` of `len` bytes that does not
+    /// fit in one transaction alongside its instruction's accounts. `hash` is the SHA-256 of
+    /// the serialized envelope, committed up front so the buffer only seals once its contents
+    /// match it.
+    pub fn open_proof_buffer(
+        ctx: Context<OpenProofBuffer>,
+        buffer_id: u64,
+        len: u32,
+        hash: [u8; 32],
+    ) -> Result<()> {
+        if len == 0 || len as usize > ProofBuffer::MAX_DATA_LEN {
+            return Err(ErrorCode::InvalidProofBuffer.into());
+        }
+        let buffer = &mut ctx.accounts.proof_buffer;
+        buffer.owner = ctx.accounts.owner.key();
+        buffer.buffer_id = buffer_id;
+        buffer.hash = hash;
+        buffer.written = 0;
+        buffer.sealed = false;
+        buffer.bump = ctx.bumps.proof_buffer;
+        buffer.data = vec![0u8; len as usize];
+        Ok(())
+    }
+
+    /// Writes `chunk` at `offset` of a proof buffer. Chunks must be written in order, so a
+    /// chunk sent twice fails rather than being appended again. The write that fills the
+    /// buffer seals it.
+    pub fn write_proof_chunk(
+        ctx: Context<WriteProofChunk>,
+        offset: u32,
+        chunk: Vec<u8>,
+    ) -> Result<()> {
+        ctx.accounts.proof_buffer.write(offset, &chunk)
+    }
+
+    /// Closes a proof buffer, sealed or not, returning its rent to the owner.
+    pub fn close_proof_buffer(_ctx: Context<CloseProofBuffer>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Creates the pool's empty identity Merkle tree.
+    pub fn initialize_identity_registry(ctx: Context<InitializeIdentityRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.registry;
+        registry.pool = ctx.accounts.pool.key();
+        registry.authority = ctx.accounts.authority.key();
+        registry.bump = ctx.bumps.registry;
+        registry.initialize_tree()
+    }
+
+    /// Appends an identity commitment to the pool's identity tree.
+    /// Only the pool authority, acting as identity issuer, may add members.
+    pub fn append_identity_commitment(
+        ctx: Context<AppendIdentityCommitment>,
+        commitment: [u8; 32],
+    ) -> Result<()> {
+        ctx.accounts.registry.append(commitment)?;
+        Ok(())
+    }
+
+    /// Whitelists `token_mint` for staking into the pool, with its stakes held in
+    /// `vault_token_account`. A position's balance counts `reward_weight` times towards the
+    /// pool's total; the weight is fixed once the mint has stakes.
+    pub fn add_asset(ctx: Context<AddAsset>, reward_weight: u64) -> Result<()> {
+        if reward_weight == 0 {
+            return Err(ErrorCode::InvalidRewardWeight.into());
+        }
+        confidential_token::ConfidentialTokenAccount::load(
+            &ctx.accounts.vault_token_account.to_account_info(),
+        )?
+        .require_credits_allowed()?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.pool = ctx.accounts.pool.key();
+        vault.mint = ctx.accounts.token_mint.key();
+        vault.token_account = ctx.accounts.vault_token_account.key();
+        vault.reward_weight = reward_weight;
+        vault.bump = ctx.bumps.vault;
+        Ok(())
+    }
+
+    /// Opens the caller's position in the pool for one of its mints, with a zero balance.
+    /// The balance is encrypted under the ElGamal key registered on the caller's token account
+    /// for confidential transfers, which is fixed for the life of the position.
+    pub fn open_position(ctx: Context<OpenPosition>) -> Result<()> {
+        let user_account = confidential_token::ConfidentialTokenAccount::load(
+            &ctx.accounts.user_token_account.to_account_info(),
+        )?;
+        let user_stake = &mut ctx.accounts.user_stake;
+        user_stake.confidential_balance = [0u8; 64];
+        user_stake.balance_commitment = [0u8; 32];
+        user_stake.elgamal_pubkey = user_account.elgamal_pubkey;
+        user_stake.asset_mint = ctx.accounts.token_mint.key();
+        let now = Clock::get()?.unix_timestamp;
+        let acc_reward_per_share = ctx
+            .accounts
+            .pool
+            .acc_reward_per_share_at(now)
+            .ok_or(ErrorCode::MathOverflow)?;
+        user_stake.settle_rewards(now, acc_reward_per_share, &[0u8; 64])?;
+        Ok(())
+    }
+
+    /// Stake tokens into an open position using confidential transfers with multi-asset support.
+    /// `new_confidential_balance` is the position's balance plus `amount`, re-randomized by the
+    /// owner, so a position can be staked into any number of times. `equality_proof` binds
+    /// `balance_commitment` to it for the pool's commitment total.
+    /// Restarts the position's lockup; see [`UserStake::record_stake`].
+    pub fn stake(
+        ctx: Context<StakeAccounts>,
+        amount: u64,
+        zk_proof: ProofSource,
+        new_confidential_balance: [u8; 64],
+        balance_commitment: [u8; 32],
+        equality_proof: ProofSource,
+        auditor_amount: Option<AuditorAmount>,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        let equality_proof = equality_proof.resolve(ctx.remaining_accounts)?;
+        let clock = Clock::get()?;
+        let auditor_ciphertext = verify_stake(
+            ctx.accounts,
+            amount,
+            &zk_proof,
+            &equality_proof,
+            &new_confidential_balance,
+            &balance_commitment,
+            auditor_amount.as_ref(),
+        )?;
+        apply_stake(
+            ctx.accounts,
+            amount,
+            new_confidential_balance,
+            balance_commitment,
+            auditor_ciphertext.as_ref(),
+            &clock,
+        )
+    }
+
+    /// Withdraw staked tokens, subject to the pool's [`LockupPolicy`].
+    /// Withdrawals before the minimum lock pay the early-withdraw penalty to the treasury, or
+    /// are rejected if the pool has none. Pools with an unbonding period only pay out an
+    /// amount requested with [`unbond`] once the period has passed.
+    /// `equality_proof` binds `balance_commitment` to the new balance and `range_proof` must
+    /// cover it, so the balance cannot go negative.
+    ///
+    /// The pool's token account is encrypted under a key held by the pool authority, which
+    /// provides the `WithdrawData` proof in `pool_withdraw_proof` and the pool account's
+    /// `pool_decryptable_balance` after the withdrawal.
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw(
+        ctx: Context<Withdraw>,
+        amount: u64,
+        zk_proof: ProofSource,
+        new_confidential_balance: [u8; 64],
+        balance_commitment: [u8; 32],
+        equality_proof: ProofSource,
+        range_proof: ProofSource,
+        pool_decryptable_balance: [u8; 36],
+        auditor_amount: Option<AuditorAmount>,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        let equality_proof = equality_proof.resolve(ctx.remaining_accounts)?;
+        let range_proof = range_proof.resolve(ctx.remaining_accounts)?;
+        // Check the lockup and work out the early-withdraw penalty.
+        let clock = Clock::get()?;
+        let lockup = ctx.accounts.pool.lockup;
+        let penalty =
+            ctx.accounts
+                .user_stake
+                .take_withdrawal(&lockup, amount, clock.unix_timestamp)?;
+
+        // Verify the ZK proof for the withdrawal.
+        let domain = zk_utils::domain_separator("withdraw", &ctx.accounts.pool.key());
+        zk_utils::verify_confidential_balance(
+            &zk_proof,
+            &domain,
+            amount,
+            zk_utils::BalanceUpdate::Debit,
+            ctx.accounts.user_stake.elgamal_pubkey,
+            ctx.accounts.user_stake.confidential_balance,
+            new_confidential_balance,
+        )?;
+        zk_utils::verify_bounded_ciphertexts(
+            &ctx.accounts.proof_account,
+            &equality_proof,
+            &range_proof,
+            &domain,
+            &ctx.accounts.user_stake.elgamal_pubkey,
+            &[new_confidential_balance],
+            &[balance_commitment],
+        )?;
+
+        // The pool PDA owns the pool token account and signs for it.
+        let seeds = ctx.accounts.pool.signer_seeds();
+        let signer = &[&seeds[..]];
+
+        // Move the tokens out of the pool's confidential balance and back to the user.
+        let token_program = ctx.accounts.confidential_token_program.to_account_info();
+        let mint = ctx.accounts.token_mint.to_account_info();
+        let decimals = ctx.accounts.token_mint.decimals;
+        let pool_token_account = ctx.accounts.pool_token_account.to_account_info();
+        let pool_info = ctx.accounts.pool.to_account_info();
+        confidential_token::withdraw(
+            &token_program,
+            &pool_token_account,
+            &mint,
+            &pool_info,
+            amount,
+            decimals,
+            pool_decryptable_balance,
+            &ctx.accounts.pool_withdraw_proof.to_account_info(),
+            signer,
+        )?;
+        confidential_token::transfer_checked(
+            &token_program,
+            &pool_token_account,
+            &mint,
+            &ctx.accounts.user_token_account.to_account_info(),
+            &pool_info,
+            amount - penalty,
+            decimals,
+            signer,
+        )?;
+        if penalty > 0 {
+            confidential_token::transfer_checked(
+                &token_program,
+                &pool_token_account,
+                &mint,
+                &ctx.accounts.treasury.to_account_info(),
+                &pool_info,
+                penalty,
+                decimals,
+                signer,
+            )?;
+        }
+
+        // Fold the balance change into the pool's totals.
+        let auditor_ciphertext =
+            ctx.accounts
+                .pool
+                .verify_auditor_amount(auditor_amount.as_ref(), &domain, amount)?;
+        ctx.accounts.pool.record_balance_change(
+            &ctx.accounts.user_stake.balance_commitment,
+            &balance_commitment,
+            zk_utils::BalanceUpdate::Debit,
+            auditor_ciphertext.as_ref(),
+            ctx.accounts.vault.reward_weight,
+        )?;
+
+        // Settle the rewards earned on the old balance.
+        checkpoint_rewards(
+            &mut ctx.accounts.pool,
+            &ctx.accounts.vault,
+            &mut ctx.accounts.user_stake,
+            clock.unix_timestamp,
+        )?;
+
+        // Update the user's confidential balance commitment.
+        ctx.accounts.user_stake.set_balance(
+            new_confidential_balance,
+            balance_commitment,
+            clock.slot,
+        );
+        Ok(())
+    }
+
+    /// Starts the unbonding period for a withdrawal of `amount`, on pools that have one.
+    /// Whether the withdrawal is early, and its penalty, is settled now, so staking again
+    /// while unbonding does not change it.
+    pub fn unbond(ctx: Context<Unbond>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let lockup = ctx.accounts.pool.lockup;
+        ctx.accounts
+            .user_stake
+            .start_unbonding(&lockup, amount, clock.unix_timestamp)
+    }
+
+    /// Pays out the rewards the position had earned at `reward_timestamp`, which must lie
+    /// between its last settlement and now. Clients pass a time they can compute the
+    /// accumulator for, since the one at execution time cannot be known when the proof is made.
+    ///
+    /// The position's pending rewards at that time, `balance · reward_per_unit - debt`, are
+    /// computed homomorphically. `claim_proof` shows they encrypt `amount · 2^64` plus the
+    /// value `remainder` encrypts, `equality_proof` binds `remainder_commitment` to that value
+    /// and `range_proof` over it shows the remainder is below `2^64`, so `amount` is exactly
+    /// the whole tokens owed and the balance stays hidden. Rewards are paid
+    /// from the pool's public balance in `reward_token_account`, funded by
+    /// `distribute_rewards`. The rewards left over are settled at the current time, so later
+    /// accrual is measured from here.
+    #[allow(clippy::too_many_arguments)]
+    pub fn claim_rewards(
+        ctx: Context<ClaimRewards>,
+        reward_timestamp: i64,
+        amount: u64,
+        claim_proof: ProofSource,
+        remainder: [u8; 64],
+        remainder_commitment: [u8; 32],
+        equality_proof: ProofSource,
+        range_proof: ProofSource,
+    ) -> Result<()> {
+        let claim_proof = claim_proof.resolve(ctx.remaining_accounts)?;
+        let equality_proof = equality_proof.resolve(ctx.remaining_accounts)?;
+        let range_proof = range_proof.resolve(ctx.remaining_accounts)?;
+        let clock = Clock::get()?;
+        let pool = &mut ctx.accounts.pool;
+        pool.accrue_rewards(clock.unix_timestamp)?;
+        let user_stake = &ctx.accounts.user_stake;
+        if reward_timestamp < user_stake.reward_timestamp || reward_timestamp > clock.unix_timestamp
+        {
+            return Err(ErrorCode::InvalidRewardCheckpoint.into());
+        }
+
+        // Verify that the pending rewards at the checkpoint split into `amount` and the remainder.
+        let claimed = (amount as u128) << 64;
+        let reward_per_unit =
+            pool.reward_per_unit(&ctx.accounts.vault, user_stake, reward_timestamp)?;
+        let pending = user_stake.pending_rewards(reward_per_unit)?;
+        let unclaimed = zk_utils::subtract_public_value(&pending, claimed)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        let domain = zk_utils::domain_separator("claim_rewards", &user_stake.key());
+        zk_utils::verify_confidential_balance(
+            &claim_proof,
+            &domain,
+            0,
+            zk_utils::BalanceUpdate::Debit,
+            user_stake.elgamal_pubkey,
+            unclaimed,
+            remainder,
+        )?;
+        zk_utils::verify_bounded_ciphertexts(
+            &ctx.accounts.proof_account,
+            &equality_proof,
+            &range_proof,
+            &domain,
+            &user_stake.elgamal_pubkey,
+            &[remainder],
+            &[remainder_commitment],
+        )?;
+
+        // Settle what is left of the rewards pending now.
+        let reward_per_unit =
+            pool.reward_per_unit(&ctx.accounts.vault, user_stake, clock.unix_timestamp)?;
+        let pending = user_stake.pending_rewards(reward_per_unit)?;
+        let carried = zk_utils::subtract_public_value(&pending, claimed)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        let acc_reward_per_share = pool.acc_reward_per_share;
+        ctx.accounts.user_stake.settle_rewards(
+            clock.unix_timestamp,
+            acc_reward_per_share,
+            &carried,
+        )?;
+
+        if amount > 0 {
+            let seeds = ctx.accounts.pool.signer_seeds();
+            confidential_token::transfer_checked(
+                &ctx.accounts.confidential_token_program.to_account_info(),
+                &ctx.accounts.reward_vault.to_account_info(),
+                &ctx.accounts.reward_mint.to_account_info(),
+                &ctx.accounts.user_reward_account.to_account_info(),
+                &ctx.accounts.pool.to_account_info(),
+                amount,
+                ctx.accounts.reward_mint.decimals,
+                &[&seeds[..]],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Commit a swap order using a commit–reveal scheme.
+    /// The trader commits to an order by providing a commitment hash and an encrypted order.
+    pub fn commit_swap(
+        ctx: Context<CommitSwap>,
+        commitment: [u8; 32],
+        encrypted_order: [u8; 64],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let trade_order = &mut ctx.accounts.trade_order;
+        trade_order.trader = ctx.accounts.trader.key();
+        trade_order.commitment = commitment;
+        trade_order.encrypted_order = encrypted_order;
+        trade_order.trade_timestamp = clock.unix_timestamp;
+        trade_order.commit_slot = clock.slot;
+        trade_order.revealed = false;
+        trade_order.bump = ctx.bumps.trade_order;
+        trade_order.revealed_order = Vec::new();
+        Ok(())
+    }
+
+    /// Reveal the trade order. `hash(order_details || nonce || trader)` must match the
+    /// commitment, at least `TradeOrder::MIN_REVEAL_DELAY_SLOTS` after the commit and no later
+    /// than `TradeOrder::REVEAL_WINDOW_SECONDS` after `trade_timestamp`.
+    pub fn reveal_swap(
+        ctx: Context<RevealSwap>,
+        order_details: Vec<u8>, // Decrypted order details.
+        nonce: [u8; 32],
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts
+            .trade_order
+            .reveal(order_details, &nonce, clock.slot, clock.unix_timestamp)
+    }
+
+    /// Closes an order that can no longer be revealed, because it already was or its reveal
+    /// deadline has passed, and refunds its rent to the trader. Anyone may call it.
+    pub fn close_trade_order(ctx: Context<CloseTradeOrder>) -> Result<()> {
+        if !ctx
+            .accounts
+            .trade_order
+            .is_closable(Clock::get()?.unix_timestamp)
+        {
+            return Err(ErrorCode::OrderStillRevealable.into());
+        }
+        Ok(())
+    }
+
+    /// Creates the pool's reward configuration and reward vault. Only `distributor` may call
+    /// `distribute_rewards`, which mints at most `emission_cap` tokens in each epoch of
+    /// `epoch_seconds`, the first starting now. The configuration PDA signs the mints, so the
+    /// reward mint's authority must be handed to it before rewards can be distributed.
+    pub fn initialize_reward_config(
+        ctx: Context<InitializeRewardConfig>,
+        distributor: Pubkey,
+        epoch_seconds: i64,
+        emission_cap: u64,
+    ) -> Result<()> {
+        let reward_config = &mut ctx.accounts.reward_config;
+        reward_config.pool = ctx.accounts.pool.key();
+        reward_config.epoch = 0;
+        reward_config.epoch_start = Clock::get()?.unix_timestamp;
+        reward_config.epoch_emitted = 0;
+        reward_config.bump = ctx.bumps.reward_config;
+        reward_config.configure(distributor, epoch_seconds, emission_cap)
+    }
+
+    /// Replaces the reward distributor and emission limits. The current epoch keeps its start
+    /// and what it has emitted so far.
+    pub fn update_reward_config(
+        ctx: Context<UpdateRewardConfig>,
+        distributor: Pubkey,
+        epoch_seconds: i64,
+        emission_cap: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .reward_config
+            .configure(distributor, epoch_seconds, emission_cap)
+    }
+
+    /// Distribute rewards to liquidity providers.
+    /// Incorporate zk-SNARK-based reward calculation to preserve privacy.
+    /// Token-2022 has no confidential mint, so rewards are minted to the public balance of the
+    /// pool's reward vault, which `claim_rewards` pays them out from. Only the pool's reward
+    /// distributor may call it, within the current epoch's emission cap.
+    pub fn distribute_rewards(
+        ctx: Context<DistributeRewards>,
+        zk_reward_proof: ProofSource,
+        reward_amount: u64,
+    ) -> Result<()> {
+        let zk_reward_proof = zk_reward_proof.resolve(ctx.remaining_accounts)?;
+        // Verify the ZK proof for reward distribution against the pool's reward circuit.
+        let pool_key = ctx.accounts.pool.key();
+        zk_utils::verify_groth16(
+            &ctx.accounts.vk_registry,
+            Circuit::Reward,
+            &zk_reward_proof,
+            &zk_utils::domain_separator("distribute_rewards", &pool_key),
+            &[
+                zk_utils::hash_to_field(&[pool_key.as_ref()]),
+                zk_utils::u64_to_field(reward_amount),
+                zk_utils::hash_to_field(&[&ctx.accounts.pool.total_commitment]),
+            ],
+        )?;
+        ctx.accounts
+            .reward_config
+            .record_emission(reward_amount, Clock::get()?.unix_timestamp)?;
+
+        let seeds = ctx.accounts.reward_config.signer_seeds();
+        confidential_token::mint_to_checked(
+            &ctx.accounts.confidential_token_program.to_account_info(),
+            &ctx.accounts.token_mint.to_account_info(),
+            &ctx.accounts.reward_vault.to_account_info(),
+            &ctx.accounts.reward_config.to_account_info(),
+            reward_amount,
+            ctx.accounts.token_mint.decimals,
+            &[&seeds[..]],
+        )?;
+        Ok(())
+    }
+
+    /// Posts the rewards of the reward configuration's current `epoch` as the Merkle root of
+    /// `leaf_count` leaves (see `zk_utils::reward_leaf`). The `total_amount` the leaves add up
+    /// to counts against the epoch's emission cap and is minted into the confidential balance
+    /// of the epoch's own vault, at `[RewardEpoch::VAULT_SEED, pool, epoch]`, which claims are
+    /// paid from. Leaves can be claimed with `claim_epoch_reward` for `claim_seconds`, after
+    /// which what is left is swept to the treasury by `sweep_reward_epoch`.
+    ///
+    /// The vault is encrypted under a key held by the pool authority, which provides the
+    /// `PubkeyValidityData` proof in `vault_pubkey_proof`, the vault's
+    /// `decryptable_zero_balance` and its `vault_decryptable_balance` once funded. The reward
+    /// mint must approve new confidential accounts automatically.
+    #[allow(clippy::too_many_arguments)]
+    pub fn post_reward_epoch(
+        ctx: Context<PostRewardEpoch>,
+        epoch: u64,
+        merkle_root: [u8; 32],
+        leaf_count: u32,
+        total_amount: u64,
+        claim_seconds: i64,
+        decryptable_zero_balance: [u8; 36],
+        vault_decryptable_balance: [u8; 36],
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let reward_config = &mut ctx.accounts.reward_config;
+        reward_config.record_emission(total_amount, now)?;
+        if epoch != reward_config.epoch || leaf_count == 0 || claim_seconds <= 0 {
+            return Err(ErrorCode::InvalidRewardEpoch.into());
+        }
+
+        let reward_epoch = &mut ctx.accounts.reward_epoch;
+        reward_epoch.pool = ctx.accounts.pool.key();
+        reward_epoch.epoch = epoch;
+        reward_epoch.merkle_root = merkle_root;
+        reward_epoch.leaf_count = leaf_count;
+        reward_epoch.total_amount = total_amount;
+        reward_epoch.expiry = now
+            .checked_add(claim_seconds)
+            .ok_or(ErrorCode::MathOverflow)?;
+        reward_epoch.swept = false;
+        reward_epoch.bump = ctx.bumps.reward_epoch;
+
+        let token_program = ctx.accounts.confidential_token_program.to_account_info();
+        let epoch_vault = ctx.accounts.epoch_vault.to_account_info();
+        let mint = ctx.accounts.token_mint.to_account_info();
+        let pool_info = ctx.accounts.pool.to_account_info();
+        let pool_seeds = ctx.accounts.pool.signer_seeds();
+        let pool_signer = &[&pool_seeds[..]];
+        confidential_token::reallocate(
+            &token_program,
+            &epoch_vault,
+            &ctx.accounts.distributor.to_account_info(),
+            &pool_info,
+            &ctx.accounts.system_program.to_account_info(),
+            pool_signer,
+        )?;
+        confidential_token::configure_account(
+            &token_program,
+            &epoch_vault,
+            &mint,
+            &pool_info,
+            decryptable_zero_balance,
+            &ctx.accounts.vault_pubkey_proof.to_account_info(),
+            pool_signer,
+        )?;
+
+        let config_seeds = ctx.accounts.reward_config.signer_seeds();
+        let decimals = ctx.accounts.token_mint.decimals;
+        confidential_token::mint_to_checked(
+            &token_program,
+            &mint,
+            &epoch_vault,
+            &ctx.accounts.reward_config.to_account_info(),
+            total_amount,
+            decimals,
+            &[&config_seeds[..]],
+        )?;
+        confidential_token::deposit(
+            &token_program,
+            &epoch_vault,
+            &mint,
+            &pool_info,
+            total_amount,
+            decimals,
+            pool_signer,
+        )?;
+        confidential_token::apply_pending_balance(
+            &token_program,
+            &epoch_vault,
+            &pool_info,
+            1,
+            vault_decryptable_balance,
+            pool_signer,
+        )
+    }
+
+    /// Pays the reward of one leaf of an epoch to `recipient_token_account` as a confidential
+    /// transfer from the epoch's vault, so neither the leaf nor the amount is revealed.
+    /// `zk_proof` is a `RewardClaim` Groth16 proof that the caller can open the commitment of
+    /// a leaf under the epoch's root, that `nullifier_hash` is that leaf's nullifier, and that
+    /// the transfer's amount is the reward the leaf encrypts. The nullifier blocks a second
+    /// claim of the leaf without identifying it.
+    ///
+    /// The vault is encrypted under a key held by the pool authority, which provides the
+    /// `TransferData` proof in `transfer_proof` and the vault's `vault_decryptable_balance`
+    /// after the transfer.
+    pub fn claim_epoch_reward(
+        ctx: Context<ClaimEpochReward>,
+        nullifier_hash: [u8; 32],
+        zk_proof: ProofSource,
+        vault_decryptable_balance: [u8; 36],
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        let reward_epoch = &ctx.accounts.reward_epoch;
+        if reward_epoch.swept || Clock::get()?.unix_timestamp >= reward_epoch.expiry {
+            return Err(ErrorCode::RewardEpochClosed.into());
+        }
+        let reward_epoch_key = reward_epoch.key();
+        let transfer = zk_utils::load_transfer_proof_context(&ctx.accounts.transfer_proof)?;
+        let domain = zk_utils::domain_separator("claim_epoch_reward", &reward_epoch_key);
+        zk_utils::verify_groth16(
+            &ctx.accounts.vk_registry,
+            Circuit::RewardClaim,
+            &zk_proof,
+            &domain,
+            &zk_utils::reward_claim_public_inputs(
+                &domain,
+                reward_epoch.merkle_root,
+                nullifier_hash,
+                &transfer,
+                &ctx.accounts.recipient_token_account.key(),
+            ),
+        )?;
+
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            &ctx.accounts.claimant.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &[
+                b"nullifier",
+                reward_epoch_key.as_ref(),
+                nullifier_hash.as_ref(),
+                &[ctx.bumps.nullifier],
+            ],
+        )?;
+
+        let seeds = ctx.accounts.pool.signer_seeds();
+        confidential_token::transfer(
+            &ctx.accounts.confidential_token_program.to_account_info(),
+            &ctx.accounts.epoch_vault.to_account_info(),
+            &ctx.accounts.reward_mint.to_account_info(),
+            &ctx.accounts.recipient_token_account.to_account_info(),
+            &ctx.accounts.pool.to_account_info(),
+            vault_decryptable_balance,
+            &ctx.accounts.transfer_proof.to_account_info(),
+            &[&seeds[..]],
+        )
+    }
+
+    /// Sends the `amount` left in an expired epoch's vault to the treasury. Anyone may call
+    /// it, as the destination is fixed, but only the pool authority can decrypt the vault's
+    /// balance and provide the `WithdrawData` proof in `vault_withdraw_proof` and the vault's
+    /// `vault_decryptable_balance` afterwards.
+    pub fn sweep_reward_epoch(
+        ctx: Context<SweepRewardEpoch>,
+        amount: u64,
+        vault_decryptable_balance: [u8; 36],
+    ) -> Result<()> {
+        ctx.accounts
+            .reward_epoch
+            .sweep(Clock::get()?.unix_timestamp)?;
+        if amount == 0 {
+            return Ok(());
+        }
+        let seeds = ctx.accounts.pool.signer_seeds();
+        let signer = &[&seeds[..]];
+        let token_program = ctx.accounts.confidential_token_program.to_account_info();
+        let epoch_vault = ctx.accounts.epoch_vault.to_account_info();
+        let mint = ctx.accounts.reward_mint.to_account_info();
+        let decimals = ctx.accounts.reward_mint.decimals;
+        let pool_info = ctx.accounts.pool.to_account_info();
+        confidential_token::withdraw(
+            &token_program,
+            &epoch_vault,
+            &mint,
+            &pool_info,
+            amount,
+            decimals,
+            vault_decryptable_balance,
+            &ctx.accounts.vault_withdraw_proof.to_account_info(),
+            signer,
+        )?;
+        confidential_token::transfer_checked(
+            &token_program,
+            &epoch_vault,
+            &mint,
+            &ctx.accounts.treasury.to_account_info(),
+            &pool_info,
+            amount,
+            decimals,
+            signer,
+        )
+    }
+
+    /// Credits deposits waiting in the pool token account's pending balance to its available
+    /// balance, so they can be withdrawn. Only the pool authority holds the account's
+    /// encryption key and can compute `new_decryptable_available_balance`.
+    pub fn apply_pool_pending_balance(
+        ctx: Context<ApplyPoolPendingBalance>,
+        expected_pending_balance_credit_counter: u64,
+        new_decryptable_available_balance: [u8; 36],
+    ) -> Result<()> {
+        let seeds = ctx.accounts.pool.signer_seeds();
+        confidential_token::apply_pending_balance(
+            &ctx.accounts.confidential_token_program.to_account_info(),
+            &ctx.accounts.pool_token_account.to_account_info(),
+            &ctx.accounts.pool.to_account_info(),
+            expected_pending_balance_credit_counter,
+            new_decryptable_available_balance,
+            &[&seeds[..]],
+        )
+    }
+
+    /// Creates the pool's governance account. Reward parameters can afterwards only change
+    /// through proposals that reach `quorum` votes with at least `threshold_bps` of them in favour.
+    pub fn initialize_governance(
+        ctx: Context<InitializeGovernance>,
+        quorum: u64,
+        threshold_bps: u16,
+        voting_period_slots: u64,
+        vote_weighting: VoteWeighting,
+    ) -> Result<()> {
+        if threshold_bps == 0 || threshold_bps > 10_000 || voting_period_slots == 0 {
+            return Err(ErrorCode::InvalidGovernanceConfig.into());
+        }
+        if vote_weighting == (VoteWeighting::Stake { stake_per_vote: 0 }) {
+            return Err(ErrorCode::InvalidGovernanceConfig.into());
+        }
+        let governance = &mut ctx.accounts.governance;
+        governance.authority = ctx.accounts.authority.key();
+        governance.pool = ctx.accounts.pool.key();
+        governance.quorum = quorum;
+        governance.threshold_bps = threshold_bps;
+        governance.voting_period_slots = voting_period_slots;
+        governance.vote_weighting = vote_weighting;
+        governance.proposal_count = 0;
+        governance.bump = ctx.bumps.governance;
+        Ok(())
+    }
+
+    /// Registers the committee that decrypts proposal tallies. Any `threshold` of the members
+    /// can jointly decrypt; fewer learn nothing about individual ballots or the running tally.
+    pub fn initialize_tally_committee(
+        ctx: Context<InitializeTallyCommittee>,
+        threshold: u8,
+        election_pubkey: [u8; 32],
+        members: Vec<CommitteeMember>,
+    ) -> Result<()> {
+        let committee = &mut ctx.accounts.tally_committee;
+        committee.governance = ctx.accounts.governance.key();
+        committee.threshold = threshold;
+        committee.election_pubkey = election_pubkey;
+        committee.members = members;
+        committee.bump = ctx.bumps.tally_committee;
+        committee.validate()
+    }
+
+    /// Opens a proposal for voting from the current slot until the end of the voting period.
+    /// Quorum, threshold and vote weighting are copied from the governance account so later
+    /// config changes cannot move the goalposts of an open vote.
+    pub fn create_proposal(ctx: Context<CreateProposal>, payload: ProposalPayload) -> Result<()> {
+        let governance = &mut ctx.accounts.governance;
+        let start_slot = Clock::get()?.slot;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.governance = governance.key();
+        proposal.id = governance.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.payload = payload;
+        proposal.start_slot = start_slot;
+        proposal.end_slot = start_slot
+            .checked_add(governance.voting_period_slots)
+            .ok_or(ErrorCode::MathOverflow)?;
+        proposal.quorum = governance.quorum;
+        proposal.threshold_bps = governance.threshold_bps;
+        proposal.vote_weighting = governance.vote_weighting;
+        proposal.votes_for = 0;
+        proposal.votes_against = 0;
+        proposal.election_pubkey = ctx.accounts.tally_committee.election_pubkey;
+        proposal.encrypted_tally = [0u8; 64];
+        proposal.encrypted_turnout = [0u8; 64];
+        proposal.votes_cast = 0;
+        proposal.ballots_cast = 0;
+        proposal.decryption_shares = [[0u8; 32]; MAX_COMMITTEE_SIZE];
+        proposal.turnout_decryption_shares = [[0u8; 32]; MAX_COMMITTEE_SIZE];
+        proposal.decryption_share_mask = 0;
+        proposal.status = ProposalStatus::Active;
+        proposal.bump = ctx.bumps.proposal;
+
+        governance.proposal_count = governance
+            .proposal_count
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Delegates the caller's vote to `delegate`, another member's governance identity (the
+    /// nullifier hash their own `cast_vote` and `delegate_votes` proofs reveal).
+    ///
+    /// The delegator is only known by their own governance identity, so nothing links a
+    /// delegation to a registry commitment or wallet; `payer` may be a relayer. Delegations
+    /// count for proposals created after this slot, and a delegator cannot vote directly in
+    /// those proposals. Each identity has at most one delegation at a time.
+    pub fn delegate_votes(
+        ctx: Context<DelegateVotes>,
+        delegate: [u8; 32],
+        zk_identity_proof: ProofSource,
+        identity_root: [u8; 32],
+        nullifier_hash: [u8; 32],
+    ) -> Result<()> {
+        let zk_identity_proof = zk_identity_proof.resolve(ctx.remaining_accounts)?;
+        let governance = &ctx.accounts.governance;
+        if governance.vote_weighting != VoteWeighting::Identity {
+            return Err(ErrorCode::VoteWeightingMismatch.into());
+        }
+        let governance_key = governance.key();
+        let slot = Clock::get()?.slot;
+        let delegation = &mut ctx.accounts.delegation;
+
+        // Binding the proof to the last revocation stops it being replayed to re-delegate
+        // after the delegator revoked.
+        let signal = [&delegate[..], &delegation.revoked_slot.to_le_bytes()].concat();
+        let domain = zk_utils::domain_separator("delegate_votes", &governance_key);
+        zk_utils::verify_identity_proof(
+            &ctx.accounts.identity_registry,
+            &ctx.accounts.vk_registry,
+            &zk_identity_proof,
+            &domain,
+            identity_root,
+            nullifier_hash,
+            &signal,
+        )?;
+        if delegate == nullifier_hash {
+            return Err(ErrorCode::InvalidDelegation.into());
+        }
+        if delegation.active {
+            return Err(ErrorCode::AlreadyDelegated.into());
+        }
+        // A revoked delegation still applies to proposals created before its revocation, so
+        // the record cannot be overwritten until all of them have closed.
+        if delegation.governance != Pubkey::default()
+            && slot
+                < delegation
+                    .revoked_slot
+                    .saturating_add(governance.voting_period_slots)
+        {
+            return Err(ErrorCode::DelegationCoolingDown.into());
+        }
+
+        delegation.governance = governance_key;
+        delegation.delegate = delegate;
+        delegation.delegated_slot = slot;
+        delegation.active = true;
+        delegation.bump = ctx.bumps.delegation;
+
+        let delegate_weight = &mut ctx.accounts.delegate_weight;
+        if delegate_weight.governance == Pubkey::default() {
+            delegate_weight.governance = governance_key;
+            delegate_weight.delegate = delegate;
+            delegate_weight.bump = ctx.bumps.delegate_weight;
+        }
+        let weight = delegate_weight
+            .current()
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        delegate_weight.record(slot, weight, governance.voting_period_slots)
+    }
+
+    /// Revokes the caller's active delegation. Proposals created from this slot on no longer
+    /// count it, and the caller may vote in them directly.
+    pub fn revoke_delegation(
+        ctx: Context<RevokeDelegation>,
+        zk_identity_proof: ProofSource,
+        identity_root: [u8; 32],
+        nullifier_hash: [u8; 32],
+    ) -> Result<()> {
+        let zk_identity_proof = zk_identity_proof.resolve(ctx.remaining_accounts)?;
+        let governance = &ctx.accounts.governance;
+        let slot = Clock::get()?.slot;
+        let delegation = &mut ctx.accounts.delegation;
+        if !delegation.active {
+            return Err(ErrorCode::DelegationNotActive.into());
+        }
+
+        let signal = [
+            &delegation.delegate[..],
+            &delegation.delegated_slot.to_le_bytes(),
+        ]
+        .concat();
+        let domain = zk_utils::domain_separator("revoke_delegation", &governance.key());
+        zk_utils::verify_identity_proof(
+            &ctx.accounts.identity_registry,
+            &ctx.accounts.vk_registry,
+            &zk_identity_proof,
+            &domain,
+            identity_root,
+            nullifier_hash,
+            &signal,
+        )?;
+
+        delegation.active = false;
+        delegation.revoked_slot = slot;
+        let delegate_weight = &mut ctx.accounts.delegate_weight;
+        let weight = delegate_weight
+            .current()
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        delegate_weight.record(slot, weight, governance.voting_period_slots)
+    }
+
+    /// Cast a confidential vote on a proposal.
+    /// `ballot` is an ElGamal encryption of 0 or the voter's weight under the committee's
+    /// election key, and `ballot_proof` shows it encrypts nothing else. Ballots are only ever
+    /// summed, so no single vote is decrypted. The weight is one plus the delegations the voter
+    /// held when the proposal was created.
+    ///
+    /// The identity proof is scoped to the governance rather than the proposal, so its
+    /// `nullifier_hash` is the voter's governance identity: the key their delegations are
+    /// recorded under. Each identity may vote once per proposal: its nullifier PDA is created
+    /// here and a second vote fails. `voter` only pays for the nullifier and may be a relayer.
+    /// Only for identity-weighted governance; stake-weighted proposals take votes through
+    /// `register_stake_weight` and `cast_stake_vote`.
+    pub fn cast_vote(
+        ctx: Context<CastVote>,
+        ballot: [u8; 64],
+        ballot_proof: ProofSource,
+        zk_vote_proof: ProofSource,
+        identity_root: [u8; 32],
+        nullifier_hash: [u8; 32],
+    ) -> Result<()> {
+        let ballot_proof = ballot_proof.resolve(ctx.remaining_accounts)?;
+        let zk_vote_proof = zk_vote_proof.resolve(ctx.remaining_accounts)?;
+        let governance_key = ctx.accounts.governance.key();
+        let proposal_key = ctx.accounts.proposal.key();
+        let proposal = &ctx.accounts.proposal;
+        if !proposal.is_voting_open(Clock::get()?.slot) {
+            return Err(ErrorCode::VotingClosed.into());
+        }
+        if proposal.vote_weighting != VoteWeighting::Identity {
+            return Err(ErrorCode::VoteWeightingMismatch.into());
+        }
+
+        let identity_domain = zk_utils::domain_separator("cast_vote", &governance_key);
+        let signal = [proposal_key.as_ref(), &ballot[..]].concat();
+        zk_utils::verify_identity_proof(
+            &ctx.accounts.identity_registry,
+            &ctx.accounts.vk_registry,
+            &zk_vote_proof,
+            &identity_domain,
+            identity_root,
+            nullifier_hash,
+            &signal,
+        )?;
+
+        // Delegations are read as of the proposal's creation slot, its snapshot.
+        let delegation =
+            load_optional_account::<Delegation>(&ctx.accounts.delegation.to_account_info())?;
+        if delegation.is_some_and(|delegation| delegation.is_effective_at(proposal.start_slot)) {
+            return Err(ErrorCode::VoteDelegated.into());
+        }
+        let delegate_weight = ctx.accounts.delegate_weight.to_account_info();
+        let delegated_weight = load_optional_account::<DelegateWeight>(&delegate_weight)?
+            .map_or(0, |weight| weight.weight_at(proposal.start_slot));
+        let weight = delegated_weight
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let domain = zk_utils::domain_separator("cast_vote", &proposal_key);
+        zk_utils::verify_ballot(
+            &ballot_proof,
+            &domain,
+            &proposal.election_pubkey,
+            &ballot,
+            &zk_utils::public_weight(weight)?,
+        )?;
+
+        spend_nullifier(
+            &ctx.accounts.nullifier.to_account_info(),
+            &ctx.accounts.voter.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            &[
+                b"nullifier",
+                proposal_key.as_ref(),
+                nullifier_hash.as_ref(),
+                &[ctx.bumps.nullifier],
+            ],
+        )?;
+
+        ctx.accounts.proposal.add_ballot(&ballot, weight)
+    }
+
+    /// Registers a hidden vote weight for a stake-weighted proposal: `w` votes, where
+    /// `w·stake_per_vote` is at most the caller's confidential stake at the proposal's
+    /// snapshot (its creation slot).
+    ///
+    /// `stake_split` is `E_w || E_rem`, two ciphertexts under the owner's ElGamal key whose
+    /// combination `stake_per_vote·E_w + E_rem` is shown by `split_proof` to encrypt the
+    /// snapshot balance. `equality_proof` binds the commitments in `split_commitments` to the
+    /// values of `E_w` and `E_rem`, and `range_proof` over them keeps both parts non-negative.
+    /// `weight` encrypts the same `w` under the election key and is what `cast_stake_vote`
+    /// weights the ballot by. Neither the balance nor `w` is revealed, though the registration
+    /// shows that this stake takes part. Kept apart from `cast_stake_vote` so each transaction
+    /// fits the size limit.
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_stake_weight(
+        ctx: Context<RegisterStakeWeight>,
+        weight: [u8; 64],
+        stake_split: [u8; 128],
+        split_commitments: [u8; 64],
+        split_proof: ProofSource,
+        equality_proof: ProofSource,
+        range_proof: ProofSource,
+        weight_proof: ProofSource,
+    ) -> Result<()> {
+        let split_proof = split_proof.resolve(ctx.remaining_accounts)?;
+        let equality_proof = equality_proof.resolve(ctx.remaining_accounts)?;
+        let range_proof = range_proof.resolve(ctx.remaining_accounts)?;
+        let weight_proof = weight_proof.resolve(ctx.remaining_accounts)?;
+        let proposal_key = ctx.accounts.proposal.key();
+        let proposal = &ctx.accounts.proposal;
+        if !proposal.is_voting_open(Clock::get()?.slot) {
+            return Err(ErrorCode::VotingClosed.into());
+        }
+        let VoteWeighting::Stake { stake_per_vote } = proposal.vote_weighting else {
+            return Err(ErrorCode::VoteWeightingMismatch.into());
+        };
+        let user_stake = &ctx.accounts.user_stake;
+        let balance = user_stake
+            .balance_at(proposal.start_slot)
+            .ok_or(ErrorCode::StakeSnapshotUnavailable)?;
+
+        let mut weight_part = [0u8; 64];
+        let mut remainder = [0u8; 64];
+        weight_part.copy_from_slice(&stake_split[..64]);
+        remainder.copy_from_slice(&stake_split[64..]);
+        let recombined =
+            zk_utils::scale_ciphertext(&zk_utils::amount_scalar(stake_per_vote), &weight_part)
+                .and_then(|scaled| zk_utils::add_ciphertexts(&scaled, &remainder))
+                .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+
+        let domain = zk_utils::domain_separator("register_stake_weight", &proposal_key);
+        zk_utils::verify_confidential_balance(
+            &split_proof,
+            &domain,
+            0,
+            zk_utils::BalanceUpdate::Debit,
+            user_stake.elgamal_pubkey,
+            balance,
+            recombined,
+        )?;
+        let (weight_commitment, remainder_commitment) =
+            zk_utils::split_ciphertext(&split_commitments);
+        zk_utils::verify_bounded_ciphertexts(
+            &ctx.accounts.proof_account,
+            &equality_proof,
+            &range_proof,
+            &domain,
+            &user_stake.elgamal_pubkey,
+            &[weight_part, remainder],
+            &[weight_commitment.0, remainder_commitment.0],
+        )?;
+        zk_utils::verify_weight_commitment(
+            &weight_proof,
+            &domain,
+            &proposal.election_pubkey,
+            &weight_commitment.0,
+            &weight,
+        )?;
+
+        let stake_weight = &mut ctx.accounts.stake_weight;
+        stake_weight.proposal = proposal_key;
+        stake_weight.owner = ctx.accounts.owner.key();
+        stake_weight.weight = weight;
+        stake_weight.voted = false;
+        stake_weight.bump = ctx.bumps.stake_weight;
+        Ok(())
+    }
+
+    /// Cast a confidential vote on a stake-weighted proposal.
+    /// `ballot` must encrypt 0 or the same value as the owner's registered `StakeWeight`. The
+    /// weight is added to the proposal's encrypted turnout, so only the total weight that
+    /// voted is ever decrypted.
+    pub fn cast_stake_vote(
+        ctx: Context<CastStakeVote>,
+        ballot: [u8; 64],
+        ballot_proof: ProofSource,
+    ) -> Result<()> {
+        let ballot_proof = ballot_proof.resolve(ctx.remaining_accounts)?;
+        let proposal_key = ctx.accounts.proposal.key();
+        let proposal = &mut ctx.accounts.proposal;
+        if !proposal.is_voting_open(Clock::get()?.slot) {
+            return Err(ErrorCode::VotingClosed.into());
+        }
+        let stake_weight = &mut ctx.accounts.stake_weight;
+        if stake_weight.voted {
+            return Err(ErrorCode::StakeAlreadyVoted.into());
+        }
+
+        let domain = zk_utils::domain_separator("cast_stake_vote", &proposal_key);
+        zk_utils::verify_ballot(
+            &ballot_proof,
+            &domain,
+            &proposal.election_pubkey,
+            &ballot,
+            &stake_weight.weight,
+        )?;
+        stake_weight.voted = true;
+        proposal.add_stake_ballot(&ballot, &stake_weight.weight)
+    }
+
+    /// Posts a committee member's partial decryptions of a closed proposal's encrypted totals
+    /// (see `Proposal::encrypted_totals`), with a DLEQ proof that they were computed with the
+    /// member's registered key share.
+    pub fn submit_decryption_share(
+        ctx: Context<SubmitDecryptionShare>,
+        shares: Vec<[u8; 32]>,
+        dleq_proof: ProofSource,
+    ) -> Result<()> {
+        let dleq_proof = dleq_proof.resolve(ctx.remaining_accounts)?;
+        let proposal_key = ctx.accounts.proposal.key();
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.status != ProposalStatus::Active {
+            return Err(ErrorCode::ProposalNotActive.into());
+        }
+        if Clock::get()?.slot < proposal.end_slot {
+            return Err(ErrorCode::VotingStillOpen.into());
+        }
+        let committee = &ctx.accounts.tally_committee;
+        let position = committee
+            .member_position(&ctx.accounts.member.key())
+            .ok_or(ErrorCode::NotCommitteeMember)?;
+
+        let ciphertexts_c1: Vec<[u8; 32]> = proposal
+            .encrypted_totals()
+            .iter()
+            .map(|total| zk_utils::split_ciphertext(total).0 .0)
+            .collect();
+        let domain = zk_utils::domain_separator("submit_decryption_share", &proposal_key);
+        zk_utils::verify_decryption_share(
+            &dleq_proof,
+            &domain,
+            &committee.members[position].share_pubkey,
+            &ciphertexts_c1,
+            &shares,
+        )?;
+        proposal.decryption_shares[position] = shares[0];
+        if let Some(turnout_share) = shares.get(1) {
+            proposal.turnout_decryption_shares[position] = *turnout_share;
+        }
+        proposal.decryption_share_mask |= 1 << position;
+        Ok(())
+    }
+
+    /// Closes voting on a proposal once its end slot has passed, marking it passed or rejected.
+    /// `votes_for` and `votes_cast` are the decrypted tally and turnout, which anyone can
+    /// recover off-chain from the posted decryption shares; they are only accepted if they
+    /// match the encrypted totals. Under identity weighting the turnout is public already and
+    /// `votes_cast` must equal it.
+    pub fn finalize_proposal(
+        ctx: Context<FinalizeProposal>,
+        votes_for: u64,
+        votes_cast: u64,
+    ) -> Result<()> {
+        let committee = &ctx.accounts.tally_committee;
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.status != ProposalStatus::Active {
+            return Err(ErrorCode::ProposalNotActive.into());
+        }
+        if Clock::get()?.slot < proposal.end_slot {
+            return Err(ErrorCode::VotingStillOpen.into());
+        }
+        let stake_weighted = matches!(proposal.vote_weighting, VoteWeighting::Stake { .. });
+        if votes_for > votes_cast || (!stake_weighted && votes_cast != proposal.votes_cast) {
+            return Err(ErrorCode::InvalidTally.into());
+        }
+        // With no ballots the totals are the identity and need no decryption.
+        if proposal.ballots_cast > 0 {
+            let threshold = committee.threshold as usize;
+            let shares = proposal.decryption_shares();
+            if shares.len() < threshold {
+                return Err(ErrorCode::InsufficientDecryptionShares.into());
+            }
+            zk_utils::verify_tally(&proposal.encrypted_tally, &shares[..threshold], votes_for)?;
+            if stake_weighted {
+                let turnout_shares = proposal.turnout_decryption_shares();
+                zk_utils::verify_tally(
+                    &proposal.encrypted_turnout,
+                    &turnout_shares[..threshold],
+                    votes_cast,
+                )?;
+            }
+        } else if votes_cast != 0 {
+            return Err(ErrorCode::InvalidTally.into());
+        }
+        proposal.votes_cast = votes_cast;
+        proposal.votes_for = votes_for;
+        proposal.votes_against = votes_cast - votes_for;
+        proposal.status = proposal.outcome();
+        Ok(())
+    }
+
+    /// Applies a passed proposal's payload. Each proposal executes at most once.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.status != ProposalStatus::Passed {
+            return Err(ErrorCode::ProposalNotPassed.into());
+        }
+        proposal.status = ProposalStatus::Executed;
+        match proposal.payload.clone() {
+            ProposalPayload::UpdateRewardParams { new_reward_rate } => update_reward_params(
+                &mut ctx.accounts.pool,
+                new_reward_rate,
+                Clock::get()?.unix_timestamp,
+            ),
+            ProposalPayload::UpdateLockupPolicy { lockup } => {
+                update_lockup_policy(&mut ctx.accounts.pool, lockup)
+            }
+            ProposalPayload::UpdateRewardTiers { reward_tiers } => {
+                update_reward_tiers(&mut ctx.accounts.pool, reward_tiers)
+            }
+        }
+    }
+
+    // ---------------------------------------------------------------------
+    // Additional Advanced Features
+    // ---------------------------------------------------------------------
+
+    /// zk-enabled multi-signature transaction.
+    pub fn zk_multisig_transaction(
+        ctx: Context<AdditionalFeatures>,
+        _multisig_data: Vec<u8>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement multi-signature approvals using zk-SNARKs so that multiple parties can sign without revealing their identities.
+        let domain =
+            zk_utils::domain_separator("zk_multisig_transaction", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// ZK rollback protection to prevent transaction replay or reversion.
+    pub fn zk_rollback_protection(
+        ctx: Context<AdditionalFeatures>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement zk-proofs to ensure the transaction is not being replayed or fraudulently reverted.
+        let domain =
+            zk_utils::domain_separator("zk_rollback_protection", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// Stakes into several positions, in one pool or several, in one instruction.
+    /// Entry `i` takes its accounts from `remaining_accounts`, laid out as the accounts of
+    /// `stake`, and its amount, proofs, new balance and commitment from index `i` of each
+    /// argument. Proof buffers referenced by `zk_proofs` or `equality_proofs` follow the last
+    /// entry's accounts, each once.
+    /// Every entry is verified before any tokens move, and any failure fails the batch.
+    pub fn batch_stake<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchStake<'info>>,
+        amounts: Vec<u64>,
+        zk_proofs: Vec<ProofSource>,
+        new_confidential_balances: Vec<[u8; 64]>,
+        balance_commitments: Vec<[u8; 32]>,
+        equality_proofs: Vec<ProofSource>,
+        auditor_amounts: Vec<Option<AuditorAmount>>,
+    ) -> Result<()> {
+        let count = amounts.len();
+        if count == 0
+            || zk_proofs.len() != count
+            || new_confidential_balances.len() != count
+            || balance_commitments.len() != count
+            || equality_proofs.len() != count
+            || auditor_amounts.len() != count
+        {
+            return Err(ErrorCode::BatchLengthMismatch.into());
+        }
+        let (entry_accounts, proof_buffers) = split_proof_buffers(
+            ctx.remaining_accounts,
+            zk_proofs.iter().chain(&equality_proofs),
+        )?;
+        let zk_proofs = zk_proofs
+            .iter()
+            .map(|proof| proof.resolve(proof_buffers))
+            .collect::<Result<Vec<_>>>()?;
+        let equality_proofs = equality_proofs
+            .iter()
+            .map(|proof| proof.resolve(proof_buffers))
+            .collect::<Result<Vec<_>>>()?;
+        let mut entries = load_stake_entries(entry_accounts, count)?;
+        if entries
+            .iter()
+            .any(|entry| entry.user.key() != ctx.accounts.user.key())
+        {
+            return Err(ErrorCode::BatchUserMismatch.into());
+        }
+
+        let mut auditor_ciphertexts = Vec::with_capacity(count);
+        for (i, entry) in entries.iter().enumerate() {
+            auditor_ciphertexts.push(verify_stake(
+                entry,
+                amounts[i],
+                &zk_proofs[i],
+                &equality_proofs[i],
+                &new_confidential_balances[i],
+                &balance_commitments[i],
+                auditor_amounts[i].as_ref(),
+            )?);
+        }
+
+        let clock = Clock::get()?;
+        for (i, entry) in entries.iter_mut().enumerate() {
+            // Entries staking other mints into the same pool have already written it back.
+            entry.pool.reload()?;
+            apply_stake(
+                entry,
+                amounts[i],
+                new_confidential_balances[i],
+                balance_commitments[i],
+                auditor_ciphertexts[i].as_ref(),
+                &clock,
+            )?;
+            entry.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// zk-enabled exit mechanism for LPs to withdraw liquidity without revealing exact shares.
+    pub fn zk_exit(
+        ctx: Context<AdditionalFeatures>,
+        amount: u64,
+        zk_proof: ProofSource,
+        new_confidential_balance: [u8; 64],
+        balance_commitment: [u8; 32],
+        equality_proof: ProofSource,
+        range_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        let equality_proof = equality_proof.resolve(ctx.remaining_accounts)?;
+        let range_proof = range_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement a zk-enabled exit that verifies withdrawal without exposing the precise stake.
+        let domain = zk_utils::domain_separator("zk_exit", &ctx.accounts.user_stake.key());
+        zk_utils::verify_confidential_balance(
+            &zk_proof,
+            &domain,
+            amount,
+            zk_utils::BalanceUpdate::Debit,
+            ctx.accounts.user_stake.elgamal_pubkey,
+            ctx.accounts.user_stake.confidential_balance,
+            new_confidential_balance,
+        )?;
+        zk_utils::verify_bounded_ciphertexts(
+            &ctx.accounts.proof_account,
+            &equality_proof,
+            &range_proof,
+            &domain,
+            &ctx.accounts.user_stake.elgamal_pubkey,
+            &[new_confidential_balance],
+            &[balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// zk-based automatic liquidity rebalancing.
+    pub fn zk_auto_rebalance(
+        ctx: Context<AdditionalFeatures>,
+        zk_proof: ProofSource,
+        _liquidity_params: Vec<u8>,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement automatic liquidity rebalancing using zk-proofs to adjust pool parameters confidentially.
+        let domain =
+            zk_utils::domain_separator("zk_auto_rebalance", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// zk-time lock unlocking mechanism for liquidity.
+    pub fn zk_time_lock_unlock(
+        ctx: Context<AdditionalFeatures>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Use zk-time locks to allow liquidity unlocking only after a specified delay.
+        let domain =
+            zk_utils::domain_separator("zk_time_lock_unlock", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// Place a confidential limit order using zk-proofs.
+    pub fn confidential_limit_order(
+        ctx: Context<AdditionalFeatures>,
+        _order_data: Vec<u8>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement confidential limit orders, allowing users to set trade conditions privately.
+        let domain =
+            zk_utils::domain_separator("confidential_limit_order", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// zk-secured smart contract upgradability.
+    pub fn zk_upgrade(
+        ctx: Context<AdditionalFeatures>,
+        _upgrade_data: Vec<u8>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement contract upgrade validation using zk-proofs for governance-approved changes.
+        let domain = zk_utils::domain_separator("zk_upgrade", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// zk-proof of funds verification to confirm user holds required funds confidentially.
+    pub fn zk_proof_of_funds(
+        ctx: Context<AdditionalFeatures>,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Verify via zk-proofs that the user holds the required funds without exposing the actual balance.
+        let domain =
+            zk_utils::domain_separator("zk_proof_of_funds", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// zk-private flash loans where loan details remain confidential until repayment.
+    pub fn zk_private_flash_loan(
+        ctx: Context<AdditionalFeatures>,
+        _loan_amount: u64,
+        zk_proof: ProofSource,
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Implement private flash loans using zk-proofs to conceal loan amounts and terms until settlement.
+        let domain =
+            zk_utils::domain_separator("zk_private_flash_loan", &ctx.accounts.user_stake.key());
+        zk_utils::verify_transfer_proof(
+            &ctx.accounts.proof_account,
+            &zk_proof,
+            &domain,
+            &[ctx.accounts.user_stake.balance_commitment],
+        )?;
+        Ok(())
+    }
+
+    /// Integrate zk-Rollups to batch confidential transactions and reduce transaction fees.
+    pub fn integrate_zk_rollup(_ctx: Context<AdditionalFeatures>) -> Result<()> {
+        // TODO: Implement zk-Rollup integration to improve scalability and reduce transaction fees.
+        Ok(())
+    }
+
+    /// Implement private order matching in the AMM using zk-proofs.
+    pub fn private_order_matching(_ctx: Context<AdditionalFeatures>) -> Result<()> {
+        // TODO: Create a private order book via zk-proofs so that liquidity levels remain confidential.
+        Ok(())
+    }
+
+    /// Enable private lending and borrowing markets using zk-enabled credit scores.
+    pub fn private_lending(_ctx: Context<AdditionalFeatures>) -> Result<()> {
+        // TODO: Implement confidential lending pools without revealing borrower identities.
+        Ok(())
+    }
+
+    /// Introduce zk-proof staking challenges for LPs.
+    pub fn zk_proof_staking_challenges(_ctx: Context<AdditionalFeatures>) -> Result<()> {
+        // TODO: Allow LPs to contest suspicious liquidity changes using zk-proofs without revealing full stake details.
+        Ok(())
+    }
+
+    /// Display on-chain liquidity privacy metrics by showing aggregate liquidity without exposing individual positions.
+    pub fn onchain_liquidity_privacy_metrics(_ctx: Context<AdditionalFeatures>) -> Result<()> {
+        // TODO: Implement on-chain metrics that protect individual LP privacy.
+        Ok(())
+    }
+
+    /// Enable zk-encrypted messaging for LP coordination.
+    /// Only members of the pool's identity registry may post.
+    pub fn zk_encrypted_messaging(
+        ctx: Context<EncryptedMessaging>,
+        message: Vec<u8>,
+        zk_proof: ProofSource,
+        identity_root: [u8; 32],
+        nullifier_hash: [u8; 32],
+    ) -> Result<()> {
+        let zk_proof = zk_proof.resolve(ctx.remaining_accounts)?;
+        // TODO: Develop an off-chain zk-enabled messaging system for LP coordination.
+        let domain = zk_utils::domain_separator(
+            "zk_encrypted_messaging",
+            &ctx.accounts.identity_registry.pool,
+        );
+        zk_utils::verify_identity_proof(
+            &ctx.accounts.identity_registry,
+            &ctx.accounts.vk_registry,
+            &zk_proof,
+            &domain,
+            identity_root,
+            nullifier_hash,
+            &message,
+        )?;
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------
+// Account Definitions
+// ---------------------------------------------------------------------
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LiquidityPool::LEN,
+        seeds = [LiquidityPool::SEED, token_mint.key().as_ref()],
+        bump,
+    )]
+    pub pool: Account<'info, LiquidityPool>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Only recorded, as the owner of the pool's treasury token accounts.
+    pub treasury: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// The liquidity pool configuration.
+#[account]
+pub struct LiquidityPool {
+    pub authority: Pubkey,
+    pub token_mint: Pubkey,
+    /// Sum of every position's `balance_commitment`, each scaled by its mint's reward
+    /// weight, i.e. a commitment to the reward-weighted total staked.
+    /// Rewards are proven against it.
+    pub total_commitment: [u8; 32],
+    /// ElGamal key the total is also encrypted to, if the pool has an auditor.
+    pub auditor_elgamal_pubkey: Option<[u8; 32]>,
+    /// Encryption of the reward-weighted total under `auditor_elgamal_pubkey`.
+    pub auditor_total: [u8; 64],
+    pub lockup: LockupPolicy,
+    /// Owner of the token accounts early-withdraw penalties are paid to, one per mint.
+    pub treasury: Pubkey,
+    pub reward_tiers: RewardTiers,
+    /// Rewards per second for each staked base unit and unit of reward factor, in `2^-64`
+    /// reward tokens. The total staked is hidden, so the rate is per unit rather than a
+    /// pool-wide emission split between the stakers.
+    pub reward_rate: u64,
+    /// Rewards accrued so far per staked base unit and unit of reward factor, in `2^-64`
+    /// reward tokens.
+    pub acc_reward_per_share: u128,
+    /// When `acc_reward_per_share` was last brought up to date.
+    pub last_reward_timestamp: i64,
+    /// The most recent reward rates, oldest first, so the accumulator can be recovered at the
+    /// times positions cross a tier. The last entry is the current `reward_rate`.
+    pub reward_rate_history: Vec<RewardRatePeriod>,
+    pub bump: u8,
+    // Additional configuration fields (e.g., fee rate, trade volume) can be added here.
+}
+
+impl LiquidityPool {
+    // Total space: 32 (authority) + 32 (mint) + 32 (total) + 33 (auditor) + 64 + 18 (lockup)
+    //              + 32 (treasury) + 44 (reward tiers) + 8 (rate) + 16 (accumulator) + 8
+    //              + 260 (rate history) + 1 = 580 bytes.
+    pub const LEN: usize = 32
+        + 32
+        + 32
+        + (1 + 32)
+        + 64
+        + LockupPolicy::LEN
+        + 32
+        + RewardTiers::LEN
+        + 8
+        + 16
+        + 8
+        + (4 + MAX_REWARD_RATE_HISTORY * RewardRatePeriod::LEN)
+        + 1;
+
+    /// Seed prefix of the pool PDA, which lives at `[SEED, token_mint]`.
+    pub const SEED: &'static [u8] = b"pool";
+
+    /// The pool PDA for `token_mint` and its canonical bump.
+    pub fn find_address(token_mint: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, token_mint.as_ref()], &crate::ID)
+    }
+
+    /// Seeds the pool signs its token-account CPIs with. The pool PDA is the owner of the
+    /// pool token accounts.
+    pub fn signer_seeds(&self) -> [&[u8]; 3] {
+        [
+            Self::SEED,
+            self.token_mint.as_ref(),
+            std::slice::from_ref(&self.bump),
+        ]
+    }
+
+    /// Brings `acc_reward_per_share` up to `now` at the current `reward_rate`.
+    pub fn accrue_rewards(&mut self, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_reward_timestamp);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+        self.acc_reward_per_share = (self.reward_rate as u128)
+            .checked_mul(elapsed as u128)
+            .and_then(|accrued| self.acc_reward_per_share.checked_add(accrued))
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.last_reward_timestamp = now;
+        Ok(())
+    }
+
+    /// Switches to `reward_rate` from `now`, which the accumulator must be brought up to
+    /// first. The oldest rate is forgotten once the history is full.
+    pub fn set_reward_rate(&mut self, reward_rate: u64, now: i64) {
+        if self.reward_rate_history.len() == MAX_REWARD_RATE_HISTORY {
+            self.reward_rate_history.remove(0);
+        }
+        self.reward_rate_history.push(RewardRatePeriod {
+            start_timestamp: now,
+            start_acc_reward_per_share: self.acc_reward_per_share,
+            reward_rate,
+        });
+        self.reward_rate = reward_rate;
+    }
+
+    /// The value of `acc_reward_per_share` at `timestamp`, continuing at the current rate past
+    /// the last accrual. `None` if `timestamp` is older than the rate history reaches.
+    pub fn acc_reward_per_share_at(&self, timestamp: i64) -> Option<u128> {
+        let (start_timestamp, start_acc, reward_rate) = if timestamp >= self.last_reward_timestamp {
+            (
+                self.last_reward_timestamp,
+                self.acc_reward_per_share,
+                self.reward_rate,
+            )
+        } else {
+            let period = self
+                .reward_rate_history
+                .iter()
+                .rev()
+                .find(|period| period.start_timestamp <= timestamp)?;
+            (
+                period.start_timestamp,
+                period.start_acc_reward_per_share,
+                period.reward_rate,
+            )
+        };
+        (reward_rate as u128)
+            .checked_mul((timestamp - start_timestamp) as u128)
+            .and_then(|accrued| start_acc.checked_add(accrued))
+    }
+
+    /// Rewards per staked base unit `position`, in `vault`, has earned from its last
+    /// settlement to `timestamp`, in `2^-64` tokens. The stretch is split where the position
+    /// reaches each tier, and each part earns at the multiplier of the tier it was in.
+    ///
+    /// A tier reached before the oldest rate in the history cannot be split off, so the
+    /// position earns at its earlier multiplier until the next split it can make.
+    pub fn reward_per_unit(
+        &self,
+        vault: &AssetVault,
+        position: &UserStake,
+        timestamp: i64,
+    ) -> Result<u128> {
+        let end_acc = self
+            .acc_reward_per_share_at(timestamp)
+            .ok_or(ErrorCode::InvalidRewardCheckpoint)?;
+        let crossings = self
+            .reward_tiers
+            .tiers
+            .iter()
+            .map(|tier| {
+                position
+                    .stake_timestamp
+                    .saturating_add(tier.min_stake_seconds)
+            })
+            .filter(|crossing| *crossing > position.reward_timestamp && *crossing < timestamp)
+            .filter_map(|crossing| Some((crossing, self.acc_reward_per_share_at(crossing)?)));
+
+        let mut start = (position.reward_timestamp, position.reward_acc);
+        let mut reward_per_unit = 0u128;
+        for end in crossings.chain(std::iter::once((timestamp, end_acc))) {
+            let factor = vault.reward_factor(
+                self.reward_tiers
+                    .multiplier_bps(position.stake_timestamp, start.0),
+            )?;
+            reward_per_unit = end
+                .1
+                .checked_sub(start.1)
+                .and_then(|accrued| accrued.checked_mul(factor as u128))
+                .and_then(|accrued| reward_per_unit.checked_add(accrued))
+                .ok_or(ErrorCode::MathOverflow)?;
+            start = end;
+        }
+        Ok(reward_per_unit)
+    }
+
+    /// Checks `auditor_amount` encrypts `amount` to the pool's auditor and returns its
+    /// ciphertext. It must be given exactly when the pool has an auditor.
+    pub fn verify_auditor_amount(
+        &self,
+        auditor_amount: Option<&AuditorAmount>,
+        domain: &[u8; 32],
+        amount: u64,
+    ) -> Result<Option<[u8; 64]>> {
+        match (&self.auditor_elgamal_pubkey, auditor_amount) {
+            (None, None) => Ok(None),
+            (Some(auditor_pubkey), Some(auditor_amount)) => {
+                zk_utils::verify_auditor_amount(
+                    &auditor_amount.proof,
+                    domain,
+                    auditor_pubkey,
+                    &auditor_amount.ciphertext,
+                    amount,
+                )?;
+                Ok(Some(auditor_amount.ciphertext))
+            }
+            _ => Err(ErrorCode::AuditorAmountMismatch.into()),
+        }
+    }
+
+    /// Moves a position's balance from `old_commitment` to `new_commitment` in the pool
+    /// totals, counting it `reward_weight` times.
+    /// Both are [`UserStake::balance_commitment`]s, bound to the balance by an equality proof,
+    /// so the total commits to what the positions really hold. It changes by the difference
+    /// of two fresh commitments, so the change does not reveal the amount.
+    pub fn record_balance_change(
+        &mut self,
+        old_commitment: &[u8; 32],
+        new_commitment: &[u8; 32],
+        direction: zk_utils::BalanceUpdate,
+        auditor_amount: Option<&[u8; 64]>,
+        reward_weight: u64,
+    ) -> Result<()> {
+        let weight = zk_utils::amount_scalar(reward_weight);
+        let old_commitment = PodRistrettoPoint(*old_commitment);
+        let new_commitment = PodRistrettoPoint(*new_commitment);
+        let total = subtract_ristretto(&new_commitment, &old_commitment)
+            .and_then(|delta| multiply_ristretto(&weight, &delta))
+            .and_then(|delta| add_ristretto(&PodRistrettoPoint(self.total_commitment), &delta))
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        self.total_commitment = total.0;
+        if let Some(auditor_amount) = auditor_amount {
+            let weighted = zk_utils::scale_ciphertext(&weight, auditor_amount)
+                .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+            self.auditor_total = match direction {
+                zk_utils::BalanceUpdate::Credit => {
+                    zk_utils::add_ciphertexts(&self.auditor_total, &weighted)
+                }
+                zk_utils::BalanceUpdate::Debit => {
+                    zk_utils::subtract_ciphertexts(&self.auditor_total, &weighted)
+                }
+            }
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maximum number of reward rates a pool remembers.
+pub const MAX_REWARD_RATE_HISTORY: usize = 8;
+
+/// A reward rate and when it took effect.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RewardRatePeriod {
+    pub start_timestamp: i64,
+    /// The pool's `acc_reward_per_share` when the rate took effect.
+    pub start_acc_reward_per_share: u128,
+    pub reward_rate: u64,
+}
+
+impl RewardRatePeriod {
+    // Total space: 8 (start) + 16 (accumulator) + 8 (rate) = 32 bytes.
+    pub const LEN: usize = 8 + 16 + 8;
+}
+
+/// Reward multiplier of a position below every tier, in basis points (1.0x).
+pub const BASE_REWARD_MULTIPLIER_BPS: u16 = 10_000;
+
+/// Maximum number of reward tiers a pool can define.
+pub const MAX_REWARD_TIERS: usize = 4;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RewardTier {
+    /// Time since the position's last stake needed to reach the tier.
+    pub min_stake_seconds: i64,
+    pub multiplier_bps: u16,
+}
+
+/// Staking-duration tiers that scale a position's rewards. Set at `initialize_pool` and by
+/// governance; tiers are ordered by duration and never lower the multiplier.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RewardTiers {
+    pub tiers: Vec<RewardTier>,
+}
+
+impl RewardTiers {
+    // Total space: 4 + 4 * (8 + 2) = 44 bytes.
+    pub const LEN: usize = 4 + MAX_REWARD_TIERS * (8 + 2);
+
+    pub fn validate(&self) -> Result<()> {
+        if self.tiers.len() > MAX_REWARD_TIERS {
+            return Err(ErrorCode::InvalidRewardTiers.into());
+        }
+        let mut previous = RewardTier {
+            min_stake_seconds: -1,
+            multiplier_bps: BASE_REWARD_MULTIPLIER_BPS,
+        };
+        for tier in &self.tiers {
+            if tier.min_stake_seconds <= previous.min_stake_seconds
+                || tier.multiplier_bps < previous.multiplier_bps
+            {
+                return Err(ErrorCode::InvalidRewardTiers.into());
+            }
+            previous = *tier;
+        }
+        Ok(())
+    }
+
+    /// The multiplier of the highest tier a position last staked into at `stake_timestamp`
+    /// has reached by `now`.
+    pub fn multiplier_bps(&self, stake_timestamp: i64, now: i64) -> u16 {
+        let staked_for = now.saturating_sub(stake_timestamp);
+        self.tiers
+            .iter()
+            .take_while(|tier| staked_for >= tier.min_stake_seconds)
+            .last()
+            .map_or(BASE_REWARD_MULTIPLIER_BPS, |tier| tier.multiplier_bps)
+    }
+}
+
+/// A mint the pool accepts stakes in, whitelisted by `add_asset`.
+/// Lives at `[SEED, pool, mint]`; positions in it at `["user_stake", user, pool, mint]`.
+#[account]
+pub struct AssetVault {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    /// Pool-owned confidential token account holding the mint's stakes.
+    pub token_account: Pubkey,
+    /// How many times a balance in this mint counts towards the pool's total.
+    pub reward_weight: u64,
+    pub bump: u8,
+}
+
+impl AssetVault {
+    // Total space: 32 (pool) + 32 (mint) + 32 (token account) + 8 (weight) + 1 = 105 bytes.
+    pub const LEN: usize = 32 + 32 + 32 + 8 + 1;
+
+    pub const SEED: &'static [u8] = b"vault";
+
+    /// Reward factor of a position in this mint at tier multiplier `multiplier_bps`.
+    pub fn reward_factor(&self, multiplier_bps: u16) -> Result<u64> {
+        self.reward_weight
+            .checked_mul(multiplier_bps as u64)
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+}
+
+/// When staked tokens may leave the pool. Set at `initialize_pool` and by governance.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockupPolicy {
+    /// Seconds after a position's last stake before it can exit without a penalty.
+    pub min_lock_seconds: i64,
+    /// Seconds between `unbond` and the matching `withdraw`; 0 lets `withdraw` pay out directly.
+    pub unbonding_seconds: i64,
+    /// Share of an early exit paid to the treasury. 0 rejects early exits instead.
+    pub early_withdraw_penalty_bps: u16,
+}
+
+impl LockupPolicy {
+    // Total space: 8 + 8 + 2 = 18 bytes.
+    pub const LEN: usize = 8 + 8 + 2;
+
+    pub fn validate(&self) -> Result<()> {
+        if self.min_lock_seconds < 0
+            || self.unbonding_seconds < 0
+            || self.early_withdraw_penalty_bps > 10_000
+        {
+            return Err(ErrorCode::InvalidLockupPolicy.into());
+        }
+        Ok(())
+    }
+
+    /// The penalty on exiting with `amount` at `exit_timestamp` from a position last staked
+    /// into at `stake_timestamp`.
+    pub fn early_exit_penalty(
+        &self,
+        stake_timestamp: i64,
+        exit_timestamp: i64,
+        amount: u64,
+    ) -> Result<u64> {
+        if exit_timestamp.saturating_sub(stake_timestamp) >= self.min_lock_seconds {
+            return Ok(0);
+        }
+        if self.early_withdraw_penalty_bps == 0 {
+            return Err(ErrorCode::StakeDurationNotMet.into());
+        }
+        Ok((amount as u128 * self.early_withdraw_penalty_bps as u128 / 10_000) as u64)
+    }
+}
+
+/// An amount encrypted to the pool auditor, with the proof that it is the instruction's amount.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AuditorAmount {
+    pub ciphertext: [u8; 64],
+    pub proof: ProofEnvelope,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVerifyingKeyRegistry<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VerifyingKeyRegistry::BASE_LEN,
+        seeds = [b"vk_registry", pool.key().as_ref()],
+        bump,
+    )]
+    pub registry: Account<'info, VerifyingKeyRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterVerifyingKey<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(mut, has_one = pool, seeds = [b"vk_registry", pool.key().as_ref()], bump = registry.bump)]
+    pub registry: Account<'info, VerifyingKeyRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// The statements a proof can be about. Groth16 circuits have their verifying keys
+/// registered per pool; `Balance` and `Range` are checked by the sigma and Bulletproofs verifiers.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    Balance,
+    Reward,
+    Vote,
+    Identity,
+    Governance,
+    Range,
+    DecryptionShare,
+    StakeWeight,
+    AuditorAmount,
+    RewardClaim,
+    CiphertextCommitmentEquality,
+}
+
+/// The proof systems a `ProofEnvelope` can carry.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofSystem {
+    Sigma,        // Chaum–Pedersen balance-update proof, verified in-program.
+    Bulletproofs, // Range proof verified by the ZK Token Proof program; `proof` is its location.
+    Groth16,      // BN254 SNARK verified against the pool's verifying-key registry.
+}
+
+/// How a `ProofEnvelope`'s proof bytes are encoded.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofCompression {
+    None,
+    Zlib, // Inflated on-chain by `zk_utils::decompress_proof` before verification.
+}
+
+/// Framing every proof argument is wrapped in, so a proof made for one instruction,
+/// pool or circuit cannot be passed off as another.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProofEnvelope {
+    pub version: u8,
+    pub system: ProofSystem,
+    pub circuit: Circuit,
+    pub public_input_hash: [u8; 32], // `zk_utils::public_input_hash` of the statement.
+    pub domain: [u8; 32],            // `zk_utils::domain_separator(instruction, pool)`.
+    pub compression: ProofCompression,
+    pub proof: Vec<u8>,
+}
+
+/// A proof argument: the envelope itself, or the address of a sealed `ProofBuffer` it was
+/// uploaded to. Buffers are passed in the instruction's remaining accounts.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProofSource {
+    Inline(ProofEnvelope),
+    Buffer(Pubkey),
+}
+
+impl ProofSource {
+    /// The envelope, read from its buffer among `accounts` if it was uploaded.
+    pub fn resolve(&self, accounts: &[AccountInfo]) -> Result<Cow<'_, ProofEnvelope>> {
+        match self {
+            ProofSource::Inline(envelope) => Ok(Cow::Borrowed(envelope)),
+            ProofSource::Buffer(address) => {
+                let info = accounts
+                    .iter()
+                    .find(|info| info.key == address)
+                    .ok_or(ErrorCode::ProofBufferMissing)?;
+                if *info.owner != crate::ID {
+                    return Err(anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram.into());
+                }
+                ProofBuffer::try_deserialize(&mut &info.try_borrow_data()?[..])?
+                    .envelope()
+                    .map(Cow::Owned)
+            }
+        }
+    }
+}
+
+/// A proof envelope uploaded in chunks, at `[SEED, owner, buffer_id]`.
+#[account]
+pub struct ProofBuffer {
+    pub owner: Pubkey,
+    pub buffer_id: u64,
+    /// SHA-256 of the complete `data`, committed when the buffer is opened.
+    pub hash: [u8; 32],
+    /// How many bytes of `data` have been written, from the start.
+    pub written: u32,
+    pub sealed: bool,
+    pub bump: u8,
+    /// The serialized `ProofEnvelope`; its length is fixed when the buffer is opened.
+    pub data: Vec<u8>,
+}
+
+impl ProofBuffer {
+    // Space without the data: 32 (owner) + 8 + 32 (hash) + 4 + 1 + 1 + 4 (vec len) = 82 bytes.
+    pub const BASE_LEN: usize = 32 + 8 + 32 + 4 + 1 + 1 + 4;
+
+    /// Largest envelope a buffer holds, so the account can be created by a single CPI.
+    pub const MAX_DATA_LEN: usize =
+        anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE - 8 - Self::BASE_LEN;
+
+    pub const SEED: &'static [u8] = b"proof_buffer";
+
+    pub fn space(len: usize) -> usize {
+        Self::BASE_LEN + len
+    }
+
+    /// Writes `chunk` at `offset`, which must be where the last write ended, and seals the
+    /// buffer if that fills it. Filling it with contents that do not match `hash` fails.
+    pub fn write(&mut self, offset: u32, chunk: &[u8]) -> Result<()> {
+        if self.sealed {
+            return Err(ErrorCode::ProofBufferSealed.into());
+        }
+        if offset != self.written || chunk.is_empty() {
+            return Err(ErrorCode::InvalidProofChunk.into());
+        }
+        let start = offset as usize;
+        let end = start + chunk.len();
+        if end > self.data.len() {
+            return Err(ErrorCode::InvalidProofChunk.into());
+        }
+        self.data[start..end].copy_from_slice(chunk);
+        self.written = end as u32;
+        if end == self.data.len() {
+            if hashv(&[&self.data]).to_bytes() != self.hash {
+                return Err(ErrorCode::ProofBufferHashMismatch.into());
+            }
+            self.sealed = true;
+        }
+        Ok(())
+    }
+
+    /// The envelope in a sealed buffer.
+    pub fn envelope(&self) -> Result<ProofEnvelope> {
+        if !self.sealed {
+            return Err(ErrorCode::ProofBufferNotSealed.into());
+        }
+        ProofEnvelope::try_from_slice(&self.data).map_err(|_| ErrorCode::MalformedProof.into())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(buffer_id: u64, len: u32)]
+pub struct OpenProofBuffer<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + ProofBuffer::space(len as usize),
+        seeds = [ProofBuffer::SEED, owner.key().as_ref(), &buffer_id.to_le_bytes()],
+        bump,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WriteProofChunk<'info> {
+    #[account(
+        mut,
+        has_one = owner,
+        seeds = [ProofBuffer::SEED, owner.key().as_ref(), &proof_buffer.buffer_id.to_le_bytes()],
+        bump = proof_buffer.bump,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseProofBuffer<'info> {
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner,
+        seeds = [ProofBuffer::SEED, owner.key().as_ref(), &proof_buffer.buffer_id.to_le_bytes()],
+        bump = proof_buffer.bump,
+    )]
+    pub proof_buffer: Account<'info, ProofBuffer>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+/// A Groth16 verifying key on BN254, with points in the big-endian alt_bn128 syscall encoding.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Groth16VerifyingKey {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    pub ic: Vec<[u8; 64]>, // One point per public input, plus the constant term.
+}
+
+impl Groth16VerifyingKey {
+    pub fn space(&self) -> usize {
+        64 + 128 * 3 + 4 + 64 * self.ic.len()
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RegisteredVerifyingKey {
+    pub circuit: Circuit,
+    pub version: u32, // Incremented on every rotation.
+    pub key: Groth16VerifyingKey,
+}
+
+/// Per-pool registry of Groth16 verifying keys, one per circuit.
+#[account]
+pub struct VerifyingKeyRegistry {
+    pub pool: Pubkey,
+    pub bump: u8,
+    pub keys: Vec<RegisteredVerifyingKey>,
+}
+
+impl VerifyingKeyRegistry {
+    // Space of an empty registry: 32 (pool) + 1 (bump) + 4 (vec len) = 37 bytes.
+    pub const BASE_LEN: usize = 32 + 1 + 4;
+
+    pub fn find(&self, circuit: Circuit) -> Option<&RegisteredVerifyingKey> {
+        self.keys.iter().find(|entry| entry.circuit == circuit)
+    }
+
+    pub fn space(&self) -> usize {
+        Self::BASE_LEN
+            + self
+                .keys
+                .iter()
+                .map(|entry| 1 + 4 + entry.key.space())
+                .sum::<usize>()
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeIdentityRegistry<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IdentityRegistry::LEN,
+        seeds = [b"identity_registry", pool.key().as_ref()],
+        bump,
+    )]
+    pub registry: Account<'info, IdentityRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppendIdentityCommitment<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(mut, has_one = pool, seeds = [b"identity_registry", pool.key().as_ref()], bump = registry.bump)]
+    pub registry: Account<'info, IdentityRegistry>,
+    pub authority: Signer<'info>,
+}
+
+/// Depth of the identity Merkle tree, i.e. room for 2^20 identities.
+pub const IDENTITY_TREE_DEPTH: usize = 20;
+
+/// How many recent roots an identity proof may reference, so proofs made just before
+/// another member joined still verify.
+pub const IDENTITY_ROOT_HISTORY: usize = 32;
+
+/// Per-pool incremental Poseidon Merkle tree of identity commitments.
+///
+/// Only the right-most path is stored (`filled_subtrees`), so appends cost `IDENTITY_TREE_DEPTH`
+/// hashes and the account size does not depend on the number of members.
+#[account]
+#[derive(Default)]
+pub struct IdentityRegistry {
+    pub pool: Pubkey,
+    pub authority: Pubkey, // Identity issuer (the pool authority).
+    pub bump: u8,
+    pub next_index: u64,
+    pub current_root_index: u8,
+    pub filled_subtrees: [[u8; 32]; IDENTITY_TREE_DEPTH],
+    pub zeros: [[u8; 32]; IDENTITY_TREE_DEPTH], // Root of an empty subtree at each level.
+    pub roots: [[u8; 32]; IDENTITY_ROOT_HISTORY], // Ring buffer of recent roots.
+}
+
+impl IdentityRegistry {
+    // Total space: 32 (pool) + 32 (authority) + 1 (bump) + 8 (next index) + 1 (root index)
+    // + 20 * 32 (filled subtrees) + 20 * 32 (zeros) + 32 * 32 (roots) = 2378 bytes.
+    pub const LEN: usize =
+        32 + 32 + 1 + 8 + 1 + IDENTITY_TREE_DEPTH * 32 * 2 + IDENTITY_ROOT_HISTORY * 32;
+
+    /// Resets the tree to empty: every subtree is the zero subtree and the only root is
+    /// the empty-tree root.
+    pub fn initialize_tree(&mut self) -> Result<()> {
+        let mut zero = [0u8; 32];
+        for level in 0..IDENTITY_TREE_DEPTH {
+            self.zeros[level] = zero;
+            self.filled_subtrees[level] = zero;
+            zero = zk_utils::poseidon_pair(&zero, &zero)?;
+        }
+        self.next_index = 0;
+        self.current_root_index = 0;
+        self.roots = [[0u8; 32]; IDENTITY_ROOT_HISTORY];
+        self.roots[0] = zero;
+        Ok(())
+    }
+
+    /// Inserts `leaf` at the next free index and records the new root. Returns the leaf index.
+    pub fn append(&mut self, leaf: [u8; 32]) -> Result<u64> {
+        if leaf >= zk_utils::BN254_SCALAR_MODULUS {
+            return Err(ErrorCode::InvalidIdentityCommitment.into());
+        }
+        let leaf_index = self.next_index;
+        if leaf_index >= 1u64 << IDENTITY_TREE_DEPTH {
+            return Err(ErrorCode::IdentityTreeFull.into());
+        }
+
+        let mut index = leaf_index;
+        let mut node = leaf;
+        for level in 0..IDENTITY_TREE_DEPTH {
+            node = if index & 1 == 0 {
+                self.filled_subtrees[level] = node;
+                zk_utils::poseidon_pair(&node, &self.zeros[level])?
+            } else {
+                zk_utils::poseidon_pair(&self.filled_subtrees[level], &node)?
+            };
+            index /= 2;
+        }
+
+        self.current_root_index =
+            ((self.current_root_index as usize + 1) % IDENTITY_ROOT_HISTORY) as u8;
+        self.roots[self.current_root_index as usize] = node;
+        self.next_index = leaf_index + 1;
+        Ok(leaf_index)
+    }
+
+    /// The most recent root.
+    pub fn root(&self) -> [u8; 32] {
+        self.roots[self.current_root_index as usize]
+    }
+
+    /// Whether `root` is one of the last `IDENTITY_ROOT_HISTORY` roots.
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        // Unused ring-buffer slots are zero, so a zero root must never match.
+        *root != [0u8; 32] && self.roots.iter().any(|known| known == root)
+    }
+}
+
+#[derive(Accounts)]
+pub struct AddAsset<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AssetVault::LEN,
+        seeds = [AssetVault::SEED, pool.key().as_ref(), token_mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, AssetVault>,
+    #[account(
+        token::mint = token_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = confidential_token_program)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPosition<'info> {
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        seeds = [AssetVault::SEED, pool.key().as_ref(), token_mint.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AssetVault>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStake::LEN,
+        seeds = [
+            b"user_stake",
+            user.key().as_ref(),
+            pool.key().as_ref(),
+            token_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
+    #[account(
+        token::mint = token_mint,
+        token::authority = user,
+        token::token_program = confidential_token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = confidential_token_program)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeAccounts<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        seeds = [AssetVault::SEED, pool.key().as_ref(), token_mint.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AssetVault>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            user.key().as_ref(),
+            pool.key().as_ref(),
+            token_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = user,
+        token::token_program = confidential_token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        address = vault.token_account,
+        token::token_program = confidential_token_program,
+    )]
+    pub pool_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = confidential_token_program)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+}
+
+#[derive(Accounts)]
+pub struct BatchStake<'info> {
+    pub user: Signer<'info>,
+}
+
+/// The user stake account stores a confidential balance commitment, stake timestamp, and asset type.
+#[account]
+pub struct UserStake {
+    pub confidential_balance: [u8; 64], // Twisted ElGamal balance ciphertext (commitment || handle)
+    pub encrypted_data: Vec<u8>,          // Optional encrypted metadata
+    pub stake_timestamp: i64,             // Time of the last stake, when the lockup restarts
+    pub asset_mint: Pubkey,               // The mint of the staked asset (for multi-asset support)
+    pub elgamal_pubkey: [u8; 32],         // Owner's ElGamal key the balance is encrypted under
+    pub balance_updated_slot: u64,        // Slot of the last balance change
+    pub prior_balance: [u8; 64],          // Balance before that change, for proposal snapshots
+    pub prior_balance_slot: u64,          // Slot from which `prior_balance` applied
+    pub unbonding_amount: u64,            // Amount requested by `unbond`, 0 if none
+    pub unbonding_penalty: u64,           // Early-withdraw penalty settled at `unbond`
+    pub unbond_timestamp: i64,            // When `unbond` was called
+    pub reward_debt: [u8; 64],            // Encrypted rewards accounted for since `reward_timestamp`, in 2^-64 tokens
+    pub reward_timestamp: i64,            // When the position's rewards were last settled
+    pub reward_acc: u128,                 // The pool's `acc_reward_per_share` at `reward_timestamp`
+    pub balance_commitment: [u8; 32],     // Pedersen commitment equality-proven to the balance
+}
+
+impl UserStake {
+    // Total space: 64 + 4 + 64 + 8 + 32 + 32 + 8 + 64 + 8 + 8 + 8 + 8 + 64 + 8 + 16 + 32
+    //              = 428 bytes.
+    pub const LEN: usize = 64 + 4 + 64 + 8 + 32 + 32 + 8 + 64 + 8 + 8 + 8 + 8 + 64 + 8 + 16 + 32;
+
+    /// Encryption of the rewards `balance` earns over `reward_per_unit` (see
+    /// [`LiquidityPool::reward_per_unit`]), in `2^-64` tokens.
+    pub fn accrued_rewards(reward_per_unit: u128, balance: &[u8; 64]) -> Result<[u8; 64]> {
+        // With a balance below 2^64 the product stays below 2^192, well inside the group
+        // order, so it never wraps.
+        zk_utils::scale_ciphertext(&zk_utils::u128_scalar(reward_per_unit), balance)
+            .ok_or(ErrorCode::InvalidBalanceCommitment.into())
+    }
+
+    /// Encryption of the rewards the position has earned and not claimed, given the
+    /// `reward_per_unit` it has earned since `reward_timestamp`.
+    pub fn pending_rewards(&self, reward_per_unit: u128) -> Result<[u8; 64]> {
+        let accrued = Self::accrued_rewards(reward_per_unit, &self.confidential_balance)?;
+        zk_utils::subtract_ciphertexts(&accrued, &self.reward_debt)
+            .ok_or(ErrorCode::InvalidBalanceCommitment.into())
+    }
+
+    /// Settles the position's rewards at `timestamp`, when the pool's accumulator stood at
+    /// `acc_reward_per_share`, with the encrypted rewards `owed` still to be claimed. Later
+    /// accrual is measured from here, on whatever balance and tier the position then has.
+    pub fn settle_rewards(
+        &mut self,
+        timestamp: i64,
+        acc_reward_per_share: u128,
+        owed: &[u8; 64],
+    ) -> Result<()> {
+        self.reward_debt = zk_utils::subtract_ciphertexts(&[0u8; 64], owed)
+            .ok_or(ErrorCode::InvalidBalanceCommitment)?;
+        self.reward_timestamp = timestamp;
+        self.reward_acc = acc_reward_per_share;
+        Ok(())
+    }
+
+    /// Records an `unbond` request for `amount` at `now`, settling its penalty.
+    pub fn start_unbonding(&mut self, lockup: &LockupPolicy, amount: u64, now: i64) -> Result<()> {
+        if lockup.unbonding_seconds == 0 {
+            return Err(ErrorCode::UnbondingNotEnabled.into());
+        }
+        if self.unbonding_amount != 0 {
+            return Err(ErrorCode::AlreadyUnbonding.into());
+        }
+        if amount == 0 {
+            return Err(ErrorCode::UnbondingRequired.into());
+        }
+        self.unbonding_penalty = lockup.early_exit_penalty(self.stake_timestamp, now, amount)?;
+        self.unbonding_amount = amount;
+        self.unbond_timestamp = now;
+        Ok(())
+    }
+
+    /// Checks a withdrawal of `amount` at `now` against `lockup`, consuming the position's
+    /// completed `unbond` request if the pool requires one, and returns the penalty owed.
+    pub fn take_withdrawal(&mut self, lockup: &LockupPolicy, amount: u64, now: i64) -> Result<u64> {
+        if lockup.unbonding_seconds == 0 {
+            return lockup.early_exit_penalty(self.stake_timestamp, now, amount);
+        }
+        if self.unbonding_amount == 0 || self.unbonding_amount != amount {
+            return Err(ErrorCode::UnbondingRequired.into());
+        }
+        if now.saturating_sub(self.unbond_timestamp) < lockup.unbonding_seconds {
+            return Err(ErrorCode::UnbondingNotComplete.into());
+        }
+        let penalty = self.unbonding_penalty;
+        self.unbonding_amount = 0;
+        self.unbonding_penalty = 0;
+        Ok(penalty)
+    }
+
+    /// Records a stake into the position. The lockup restarts for the whole position: its
+    /// balance is hidden, so the program cannot weight the lock by the amounts staked or keep
+    /// per-deposit tranches without revealing them.
+    pub fn record_stake(
+        &mut self,
+        new_balance: [u8; 64],
+        balance_commitment: [u8; 32],
+        slot: u64,
+        unix_timestamp: i64,
+    ) {
+        self.set_balance(new_balance, balance_commitment, slot);
+        self.stake_timestamp = unix_timestamp;
+    }
+
+    /// Replaces the balance and its commitment, keeping the balance it replaces for proposals
+    /// snapshotted before `slot`. Several changes within a slot keep the balance from before
+    /// the first.
+    pub fn set_balance(&mut self, balance: [u8; 64], balance_commitment: [u8; 32], slot: u64) {
+        if slot != self.balance_updated_slot {
+            self.prior_balance = self.confidential_balance;
+            self.prior_balance_slot = self.balance_updated_slot;
+            self.balance_updated_slot = slot;
+        }
+        self.confidential_balance = balance;
+        self.balance_commitment = balance_commitment;
+    }
+
+    /// The balance as of a proposal created at `snapshot_slot`, i.e. the last one set before
+    /// that slot. Only one earlier balance is kept, so after two changes since the snapshot
+    /// it is no longer available.
+    pub fn balance_at(&self, snapshot_slot: u64) -> Option<[u8; 64]> {
+        if self.balance_updated_slot < snapshot_slot {
+            Some(self.confidential_balance)
+        } else if self.prior_balance_slot < snapshot_slot {
+            Some(self.prior_balance)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        seeds = [AssetVault::SEED, pool.key().as_ref(), token_mint.key().as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AssetVault>,
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            user.key().as_ref(),
+            pool.key().as_ref(),
+            token_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = vault.token_account,
+        token::token_program = confidential_token_program,
+    )]
+    pub pool_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = user,
+        token::token_program = confidential_token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = confidential_token_program)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    /// CHECK: Instructions sysvar or ZK Token Proof context-state account, validated in `zk_utils::verify_transfer_proof`.
+    pub proof_account: UncheckedAccount<'info>,
+    /// CHECK: Context-state account holding the pool's verified `WithdrawData` proof, validated by Token-2022.
+    pub pool_withdraw_proof: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = pool.treasury,
+        token::token_program = confidential_token_program,
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct Unbond<'info> {
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            user.key().as_ref(),
+            pool.key().as_ref(),
+            user_stake.asset_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        seeds = [AssetVault::SEED, pool.key().as_ref(), user_stake.asset_mint.as_ref()],
+        bump = vault.bump,
+    )]
+    pub vault: Account<'info, AssetVault>,
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            user.key().as_ref(),
+            pool.key().as_ref(),
+            user_stake.asset_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [RewardConfig::VAULT_SEED, pool.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::token_program = confidential_token_program,
+    )]
+    pub user_reward_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.token_mint, mint::token_program = confidential_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    /// CHECK: Instructions sysvar or ZK Token Proof context-state account, validated in `zk_utils::verify_transfer_proof`.
+    pub proof_account: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CommitSwap<'info> {
+    #[account(mut)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        init,
+        payer = trader,
+        space = 8 + TradeOrder::LEN,
+        seeds = [b"trade_order", trader.key().as_ref(), pool.key().as_ref()],
+        bump,
+    )]
+    pub trade_order: Account<'info, TradeOrder>,
+    #[account(mut)]
+    pub trader: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct RevealSwap<'info> {
+    #[account(
+        mut,
+        has_one = trader,
+        seeds = [b"trade_order", trader.key().as_ref(), pool.key().as_ref()],
+        bump = trade_order.bump,
+    )]
+    pub trade_order: Account<'info, TradeOrder>,
+    pub trader: Signer<'info>,
+    pub pool: Account<'info, LiquidityPool>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTradeOrder<'info> {
+    #[account(
+        mut,
+        close = trader,
+        has_one = trader,
+        seeds = [b"trade_order", trader.key().as_ref(), pool.key().as_ref()],
+        bump = trade_order.bump,
+    )]
+    pub trade_order: Account<'info, TradeOrder>,
+    /// CHECK: Only receives the order's rent; checked against `trade_order.trader`.
+    #[account(mut)]
+    pub trader: UncheckedAccount<'info>,
+    pub pool: Account<'info, LiquidityPool>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewardConfig<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RewardConfig::LEN,
+        seeds = [RewardConfig::SEED, pool.key().as_ref()],
+        bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [RewardConfig::VAULT_SEED, pool.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.token_mint, mint::token_program = confidential_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateRewardConfig<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [RewardConfig::SEED, pool.key().as_ref()],
+        bump = reward_config.bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+    pub authority: Signer<'info>,
+}
+
+/// Who may distribute a pool's rewards and how much, per epoch. The PDA, at
+/// `[SEED, pool]`, is the mint authority for the pool's rewards, which are held until
+/// claimed in the pool-owned token account at `[VAULT_SEED, pool]`.
+#[account]
+pub struct RewardConfig {
+    pub pool: Pubkey,
+    pub distributor: Pubkey,
+    pub epoch_seconds: i64,
+    /// Most tokens `distribute_rewards` may mint in one epoch.
+    pub emission_cap: u64,
+    /// Index of the current epoch, counting from `initialize_reward_config`.
+    pub epoch: u64,
+    pub epoch_start: i64,
+    /// Tokens minted so far in the current epoch.
+    pub epoch_emitted: u64,
+    pub bump: u8,
+}
+
+impl RewardConfig {
+    // Total space: 32 (pool) + 32 (distributor) + 8 + 8 + 8 + 8 + 8 + 1 = 105 bytes.
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1;
+
+    pub const SEED: &'static [u8] = b"reward_config";
+    pub const VAULT_SEED: &'static [u8] = b"reward_vault";
+
+    /// Seeds the configuration signs reward mints with.
+    pub fn signer_seeds(&self) -> [&[u8]; 3] {
+        [
+            Self::SEED,
+            self.pool.as_ref(),
+            std::slice::from_ref(&self.bump),
+        ]
+    }
+
+    pub fn configure(
+        &mut self,
+        distributor: Pubkey,
+        epoch_seconds: i64,
+        emission_cap: u64,
+    ) -> Result<()> {
+        if epoch_seconds <= 0 {
+            return Err(ErrorCode::InvalidRewardConfig.into());
+        }
+        self.distributor = distributor;
+        self.epoch_seconds = epoch_seconds;
+        self.emission_cap = emission_cap;
+        Ok(())
+    }
+
+    /// Moves on to the epoch `now` falls in, if the current one has ended.
+    pub fn advance_epoch(&mut self, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.epoch_start);
+        if elapsed < self.epoch_seconds {
+            return Ok(());
+        }
+        let epochs = elapsed / self.epoch_seconds;
+        self.epoch = self
+            .epoch
+            .checked_add(epochs as u64)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.epoch_start += epochs * self.epoch_seconds;
+        self.epoch_emitted = 0;
+        Ok(())
+    }
+
+    /// Counts `amount` against the emission cap of the epoch `now` falls in.
+    pub fn record_emission(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.advance_epoch(now)?;
+        let emitted = self
+            .epoch_emitted
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if emitted > self.emission_cap {
+            return Err(ErrorCode::EmissionCapExceeded.into());
+        }
+        self.epoch_emitted = emitted;
         Ok(())
     }
+}
+
+#[derive(Accounts)]
+pub struct DistributeRewards<'info> {
+    #[account(has_one = token_mint)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        has_one = pool,
+        has_one = distributor,
+        seeds = [RewardConfig::SEED, pool.key().as_ref()],
+        bump = reward_config.bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+    pub distributor: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [RewardConfig::VAULT_SEED, pool.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        mint::authority = reward_config,
+        mint::token_program = confidential_token_program,
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    #[account(has_one = pool, seeds = [b"vk_registry", pool.key().as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
+}
+
+#[derive(Accounts)]
+#[instruction(epoch: u64)]
+pub struct PostRewardEpoch<'info> {
+    #[account(has_one = token_mint)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        has_one = pool,
+        has_one = distributor,
+        seeds = [RewardConfig::SEED, pool.key().as_ref()],
+        bump = reward_config.bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+    #[account(
+        init,
+        payer = distributor,
+        space = 8 + RewardEpoch::LEN,
+        seeds = [RewardEpoch::SEED, pool.key().as_ref(), &epoch.to_le_bytes()],
+        bump,
+    )]
+    pub reward_epoch: Account<'info, RewardEpoch>,
+    #[account(mut)]
+    pub distributor: Signer<'info>,
+    #[account(
+        init,
+        payer = distributor,
+        seeds = [RewardEpoch::VAULT_SEED, pool.key().as_ref(), &epoch.to_le_bytes()],
+        bump,
+        token::mint = token_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub epoch_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Context-state account holding the vault key's verified `PubkeyValidityData` proof, validated by Token-2022.
+    pub vault_pubkey_proof: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        mint::authority = reward_config,
+        mint::token_program = confidential_token_program,
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nullifier_hash: [u8; 32])]
+pub struct ClaimEpochReward<'info> {
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        has_one = pool,
+        seeds = [RewardEpoch::SEED, pool.key().as_ref(), &reward_epoch.epoch.to_le_bytes()],
+        bump = reward_epoch.bump,
+    )]
+    pub reward_epoch: Account<'info, RewardEpoch>,
+    /// CHECK: Nullifier PDA, created in `spend_nullifier`; its existence means the leaf has been claimed.
+    #[account(mut, seeds = [b"nullifier", reward_epoch.key().as_ref(), nullifier_hash.as_ref()], bump)]
+    pub nullifier: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub claimant: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [RewardEpoch::VAULT_SEED, pool.key().as_ref(), &reward_epoch.epoch.to_le_bytes()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub epoch_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::token_program = confidential_token_program,
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.token_mint, mint::token_program = confidential_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Context-state account holding the vault's verified `TransferData` proof, checked in `zk_utils::load_transfer_proof_context` and by Token-2022.
+    pub transfer_proof: UncheckedAccount<'info>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    #[account(has_one = pool, seeds = [b"vk_registry", pool.key().as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
+    pub system_program: Program<'info, System>,
+}
 
-    /// Implement private order matching in the AMM using zk-proofs.
-    pub fn private_order_matching(ctx: Context<AdditionalFeatures>) -> Result<()> {
-        // TODO: Create a private order book via zk-proofs so that liquidity levels remain confidential.
-        Ok(())
-    }
+#[derive(Accounts)]
+pub struct SweepRewardEpoch<'info> {
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        has_one = pool,
+        seeds = [RewardEpoch::SEED, pool.key().as_ref(), &reward_epoch.epoch.to_le_bytes()],
+        bump = reward_epoch.bump,
+    )]
+    pub reward_epoch: Account<'info, RewardEpoch>,
+    #[account(
+        mut,
+        seeds = [RewardEpoch::VAULT_SEED, pool.key().as_ref(), &reward_epoch.epoch.to_le_bytes()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub epoch_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Context-state account holding the vault's verified `WithdrawData` proof, validated by Token-2022.
+    pub vault_withdraw_proof: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = pool.treasury,
+        token::token_program = confidential_token_program,
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = pool.token_mint, mint::token_program = confidential_token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+}
 
-    /// Enable private lending and borrowing markets using zk-enabled credit scores.
-    pub fn private_lending(ctx: Context<AdditionalFeatures>) -> Result<()> {
-        // TODO: Implement confidential lending pools without revealing borrower identities.
-        Ok(())
-    }
+/// One epoch's Merkle distribution of rewards, at `[SEED, pool, epoch]`. The rewards are
+/// held in the pool-owned token account at `[VAULT_SEED, pool, epoch]`.
+#[account]
+pub struct RewardEpoch {
+    pub pool: Pubkey,
+    pub epoch: u64,
+    /// Poseidon root of the epoch's `(commitment, encrypted reward)` leaves.
+    pub merkle_root: [u8; 32],
+    pub leaf_count: u32,
+    pub total_amount: u64,
+    /// When the claim window closes and unclaimed rewards can be swept.
+    pub expiry: i64,
+    pub swept: bool,
+    pub bump: u8,
+}
 
-    /// Introduce zk-proof staking challenges for LPs.
-    pub fn zk_proof_staking_challenges(ctx: Context<AdditionalFeatures>) -> Result<()> {
-        // TODO: Allow LPs to contest suspicious liquidity changes using zk-proofs without revealing full stake details.
-        Ok(())
-    }
+impl RewardEpoch {
+    // Total space: 32 (pool) + 8 + 32 (root) + 4 + 8 + 8 + 1 + 1 = 94 bytes.
+    pub const LEN: usize = 32 + 8 + 32 + 4 + 8 + 8 + 1 + 1;
 
-    /// Display on-chain liquidity privacy metrics by showing aggregate liquidity without exposing individual positions.
-    pub fn onchain_liquidity_privacy_metrics(ctx: Context<AdditionalFeatures>) -> Result<()> {
-        // TODO: Implement on-chain metrics that protect individual LP privacy.
-        Ok(())
-    }
+    pub const SEED: &'static [u8] = b"reward_epoch";
+    pub const VAULT_SEED: &'static [u8] = b"epoch_vault";
 
-    /// Enable zk-encrypted messaging for LP coordination.
-    pub fn zk_encrypted_messaging(
-        ctx: Context<AdditionalFeatures>,
-        message: Vec<u8>,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // TODO: Develop an off-chain zk-enabled messaging system for LP coordination.
-        zk_utils::verify_identity_proof(zk_proof)?;
+    /// Closes the epoch for good once its claim window is over at `now`. Claimed amounts are
+    /// hidden, so the vault's confidential balance is what bounds the claims and what is left
+    /// to sweep.
+    pub fn sweep(&mut self, now: i64) -> Result<()> {
+        if now < self.expiry {
+            return Err(ErrorCode::RewardEpochOpen.into());
+        }
+        if self.swept {
+            return Err(ErrorCode::RewardEpochSwept.into());
+        }
+        self.swept = true;
         Ok(())
     }
 }
 
-// ---------------------------------------------------------------------
-// Account Definitions
-// ---------------------------------------------------------------------
+#[derive(Accounts)]
+pub struct ApplyPoolPendingBalance<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(
+        mut,
+        token::authority = pool,
+        token::token_program = confidential_token_program,
+    )]
+    pub pool_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub authority: Signer<'info>,
+    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+}
 
 #[derive(Accounts)]
-pub struct InitializePool<'info> {
+pub struct InitializeGovernance<'info> {
+    #[account(has_one = authority)]
+    pub pool: Account<'info, LiquidityPool>,
     #[account(
         init,
         payer = authority,
-        space = 8 + LiquidityPool::LEN,
-        seeds = [b"pool", token_mint.key().as_ref()],
+        space = 8 + Governance::LEN,
+        seeds = [b"governance", pool.key().as_ref()],
         bump,
     )]
-    pub pool: Account<'info, LiquidityPool>,
-    pub token_mint: Account<'info, Mint>,
+    pub governance: Account<'info, Governance>,
     #[account(mut)]
     pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
-/// The liquidity pool configuration.
+/// Governance account for proposal-based reward parameter updates and confidential voting.
 #[account]
-pub struct LiquidityPool {
+pub struct Governance {
     pub authority: Pubkey,
-    pub token_mint: Pubkey,
-    pub total_staked: u64,
+    pub pool: Pubkey,
+    /// Minimum number of votes for a proposal to pass.
+    pub quorum: u64,
+    /// Share of votes in favour needed to pass, in basis points.
+    pub threshold_bps: u16,
+    /// Length of each proposal's voting window.
+    pub voting_period_slots: u64,
+    /// How much each ballot counts.
+    pub vote_weighting: VoteWeighting,
+    /// Also the id of the next proposal.
+    pub proposal_count: u64,
     pub bump: u8,
-    // Additional configuration fields (e.g., fee rate, trade volume) can be added here.
 }
 
-impl LiquidityPool {
-    // Total space: 32 (authority) + 32 (mint) + 8 (u64) + 1 (bump) = 73 bytes.
-    pub const LEN: usize = 32 + 32 + 8 + 1;
+impl Governance {
+    // Total space: 32 + 32 + 8 + 2 + 8 + 9 (vote weighting) + 8 + 1 = 100 bytes.
+    pub const LEN: usize = 32 + 32 + 8 + 2 + 8 + 9 + 8 + 1;
+}
+
+/// How ballots are weighted.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoteWeighting {
+    /// One vote per registered identity, plus the votes delegated to it (`cast_vote`).
+    Identity,
+    /// One vote per `stake_per_vote` base units of confidential stake at the proposal's
+    /// snapshot (`register_stake_weight` and `cast_stake_vote`).
+    Stake { stake_per_vote: u64 },
 }
 
 #[derive(Accounts)]
-pub struct StakeAccounts<'info> {
-    #[account(mut, has_one = token_mint)]
-    pub pool: Account<'info, LiquidityPool>,
-    #[account(mut)]
-    pub user: Signer<'info>,
+pub struct InitializeTallyCommittee<'info> {
+    #[account(
+        has_one = authority,
+        seeds = [b"governance", governance.pool.as_ref()],
+        bump = governance.bump,
+    )]
+    pub governance: Account<'info, Governance>,
     #[account(
         init,
-        payer = user,
-        space = 8 + UserStake::LEN,
-        seeds = [b"user_stake", user.key().as_ref(), pool.key().as_ref()],
+        payer = authority,
+        space = 8 + TallyCommittee::LEN,
+        seeds = [b"tally_committee", governance.key().as_ref()],
         bump,
     )]
-    pub user_stake: Account<'info, UserStake>,
-    #[account(mut)]
-    pub user_token_account: Account<'info, ConfidentialTokenAccount>,
+    pub tally_committee: Account<'info, TallyCommittee>,
     #[account(mut)]
-    pub pool_token_account: Account<'info, ConfidentialTokenAccount>,
-    pub token_mint: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
-    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
-/// The user stake account stores a confidential balance commitment, stake timestamp, and asset type.
+/// Maximum number of members in a tally committee.
+pub const MAX_COMMITTEE_SIZE: usize = 10;
+
+/// A tally committee member. Members are numbered from 1 in list order, and `share_pubkey` is
+/// `xᵢ·G` for the member's Shamir share `xᵢ = f(i)` of the election secret key `f(0)`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CommitteeMember {
+    pub authority: Pubkey,
+    pub share_pubkey: [u8; 32],
+}
+
+/// The k-of-n committee holding the election key that proposal ballots are encrypted under.
 #[account]
-pub struct UserStake {
-    pub confidential_balance: [u8; 64], // Zero-knowledge balance commitment
-    pub encrypted_data: Vec<u8>,          // Optional encrypted metadata
-    pub stake_timestamp: i64,             // Timestamp for flash loan protection
-    pub asset_mint: Pubkey,               // The mint of the staked asset (for multi-asset support)
+pub struct TallyCommittee {
+    pub governance: Pubkey,
+    pub threshold: u8,
+    pub election_pubkey: [u8; 32],
+    pub members: Vec<CommitteeMember>,
+    pub bump: u8,
 }
 
-impl UserStake {
-    // Total space: 64 + 4 + 64 + 8 + 32 = 172 bytes.
-    pub const LEN: usize = 64 + 4 + 64 + 8 + 32;
+impl TallyCommittee {
+    // Total space: 32 + 1 + 32 + 4 + 10 * (32 + 32) + 1 = 710 bytes.
+    pub const LEN: usize = 32 + 1 + 32 + 4 + MAX_COMMITTEE_SIZE * (32 + 32) + 1;
+
+    /// Checks the committee is well formed and that all share keys and the election key lie on
+    /// one polynomial of degree `threshold - 1`, so any `threshold` shares decrypt tallies.
+    pub fn validate(&self) -> Result<()> {
+        let size = self.members.len();
+        let threshold = self.threshold as usize;
+        if threshold == 0 || threshold > size || size > MAX_COMMITTEE_SIZE {
+            return Err(ErrorCode::InvalidTallyCommittee.into());
+        }
+        if self.election_pubkey == [0u8; 32] {
+            return Err(ErrorCode::InvalidTallyCommittee.into());
+        }
+        for (position, member) in self.members.iter().enumerate() {
+            if self.member_position(&member.authority) != Some(position) {
+                return Err(ErrorCode::InvalidTallyCommittee.into());
+            }
+        }
+
+        let indices: Vec<u64> = (1..=threshold as u64).collect();
+        let points: Vec<PodRistrettoPoint> = self.members[..threshold]
+            .iter()
+            .map(|member| PodRistrettoPoint(member.share_pubkey))
+            .collect();
+        let extra_shares = self.members[threshold..]
+            .iter()
+            .enumerate()
+            .map(|(offset, member)| ((threshold + offset + 1) as u64, member.share_pubkey));
+        for (at, expected) in std::iter::once((0, self.election_pubkey)).chain(extra_shares) {
+            let (coefficients, scale) = zk_utils::lagrange_coefficients(&indices, at)
+                .ok_or(ErrorCode::InvalidTallyCommittee)?;
+            let interpolated = zk_utils::linear_combination(&coefficients, &points);
+            let scaled = multiply_ristretto(
+                &zk_utils::u128_scalar(scale as u128),
+                &PodRistrettoPoint(expected),
+            );
+            if interpolated.is_none() || interpolated != scaled {
+                return Err(ErrorCode::InvalidTallyCommittee.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Position of `authority` in `members`; its Shamir index is the position plus one.
+    pub fn member_position(&self, authority: &Pubkey) -> Option<usize> {
+        self.members
+            .iter()
+            .position(|member| member.authority == *authority)
+    }
 }
 
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut, has_one = token_mint)]
-    pub pool: Account<'info, LiquidityPool>,
-    #[account(mut, seeds = [b"user_stake", user.key().as_ref(), pool.key().as_ref()], bump)]
-    pub user_stake: Account<'info, UserStake>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(mut)]
-    pub pool_token_account: Account<'info, ConfidentialTokenAccount>,
+pub struct CreateProposal<'info> {
+    #[account(mut, seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(
+        has_one = governance,
+        seeds = [b"tally_committee", governance.key().as_ref()],
+        bump = tally_committee.bump,
+    )]
+    pub tally_committee: Account<'info, TallyCommittee>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::LEN,
+        seeds = [b"proposal", governance.key().as_ref(), &governance.proposal_count.to_le_bytes()],
+        bump,
+    )]
+    pub proposal: Account<'info, Proposal>,
     #[account(mut)]
-    pub user_token_account: Account<'info, ConfidentialTokenAccount>,
-    pub token_mint: Account<'info, Mint>,
-    pub token_program: Program<'info, Token>,
-    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CommitSwap<'info> {
+#[instruction(
+    ballot: [u8; 64],
+    ballot_proof: ProofSource,
+    zk_vote_proof: ProofSource,
+    identity_root: [u8; 32],
+    nullifier_hash: [u8; 32],
+)]
+pub struct CastVote<'info> {
+    #[account(seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(mut, has_one = governance)]
+    pub proposal: Account<'info, Proposal>,
     #[account(mut)]
+    pub voter: Signer<'info>,
+    #[account(seeds = [b"identity_registry", governance.pool.as_ref()], bump = identity_registry.bump)]
+    pub identity_registry: Account<'info, IdentityRegistry>,
+    #[account(seeds = [b"vk_registry", governance.pool.as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
+    /// CHECK: Nullifier PDA, created in `spend_nullifier`; its existence means the identity has voted.
+    #[account(mut, seeds = [b"nullifier", proposal.key().as_ref(), nullifier_hash.as_ref()], bump)]
+    pub nullifier: UncheckedAccount<'info>,
+    /// CHECK: The voter's `Delegation` PDA, which does not exist if they never delegated.
+    #[account(seeds = [b"delegation", governance.key().as_ref(), nullifier_hash.as_ref()], bump)]
+    pub delegation: UncheckedAccount<'info>,
+    /// CHECK: The voter's `DelegateWeight` PDA, which does not exist if nobody delegated to them.
+    #[account(seeds = [b"delegate_weight", governance.key().as_ref(), nullifier_hash.as_ref()], bump)]
+    pub delegate_weight: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterStakeWeight<'info> {
+    #[account(seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(has_one = governance)]
+    pub proposal: Account<'info, Proposal>,
+    #[account(address = governance.pool)]
     pub pool: Account<'info, LiquidityPool>,
+    /// Only positions in the pool's own mint carry voting weight.
+    #[account(
+        seeds = [
+            b"user_stake",
+            owner.key().as_ref(),
+            pool.key().as_ref(),
+            pool.token_mint.as_ref(),
+        ],
+        bump,
+    )]
+    pub user_stake: Account<'info, UserStake>,
     #[account(
         init,
-        payer = trader,
-        space = 8 + TradeOrder::LEN,
-        seeds = [b"trade_order", trader.key().as_ref(), pool.key().as_ref()],
+        payer = owner,
+        space = 8 + StakeWeight::LEN,
+        seeds = [b"stake_weight", proposal.key().as_ref(), user_stake.key().as_ref()],
         bump,
     )]
-    pub trade_order: Account<'info, TradeOrder>,
+    pub stake_weight: Account<'info, StakeWeight>,
     #[account(mut)]
-    pub trader: Signer<'info>,
+    pub owner: Signer<'info>,
+    /// CHECK: Instructions sysvar or ZK Token Proof context-state account, validated in `zk_utils::verify_transfer_proof`.
+    pub proof_account: UncheckedAccount<'info>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct RevealSwap<'info> {
-    #[account(mut, seeds = [b"trade_order", trader.key().as_ref(), pool.key().as_ref()], bump)]
-    pub trade_order: Account<'info, TradeOrder>,
+pub struct CastStakeVote<'info> {
     #[account(mut)]
-    pub trader: Signer<'info>,
-    pub pool: Account<'info, LiquidityPool>,
+    pub proposal: Account<'info, Proposal>,
+    #[account(mut, has_one = proposal, has_one = owner)]
+    pub stake_weight: Account<'info, StakeWeight>,
+    pub owner: Signer<'info>,
+}
+
+/// A stake's hidden vote weight on one proposal, created by `register_stake_weight`.
+#[account]
+pub struct StakeWeight {
+    pub proposal: Pubkey,
+    pub owner: Pubkey,
+    pub weight: [u8; 64], // ElGamal encryption of the weight under the election key.
+    pub voted: bool,
+    pub bump: u8,
+}
+
+impl StakeWeight {
+    // Total space: 32 + 32 + 64 + 1 + 1 = 130 bytes.
+    pub const LEN: usize = 32 + 32 + 64 + 1 + 1;
 }
 
 #[derive(Accounts)]
-pub struct DistributeRewards<'info> {
-    #[account(mut)]
-    pub pool: Account<'info, LiquidityPool>,
+#[instruction(
+    delegate: [u8; 32],
+    zk_identity_proof: ProofSource,
+    identity_root: [u8; 32],
+    nullifier_hash: [u8; 32],
+)]
+pub struct DelegateVotes<'info> {
+    #[account(seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
+    pub governance: Account<'info, Governance>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Delegation::LEN,
+        seeds = [b"delegation", governance.key().as_ref(), nullifier_hash.as_ref()],
+        bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + DelegateWeight::LEN,
+        seeds = [b"delegate_weight", governance.key().as_ref(), delegate.as_ref()],
+        bump,
+    )]
+    pub delegate_weight: Account<'info, DelegateWeight>,
+    #[account(seeds = [b"identity_registry", governance.pool.as_ref()], bump = identity_registry.bump)]
+    pub identity_registry: Account<'info, IdentityRegistry>,
+    #[account(seeds = [b"vk_registry", governance.pool.as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
     #[account(mut)]
-    pub pool_token_account: Account<'info, ConfidentialTokenAccount>,
-    pub token_program: Program<'info, Token>,
-    pub confidential_token_program: Program<'info, ConfidentialTokenProgram>,
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateRewardParams<'info> {
-    #[account(mut, has_one = authority)]
+#[instruction(zk_identity_proof: ProofSource, identity_root: [u8; 32], nullifier_hash: [u8; 32])]
+pub struct RevokeDelegation<'info> {
+    #[account(seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
     pub governance: Account<'info, Governance>,
+    #[account(
+        mut,
+        has_one = governance,
+        seeds = [b"delegation", governance.key().as_ref(), nullifier_hash.as_ref()],
+        bump = delegation.bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        mut,
+        has_one = governance,
+        seeds = [b"delegate_weight", governance.key().as_ref(), delegation.delegate.as_ref()],
+        bump = delegate_weight.bump,
+    )]
+    pub delegate_weight: Account<'info, DelegateWeight>,
+    #[account(seeds = [b"identity_registry", governance.pool.as_ref()], bump = identity_registry.bump)]
+    pub identity_registry: Account<'info, IdentityRegistry>,
+    #[account(seeds = [b"vk_registry", governance.pool.as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
+}
+
+/// An identity's delegation of its vote, keyed by the delegator's governance identity.
+#[account]
+pub struct Delegation {
+    pub governance: Pubkey,
+    pub delegate: [u8; 32], // The delegate's governance identity.
+    pub delegated_slot: u64,
+    pub revoked_slot: u64, // Slot of the last revocation, 0 if never revoked.
+    pub active: bool,
+    pub bump: u8,
+}
+
+impl Delegation {
+    // Total space: 32 + 32 + 8 + 8 + 1 + 1 = 82 bytes.
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 1 + 1;
+
+    /// Whether the delegation counts for a proposal created at `snapshot_slot`.
+    pub fn is_effective_at(&self, snapshot_slot: u64) -> bool {
+        self.delegated_slot < snapshot_slot && (self.active || self.revoked_slot >= snapshot_slot)
+    }
+}
+
+/// Maximum number of weight checkpoints kept per delegate.
+pub const MAX_DELEGATE_CHECKPOINTS: usize = 16;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightCheckpoint {
+    pub slot: u64,
+    pub weight: u64,
+}
+
+/// The number of delegations a delegate holds, with enough history to answer for every open
+/// proposal's snapshot.
+#[account]
+#[derive(Default)]
+pub struct DelegateWeight {
+    pub governance: Pubkey,
+    pub delegate: [u8; 32],
+    pub checkpoints: Vec<WeightCheckpoint>,
+    pub bump: u8,
+}
+
+impl DelegateWeight {
+    // Total space: 32 + 32 + 4 + 16 * 16 + 1 = 325 bytes.
+    pub const LEN: usize = 32 + 32 + 4 + MAX_DELEGATE_CHECKPOINTS * 16 + 1;
+
+    pub fn current(&self) -> u64 {
+        self.checkpoints
+            .last()
+            .map_or(0, |checkpoint| checkpoint.weight)
+    }
+
+    /// Delegated weight for a proposal created at `snapshot_slot`: changes made in that slot
+    /// or later do not count.
+    pub fn weight_at(&self, snapshot_slot: u64) -> u64 {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.slot < snapshot_slot)
+            .map_or(0, |checkpoint| checkpoint.weight)
+    }
+
+    /// Records `weight` as of `slot`. When the history is full the oldest checkpoint is
+    /// dropped, provided every proposal that could still read it has closed.
+    pub fn record(&mut self, slot: u64, weight: u64, voting_period_slots: u64) -> Result<()> {
+        if let Some(last) = self.checkpoints.last_mut() {
+            if last.slot == slot {
+                last.weight = weight;
+                return Ok(());
+            }
+        }
+        if self.checkpoints.len() == MAX_DELEGATE_CHECKPOINTS {
+            // Open proposals were created after `slot - voting_period_slots`, so none of
+            // them reads past the second-oldest checkpoint if that one is older.
+            if self.checkpoints[1].slot.saturating_add(voting_period_slots) > slot {
+                return Err(ErrorCode::DelegateCheckpointsFull.into());
+            }
+            self.checkpoints.remove(0);
+        }
+        self.checkpoints.push(WeightCheckpoint { slot, weight });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SubmitDecryptionShare<'info> {
+    #[account(
+        seeds = [b"tally_committee", proposal.governance.as_ref()],
+        bump = tally_committee.bump,
+    )]
+    pub tally_committee: Account<'info, TallyCommittee>,
     #[account(mut)]
-    pub authority: Signer<'info>,
-    // Additional signers can be added here for multi-signature verification.
+    pub proposal: Account<'info, Proposal>,
+    pub member: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct ConfidentialVote<'info> {
+pub struct FinalizeProposal<'info> {
+    #[account(
+        seeds = [b"tally_committee", proposal.governance.as_ref()],
+        bump = tally_committee.bump,
+    )]
+    pub tally_committee: Account<'info, TallyCommittee>,
     #[account(mut)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(mut, seeds = [b"governance", governance.pool.as_ref()], bump = governance.bump)]
     pub governance: Account<'info, Governance>,
-    #[account(mut)]
-    pub voter: Signer<'info>,
+    #[account(mut, address = governance.pool)]
+    pub pool: Account<'info, LiquidityPool>,
+    #[account(mut, has_one = governance)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+/// The action a proposal performs when executed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ProposalPayload {
+    UpdateRewardParams { new_reward_rate: u64 },
+    UpdateLockupPolicy { lockup: LockupPolicy },
+    UpdateRewardTiers { reward_tiers: RewardTiers },
 }
 
-/// Governance account for multi-signature reward parameter updates and confidential voting.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalStatus {
+    Active,
+    Passed,
+    Rejected,
+    Executed,
+}
+
+/// A governance proposal and its running tally.
+/// Ballots accumulate in `encrypted_tally` while voting is open; `votes_for` and
+/// `votes_against` are only set once the tally is decrypted at finalization. Under stake
+/// weighting the ballots' weights accumulate in `encrypted_turnout` and `votes_cast` is also
+/// only known then.
 #[account]
-pub struct Governance {
-    pub authority: Pubkey,
-    pub reward_rate: u64,
-    pub vote_count: u64,
-    // Additional governance parameters can be added here.
+pub struct Proposal {
+    pub governance: Pubkey,
+    pub id: u64,
+    pub proposer: Pubkey,
+    pub payload: ProposalPayload,
+    pub start_slot: u64,
+    pub end_slot: u64, // Voting is open for slots in [start_slot, end_slot).
+    pub quorum: u64,
+    pub threshold_bps: u16,
+    pub vote_weighting: VoteWeighting,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub election_pubkey: [u8; 32],
+    pub encrypted_tally: [u8; 64],   // ElGamal sum of all ballots.
+    pub encrypted_turnout: [u8; 64], // ElGamal sum of all stake weights.
+    pub votes_cast: u64,
+    pub ballots_cast: u64,
+    pub decryption_shares: [[u8; 32]; MAX_COMMITTEE_SIZE], // Indexed by committee position.
+    pub turnout_decryption_shares: [[u8; 32]; MAX_COMMITTEE_SIZE],
+    pub decryption_share_mask: u16, // Bit i set once member i's shares are verified.
+    pub status: ProposalStatus,
+    pub bump: u8,
+}
+
+impl Proposal {
+    // Total space: 32 + 8 + 32 + 45 (payload) + 8 + 8 + 8 + 2 + 9 (vote weighting) + 8 + 8
+    //              + 32 + 64 + 64 + 8 + 8 + 10 * 64 + 2 + 1 (status) + 1 = 988 bytes.
+    pub const LEN: usize =
+        32 + 8 + 32 + 45 + 8 + 8 + 8 + 2 + 9 + 8 + 8 + 32 + 64 + 64 + 8 + 8 + 640 + 2 + 1 + 1;
+
+    /// Whether ballots are accepted in `slot`.
+    pub fn is_voting_open(&self, slot: u64) -> bool {
+        self.status == ProposalStatus::Active && slot >= self.start_slot && slot < self.end_slot
+    }
+
+    /// Adds a verified identity-weighted ballot carrying `weight` votes to the encrypted tally.
+    pub fn add_ballot(&mut self, ballot: &[u8; 64], weight: u64) -> Result<()> {
+        self.encrypted_tally = zk_utils::add_ciphertexts(&self.encrypted_tally, ballot)
+            .ok_or(ErrorCode::InvalidZKProof)?;
+        self.votes_cast = self
+            .votes_cast
+            .checked_add(weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.ballots_cast = self
+            .ballots_cast
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Adds a verified stake-weighted ballot to the encrypted tally and its encrypted `weight`
+    /// to the encrypted turnout.
+    pub fn add_stake_ballot(&mut self, ballot: &[u8; 64], weight: &[u8; 64]) -> Result<()> {
+        self.encrypted_tally = zk_utils::add_ciphertexts(&self.encrypted_tally, ballot)
+            .ok_or(ErrorCode::InvalidZKProof)?;
+        self.encrypted_turnout = zk_utils::add_ciphertexts(&self.encrypted_turnout, weight)
+            .ok_or(ErrorCode::InvalidZKProof)?;
+        self.ballots_cast = self
+            .ballots_cast
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// The ciphertexts committee members decrypt: the tally, and under stake weighting the
+    /// turnout.
+    pub fn encrypted_totals(&self) -> Vec<[u8; 64]> {
+        match self.vote_weighting {
+            VoteWeighting::Identity => vec![self.encrypted_tally],
+            VoteWeighting::Stake { .. } => vec![self.encrypted_tally, self.encrypted_turnout],
+        }
+    }
+
+    /// Verified tally decryption shares as `(Shamir index, share)` pairs.
+    pub fn decryption_shares(&self) -> Vec<(u64, [u8; 32])> {
+        self.indexed_shares(&self.decryption_shares)
+    }
+
+    /// Verified turnout decryption shares as `(Shamir index, share)` pairs.
+    pub fn turnout_decryption_shares(&self) -> Vec<(u64, [u8; 32])> {
+        self.indexed_shares(&self.turnout_decryption_shares)
+    }
+
+    fn indexed_shares(&self, shares: &[[u8; 32]; MAX_COMMITTEE_SIZE]) -> Vec<(u64, [u8; 32])> {
+        shares
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| self.decryption_share_mask & (1 << position) != 0)
+            .map(|(position, share)| (position as u64 + 1, *share))
+            .collect()
+    }
+
+    /// `Passed` if turnout reached quorum and the share in favour reached the threshold,
+    /// otherwise `Rejected`.
+    pub fn outcome(&self) -> ProposalStatus {
+        let total = self.votes_for as u128 + self.votes_against as u128;
+        let in_favour_bps = self.votes_for as u128 * 10_000;
+        if total >= self.quorum as u128
+            && total > 0
+            && in_favour_bps >= self.threshold_bps as u128 * total
+        {
+            ProposalStatus::Passed
+        } else {
+            ProposalStatus::Rejected
+        }
+    }
 }
 
-/// A placeholder for confidential token accounts.
-/// In production, these would be managed by the SPL Confidential Token program.
+/// Marker account whose existence records a spent identity nullifier.
 #[account]
-pub struct ConfidentialTokenAccount {
-    pub balance_commitment: [u8; 64],
-    // Additional fields for confidential tokens can be added here.
+pub struct Nullifier {}
+
+impl Nullifier {
+    // Total space: 0 bytes beyond the discriminator.
+    pub const LEN: usize = 0;
 }
 
 /// Account representing a trade order commitment for commit–reveal swap execution.
 #[account]
 pub struct TradeOrder {
-    pub commitment: [u8; 32],
+    pub trader: Pubkey,
+    pub commitment: [u8; 32], // `TradeOrder::commitment_of(order_details, nonce, trader)`
     pub encrypted_order: [u8; 64],
     pub trade_timestamp: i64,
-    pub revealed_order: Vec<u8>, // Optional: store revealed order details after commit–reveal.
+    pub commit_slot: u64,
+    pub revealed: bool,
+    pub bump: u8,
+    pub revealed_order: Vec<u8>, // Order details, once revealed.
 }
 
 impl TradeOrder {
-    // Total space: 32 + 64 + 8 + 4 + 128 = 236 bytes.
-    pub const LEN: usize = 32 + 64 + 8 + 4 + 128;
+    // Total space: 32 (trader) + 32 + 64 + 8 + 8 + 1 + 1 + 4 + 128 (order) = 278 bytes.
+    pub const LEN: usize = 32 + 32 + 64 + 8 + 8 + 1 + 1 + 4 + Self::MAX_ORDER_LEN;
+
+    pub const MAX_ORDER_LEN: usize = 128;
+
+    /// Slots that must pass after the commit before a reveal, so the two can never land in
+    /// the same block.
+    pub const MIN_REVEAL_DELAY_SLOTS: u64 = 2;
+
+    /// How long after `trade_timestamp` an order can be revealed before it expires.
+    pub const REVEAL_WINDOW_SECONDS: i64 = 300;
+
+    /// `hash(order_details || nonce || trader)`. The nonce and trader have fixed lengths, so
+    /// no two orders hash the same bytes.
+    pub fn commitment_of(order_details: &[u8], nonce: &[u8; 32], trader: &Pubkey) -> [u8; 32] {
+        hashv(&[order_details, nonce, trader.as_ref()]).to_bytes()
+    }
+
+    pub fn reveal_deadline(&self) -> i64 {
+        self.trade_timestamp
+            .saturating_add(Self::REVEAL_WINDOW_SECONDS)
+    }
+
+    /// Records `order_details` as revealed at `slot` and `now`, if they and `nonce` open the
+    /// commitment within the reveal window.
+    pub fn reveal(
+        &mut self,
+        order_details: Vec<u8>,
+        nonce: &[u8; 32],
+        slot: u64,
+        now: i64,
+    ) -> Result<()> {
+        if self.revealed {
+            return Err(ErrorCode::OrderAlreadyRevealed.into());
+        }
+        let earliest_slot = self
+            .commit_slot
+            .saturating_add(Self::MIN_REVEAL_DELAY_SLOTS);
+        if slot < earliest_slot {
+            return Err(ErrorCode::RevealTooEarly.into());
+        }
+        if now > self.reveal_deadline() {
+            return Err(ErrorCode::RevealDeadlinePassed.into());
+        }
+        if order_details.len() > Self::MAX_ORDER_LEN {
+            return Err(ErrorCode::InvalidOrderDetails.into());
+        }
+        if Self::commitment_of(&order_details, nonce, &self.trader) != self.commitment {
+            return Err(ErrorCode::OrderCommitmentMismatch.into());
+        }
+        self.revealed = true;
+        self.revealed_order = order_details;
+        Ok(())
+    }
+
+    /// Whether the order can no longer be revealed at `now`.
+    pub fn is_closable(&self, now: i64) -> bool {
+        self.revealed || now > self.reveal_deadline()
+    }
 }
 
 /// Placeholder for additional zk-account action context.
@@ -644,6 +5960,16 @@ pub struct SomeZkAccountAction<'info> {
     // Additional accounts as needed.
 }
 
+/// Accounts context for identity-gated LP messaging.
+#[derive(Accounts)]
+pub struct EncryptedMessaging<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub identity_registry: Account<'info, IdentityRegistry>,
+    #[account(seeds = [b"vk_registry", identity_registry.pool.as_ref()], bump = vk_registry.bump)]
+    pub vk_registry: Account<'info, VerifyingKeyRegistry>,
+}
+
 /// Accounts context for additional advanced features.
 #[derive(Accounts)]
 pub struct AdditionalFeatures<'info> {
@@ -651,6 +5977,8 @@ pub struct AdditionalFeatures<'info> {
     pub user: Signer<'info>,
     #[account(mut)] // Added: reference to the user's stake account
     pub user_stake: Account<'info, UserStake>,
+    /// CHECK: Instructions sysvar or ZK Token Proof context-state account, validated in `zk_utils::verify_transfer_proof`.
+    pub proof_account: UncheckedAccount<'info>,
     // Additional accounts as needed.
 }
 
@@ -670,6 +5998,140 @@ pub enum ErrorCode {
     SybilAttackDetected,
     #[msg("Stake duration not met for withdrawal.")]
     StakeDurationNotMet,
-    #[msg("Compression error occurred.")]
-    CompressionError,
+    #[msg("Balance commitment is not a valid Ristretto ciphertext.")]
+    InvalidBalanceCommitment,
+    #[msg("Proof account does not hold a verified range proof.")]
+    InvalidProofAccount,
+    #[msg("Range proof does not cover the expected commitments.")]
+    RangeProofMismatch,
+    #[msg("No verifying key is registered for this circuit.")]
+    VerifyingKeyNotFound,
+    #[msg("Verifying key is malformed.")]
+    InvalidVerifyingKey,
+    #[msg("Public inputs do not match the circuit.")]
+    InvalidPublicInputs,
+    #[msg("Proof envelope version is not supported.")]
+    UnsupportedProofVersion,
+    #[msg("Proof was produced by the wrong proof system.")]
+    ProofSystemMismatch,
+    #[msg("Proof is for a different circuit.")]
+    ProofCircuitMismatch,
+    #[msg("Proof is bound to a different instruction, pool or program.")]
+    ProofDomainMismatch,
+    #[msg("Proof public-input hash does not match this instruction.")]
+    PublicInputHashMismatch,
+    #[msg("Proof bytes are malformed for their proof system.")]
+    MalformedProof,
+    #[msg("Identity commitment is not a BN254 field element.")]
+    InvalidIdentityCommitment,
+    #[msg("Identity tree is full.")]
+    IdentityTreeFull,
+    #[msg("Identity proof references an unknown Merkle root.")]
+    UnknownIdentityRoot,
+    #[msg("This identity has already voted.")]
+    NullifierAlreadyUsed,
+    #[msg("Governance quorum, threshold or voting period is invalid.")]
+    InvalidGovernanceConfig,
+    #[msg("Voting on this proposal is closed.")]
+    VotingClosed,
+    #[msg("Voting on this proposal has not ended yet.")]
+    VotingStillOpen,
+    #[msg("Proposal is not active.")]
+    ProposalNotActive,
+    #[msg("Proposal has not passed.")]
+    ProposalNotPassed,
+    #[msg("Tally committee threshold, members or keys are inconsistent.")]
+    InvalidTallyCommittee,
+    #[msg("Not enough committee members have posted decryption shares.")]
+    InsufficientDecryptionShares,
+    #[msg("Claimed tally does not match the encrypted tally.")]
+    InvalidTally,
+    #[msg("Signer is not a member of the tally committee.")]
+    NotCommitteeMember,
+    #[msg("Votes cannot be delegated to oneself.")]
+    InvalidDelegation,
+    #[msg("This identity already has an active delegation.")]
+    AlreadyDelegated,
+    #[msg("This identity has no active delegation.")]
+    DelegationNotActive,
+    #[msg("A revoked delegation still applies to open proposals.")]
+    DelegationCoolingDown,
+    #[msg("This identity delegated its vote for this proposal.")]
+    VoteDelegated,
+    #[msg("Delegate weight history is full until older proposals close.")]
+    DelegateCheckpointsFull,
+    #[msg("Instruction does not match the governance's vote weighting.")]
+    VoteWeightingMismatch,
+    #[msg("Stake balance at the proposal snapshot is no longer available.")]
+    StakeSnapshotUnavailable,
+    #[msg("This stake has already voted on this proposal.")]
+    StakeAlreadyVoted,
+    #[msg("Token account is not configured for confidential transfers.")]
+    ConfidentialTransferNotConfigured,
+    #[msg("Token account has not been approved for confidential transfers.")]
+    ConfidentialAccountNotApproved,
+    #[msg("Token account does not accept confidential credits.")]
+    ConfidentialCreditsDisabled,
+    #[msg("Auditor ciphertext is missing, or given for a pool without an auditor.")]
+    AuditorAmountMismatch,
+    #[msg("Lockup policy durations must be non-negative and the penalty at most 100%.")]
+    InvalidLockupPolicy,
+    #[msg("This pool has no unbonding period.")]
+    UnbondingNotEnabled,
+    #[msg("This position is already unbonding.")]
+    AlreadyUnbonding,
+    #[msg("Withdrawal must match a pending unbonding request.")]
+    UnbondingRequired,
+    #[msg("The unbonding period has not passed.")]
+    UnbondingNotComplete,
+    #[msg("Batch arguments and accounts must describe the same entries.")]
+    BatchLengthMismatch,
+    #[msg("A position appears more than once in the batch.")]
+    DuplicateBatchEntry,
+    #[msg("Every batch entry must be staked by the signing user.")]
+    BatchUserMismatch,
+    #[msg("An asset's reward weight must be at least 1.")]
+    InvalidRewardWeight,
+    #[msg("Reward tiers must be few, ordered by duration and never lower the multiplier.")]
+    InvalidRewardTiers,
+    #[msg("Rewards can only be claimed at a time since the position's last settlement.")]
+    InvalidRewardCheckpoint,
+    #[msg("Reward epochs must be at least a second long.")]
+    InvalidRewardConfig,
+    #[msg("The reward amount exceeds what is left of this epoch's emission cap.")]
+    EmissionCapExceeded,
+    #[msg("The reward epoch is not the current one, or has no leaves or claim window.")]
+    InvalidRewardEpoch,
+    #[msg("The reward epoch's claim window has closed.")]
+    RewardEpochClosed,
+    #[msg("The reward epoch's claim window is still open.")]
+    RewardEpochOpen,
+    #[msg("The reward epoch's unclaimed rewards have already been swept.")]
+    RewardEpochSwept,
+    #[msg("The compressed proof inflates past the maximum proof size.")]
+    ProofTooLarge,
+    #[msg("A proof buffer must hold between 1 byte and its maximum size.")]
+    InvalidProofBuffer,
+    #[msg("Proof chunks must be written in order and fit in the buffer.")]
+    InvalidProofChunk,
+    #[msg("The proof buffer is sealed.")]
+    ProofBufferSealed,
+    #[msg("The proof buffer has not been fully written.")]
+    ProofBufferNotSealed,
+    #[msg("The proof buffer's contents do not match its hash.")]
+    ProofBufferHashMismatch,
+    #[msg("The referenced proof buffer was not passed.")]
+    ProofBufferMissing,
+    #[msg("The order has already been revealed.")]
+    OrderAlreadyRevealed,
+    #[msg("The order cannot be revealed this soon after its commit.")]
+    RevealTooEarly,
+    #[msg("The order's reveal deadline has passed.")]
+    RevealDeadlinePassed,
+    #[msg("Order details are too long.")]
+    InvalidOrderDetails,
+    #[msg("The order details and nonce do not match the commitment.")]
+    OrderCommitmentMismatch,
+    #[msg("The order can still be revealed.")]
+    OrderStillRevealable,
 }
@@ -0,0 +1,199 @@
+//! Groth16 helpers: conversion of arkworks BN254 types into the byte layout expected by
+//! `zk_utils::verify_groth16`, plus reference circuits used to exercise the verifier.
+//!
+//! Production circuits are compiled with Circom; the circuits here only mirror their public
+//! input layout so proofs can be generated from Rust tests.
+
+use ark_bn254::{Bn254, Fr, G1Affine, G2Affine};
+use ark_ff::{BigInteger, PrimeField};
+use ark_groth16::{Groth16, Proof, ProvingKey, VerifyingKey};
+use ark_relations::lc;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore};
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{Circuit, Groth16VerifyingKey, ProofEnvelope, ProofSystem};
+
+/// Big-endian encoding of a scalar, as passed in `public_inputs`.
+pub fn fr_to_bytes(value: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&value.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// Parses a big-endian field element produced by `zk_utils::hash_to_field` / `u64_to_field`.
+pub fn bytes_to_fr(bytes: &[u8; 32]) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes)
+}
+
+/// `x || y`, each coordinate big-endian.
+pub fn g1_to_bytes(point: &G1Affine) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&point.x.into_bigint().to_bytes_be());
+    bytes[32..].copy_from_slice(&point.y.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// `x.c1 || x.c0 || y.c1 || y.c0`, each limb big-endian (the EIP-197 ordering).
+pub fn g2_to_bytes(point: &G2Affine) -> [u8; 128] {
+    let mut bytes = [0u8; 128];
+    bytes[..32].copy_from_slice(&point.x.c1.into_bigint().to_bytes_be());
+    bytes[32..64].copy_from_slice(&point.x.c0.into_bigint().to_bytes_be());
+    bytes[64..96].copy_from_slice(&point.y.c1.into_bigint().to_bytes_be());
+    bytes[96..].copy_from_slice(&point.y.c0.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// The on-chain representation of an arkworks verifying key.
+pub fn verifying_key(vk: &VerifyingKey<Bn254>) -> Groth16VerifyingKey {
+    Groth16VerifyingKey {
+        alpha_g1: g1_to_bytes(&vk.alpha_g1),
+        beta_g2: g2_to_bytes(&vk.beta_g2),
+        gamma_g2: g2_to_bytes(&vk.gamma_g2),
+        delta_g2: g2_to_bytes(&vk.delta_g2),
+        ic: vk.gamma_abc_g1.iter().map(g1_to_bytes).collect(),
+    }
+}
+
+/// `A || B || C`; the program negates `A` itself.
+pub fn proof_bytes(proof: &Proof<Bn254>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(zk_liquidity_pool::zk_utils::GROTH16_PROOF_LEN);
+    bytes.extend_from_slice(&g1_to_bytes(&proof.a));
+    bytes.extend_from_slice(&g2_to_bytes(&proof.b));
+    bytes.extend_from_slice(&g1_to_bytes(&proof.c));
+    bytes
+}
+
+/// Runs a circuit-specific trusted setup. Only suitable for tests.
+pub fn setup<C: ConstraintSynthesizer<Fr>, R: RngCore + CryptoRng>(
+    circuit: C,
+    rng: &mut R,
+) -> Result<(ProvingKey<Bn254>, VerifyingKey<Bn254>), SynthesisError> {
+    Groth16::<Bn254>::circuit_specific_setup(circuit, rng)
+}
+
+/// Proves `circuit` and returns the proof in the on-chain byte layout.
+pub fn prove<C: ConstraintSynthesizer<Fr>, R: RngCore + CryptoRng>(
+    pk: &ProvingKey<Bn254>,
+    circuit: C,
+    rng: &mut R,
+) -> Result<Vec<u8>, SynthesisError> {
+    Groth16::<Bn254>::prove(pk, circuit, rng).map(|proof| proof_bytes(&proof))
+}
+
+/// Wraps on-chain proof bytes for `circuit` in an envelope bound to `domain` and `public_inputs`.
+pub fn envelope(
+    circuit: Circuit,
+    domain: &[u8; 32],
+    public_inputs: &[[u8; 32]],
+    proof: Vec<u8>,
+) -> ProofEnvelope {
+    crate::envelope(
+        ProofSystem::Groth16,
+        circuit,
+        *domain,
+        zk_utils::groth16_input_hash(public_inputs),
+        proof,
+    )
+}
+
+/// Reward circuit: proves `reward = balance · acc_delta` for a private balance.
+///
+/// Public inputs, in order: `hash_to_field(pool)`, `reward`,
+/// `hash_to_field(pool.total_commitment)`. The production circuit opens the total commitment
+/// to `balance`; this one only binds it.
+#[derive(Clone, Copy)]
+pub struct RewardCircuit {
+    pub pool: Fr,
+    pub reward: Fr,
+    pub total_commitment: Fr,
+    pub balance: Fr,
+    pub acc_delta: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for RewardCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let pool = cs.new_input_variable(|| Ok(self.pool))?;
+        let reward = cs.new_input_variable(|| Ok(self.reward))?;
+        let total_commitment = cs.new_input_variable(|| Ok(self.total_commitment))?;
+        let balance = cs.new_witness_variable(|| Ok(self.balance))?;
+        let acc_delta = cs.new_witness_variable(|| Ok(self.acc_delta))?;
+        cs.enforce_constraint(lc!() + balance, lc!() + acc_delta, lc!() + reward)?;
+        // Keep the pool and total inputs in the constraint system so they are bound by the proof.
+        for input in [pool, total_commitment] {
+            cs.enforce_constraint(
+                lc!() + input,
+                lc!() + ark_relations::r1cs::Variable::One,
+                lc!() + input,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Identity circuit: proves membership of an identity commitment in the registry tree.
+///
+/// Public inputs, in order (see `zk_utils::identity_public_inputs`): `root`,
+/// `nullifier_hash`, `signal_hash`, `external_nullifier`. The production circuit checks the
+/// Poseidon Merkle path and derives the nullifier; this one only binds the public inputs.
+#[derive(Clone, Copy)]
+pub struct IdentityCircuit {
+    pub public_inputs: [Fr; 4],
+}
+
+impl IdentityCircuit {
+    pub fn new(public_inputs: &[[u8; 32]; 4]) -> Self {
+        IdentityCircuit {
+            public_inputs: public_inputs.map(|input| bytes_to_fr(&input)),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for IdentityCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for input in self.public_inputs {
+            let variable = cs.new_input_variable(|| Ok(input))?;
+            cs.enforce_constraint(
+                lc!() + variable,
+                lc!() + ark_relations::r1cs::Variable::One,
+                lc!() + variable,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reward-claim circuit: proves a leaf of a reward epoch's tree can be opened by the prover and
+/// that its encrypted reward is what the payout transfers.
+///
+/// Public inputs, in order (see `zk_utils::reward_claim_public_inputs`): `root`,
+/// `nullifier_hash`, the hashed payout ciphertexts, `hash_to_field(recipient)`,
+/// `hash_to_field(domain)`. The production circuit checks the Poseidon Merkle path, derives
+/// the leaf's nullifier and proves the payout encrypts the leaf's reward; this one only binds
+/// the public inputs.
+#[derive(Clone, Copy)]
+pub struct RewardClaimCircuit {
+    pub public_inputs: [Fr; 5],
+}
+
+impl RewardClaimCircuit {
+    pub fn new(public_inputs: &[[u8; 32]; 5]) -> Self {
+        RewardClaimCircuit {
+            public_inputs: public_inputs.map(|input| bytes_to_fr(&input)),
+        }
+    }
+}
+
+impl ConstraintSynthesizer<Fr> for RewardClaimCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        for input in self.public_inputs {
+            let variable = cs.new_input_variable(|| Ok(input))?;
+            cs.enforce_constraint(
+                lc!() + variable,
+                lc!() + ark_relations::r1cs::Variable::One,
+                lc!() + variable,
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! Off-chain mirror of the on-chain `IdentityRegistry` tree.
+//!
+//! The program only stores the right-most path, so members rebuild the full tree from the
+//! appended commitments to get the Merkle path their identity proof needs.
+
+use zk_liquidity_pool::{zk_utils, IDENTITY_TREE_DEPTH};
+
+/// A Merkle path from a leaf to the root: the sibling at each level and whether the
+/// node on the path is the right child.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerklePath {
+    pub siblings: Vec<[u8; 32]>,
+    pub is_right: Vec<bool>,
+}
+
+impl MerklePath {
+    /// Folds `leaf` up the path and returns the root it implies.
+    pub fn root(&self, leaf: &[u8; 32]) -> [u8; 32] {
+        self.siblings
+            .iter()
+            .zip(self.is_right.iter())
+            .fold(*leaf, |node, (sibling, is_right)| {
+                let hash = if *is_right {
+                    zk_utils::poseidon_pair(sibling, &node)
+                } else {
+                    zk_utils::poseidon_pair(&node, sibling)
+                };
+                hash.expect("tree nodes are field elements")
+            })
+    }
+}
+
+/// Every level of the identity tree, with missing nodes treated as empty subtrees.
+#[derive(Clone, Debug)]
+pub struct IdentityTree {
+    levels: Vec<Vec<[u8; 32]>>,
+    zeros: Vec<[u8; 32]>,
+}
+
+impl Default for IdentityTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdentityTree {
+    pub fn new() -> Self {
+        let mut zeros = vec![[0u8; 32]];
+        for level in 0..IDENTITY_TREE_DEPTH {
+            let zero = zk_utils::poseidon_pair(&zeros[level], &zeros[level]).unwrap();
+            zeros.push(zero);
+        }
+        IdentityTree {
+            levels: vec![Vec::new(); IDENTITY_TREE_DEPTH + 1],
+            zeros,
+        }
+    }
+
+    /// Appends `leaf` and returns its index, mirroring `IdentityRegistry::append`.
+    pub fn append(&mut self, leaf: [u8; 32]) -> u64 {
+        let index = self.levels[0].len();
+        self.levels[0].push(leaf);
+        let mut position = index;
+        for level in 0..IDENTITY_TREE_DEPTH {
+            let parent = position / 2;
+            let left = self.node(level, parent * 2);
+            let right = self.node(level, parent * 2 + 1);
+            let hash = zk_utils::poseidon_pair(&left, &right).unwrap();
+            if parent < self.levels[level + 1].len() {
+                self.levels[level + 1][parent] = hash;
+            } else {
+                self.levels[level + 1].push(hash);
+            }
+            position = parent;
+        }
+        index as u64
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.node(IDENTITY_TREE_DEPTH, 0)
+    }
+
+    /// The Merkle path for the leaf at `index`, if it exists.
+    pub fn path(&self, index: u64) -> Option<MerklePath> {
+        let mut position = usize::try_from(index).ok()?;
+        if position >= self.levels[0].len() {
+            return None;
+        }
+        let mut siblings = Vec::with_capacity(IDENTITY_TREE_DEPTH);
+        let mut is_right = Vec::with_capacity(IDENTITY_TREE_DEPTH);
+        for level in 0..IDENTITY_TREE_DEPTH {
+            siblings.push(self.node(level, position ^ 1));
+            is_right.push(position % 2 == 1);
+            position /= 2;
+        }
+        Some(MerklePath { siblings, is_right })
+    }
+
+    fn node(&self, level: usize, position: usize) -> [u8; 32] {
+        self.levels[level]
+            .get(position)
+            .copied()
+            .unwrap_or(self.zeros[level])
+    }
+}
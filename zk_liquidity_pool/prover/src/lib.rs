@@ -0,0 +1,521 @@
+//! Off-chain prover for the ZK liquidity pool program.
+//!
+//! Every proof produced here is checked on-chain by the matching verifier in
+//! `zk_liquidity_pool::zk_utils`; the Fiat–Shamir challenges are computed by calling the
+//! program crate directly so the two sides cannot drift apart.
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use solana_program::instruction::Instruction;
+use solana_zk_token_sdk::encryption::elgamal::{ElGamalCiphertext, ElGamalKeypair};
+use solana_zk_token_sdk::encryption::pedersen::{
+    Pedersen, PedersenCommitment, PedersenOpening, G, H,
+};
+use solana_zk_token_sdk::instruction::{
+    BatchedRangeProofContext, BatchedRangeProofU128Data, BatchedRangeProofU256Data,
+    BatchedRangeProofU64Data, ZkProofData,
+};
+use solana_zk_token_sdk::zk_token_proof_instruction::{ContextStateInfo, ProofInstruction};
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{AuditorAmount, Circuit, ProofCompression, ProofEnvelope, ProofSystem};
+
+pub mod groth16;
+pub mod identity;
+pub mod tally;
+
+/// The all-zero ciphertext a freshly created `UserStake` starts with (an encryption of 0).
+pub const ZERO_BALANCE: [u8; 64] = [0u8; 64];
+
+/// A new balance ciphertext together with the proof that it was derived from the old one.
+#[derive(Clone, Debug)]
+pub struct BalanceUpdateProof {
+    pub new_balance: [u8; 64],
+    pub proof: ProofEnvelope,
+}
+
+/// Wraps `proof` in a current-version envelope.
+///
+/// `domain` is `zk_utils::domain_separator(instruction, pool)` for the instruction the proof
+/// will be submitted to.
+pub fn envelope(
+    system: ProofSystem,
+    circuit: Circuit,
+    domain: [u8; 32],
+    public_input_hash: [u8; 32],
+    proof: Vec<u8>,
+) -> ProofEnvelope {
+    ProofEnvelope {
+        version: zk_utils::PROOF_ENVELOPE_VERSION,
+        system,
+        circuit,
+        public_input_hash,
+        domain,
+        compression: ProofCompression::None,
+        proof,
+    }
+}
+
+/// Zlib-compresses the envelope's proof for `zk_utils::decompress_proof` to inflate on-chain,
+/// if that makes it smaller. Proofs that are mostly curve points and scalars are close to
+/// random and usually do not shrink, in which case the envelope is returned unchanged.
+pub fn compress(envelope: ProofEnvelope) -> ProofEnvelope {
+    if envelope.compression != ProofCompression::None {
+        return envelope;
+    }
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&envelope.proof, 10);
+    if compressed.len() >= envelope.proof.len() {
+        return envelope;
+    }
+    ProofEnvelope {
+        compression: ProofCompression::Zlib,
+        proof: compressed,
+        ..envelope
+    }
+}
+
+fn decompress(bytes: &[u8]) -> Option<RistrettoPoint> {
+    CompressedRistretto::from_slice(bytes).decompress()
+}
+
+/// Encrypts `amount` under `keypair` in the 64-byte layout stored in `UserStake`.
+pub fn encrypt_balance(keypair: &ElGamalKeypair, amount: u64) -> [u8; 64] {
+    keypair.pubkey().encrypt(amount).to_bytes()
+}
+
+/// Decrypts a balance that is small enough for the baby-step giant-step table (`< 2^32`).
+pub fn decrypt_balance(keypair: &ElGamalKeypair, balance: &[u8; 64]) -> Option<u64> {
+    ElGamalCiphertext::from_bytes(balance)?.decrypt_u32(keypair.secret())
+}
+
+/// Produces a re-randomized `old ± amount` ciphertext and the proof that
+/// `zk_utils::verify_confidential_balance` expects for it.
+pub fn prove_balance_update(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    old_balance: &[u8; 64],
+    amount: u64,
+    direction: BalanceUpdate,
+) -> Option<BalanceUpdateProof> {
+    let old = ElGamalCiphertext::from_bytes(old_balance)?;
+    let shifted = match direction {
+        BalanceUpdate::Credit => old.add_amount(amount),
+        BalanceUpdate::Debit => old.subtract_amount(amount),
+    };
+
+    // Re-randomize with a fresh opening so consecutive balances are unlinkable.
+    let opening = PedersenOpening::new_rand();
+    let pubkey_point = *keypair.pubkey().get_point();
+    let mut new_balance = [0u8; 64];
+    new_balance[..32].copy_from_slice(
+        &(shifted.commitment.get_point() + opening.get_scalar() * *H)
+            .compress()
+            .to_bytes(),
+    );
+    new_balance[32..].copy_from_slice(
+        &(shifted.handle.get_point() + opening.get_scalar() * pubkey_point)
+            .compress()
+            .to_bytes(),
+    );
+
+    let proof = prove_zero_delta(
+        keypair,
+        domain,
+        old_balance,
+        &new_balance,
+        amount,
+        direction,
+    )?;
+    Some(BalanceUpdateProof { new_balance, proof })
+}
+
+/// Proves that `new - old ∓ amount·G` encrypts zero under `keypair`, for arbitrary ciphertexts.
+///
+/// Exposed separately from [`prove_balance_update`] so tests can build proofs for
+/// ciphertexts that were not produced by this crate (e.g. tampered ones).
+pub fn prove_zero_delta(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    old_balance: &[u8; 64],
+    new_balance: &[u8; 64],
+    amount: u64,
+    direction: BalanceUpdate,
+) -> Option<ProofEnvelope> {
+    let old_handle = decompress(&old_balance[32..])?;
+    let new_handle = decompress(&new_balance[32..])?;
+    let handle_delta = new_handle - old_handle;
+
+    let secret = keypair.secret().get_scalar();
+    let pubkey_point = keypair.pubkey().get_point();
+    let nonce = Scalar::random(&mut OsRng);
+    let y_p = (nonce * pubkey_point).compress().to_bytes();
+    let y_d = (nonce * handle_delta).compress().to_bytes();
+
+    let elgamal_pubkey = keypair.pubkey().to_bytes();
+    let challenge = zk_utils::balance_proof_challenge(
+        domain,
+        &elgamal_pubkey,
+        old_balance,
+        new_balance,
+        amount,
+        direction,
+        &y_p,
+        &y_d,
+    );
+    let challenge = Scalar::from_canonical_bytes(challenge.0)?;
+    let z = nonce + challenge * secret;
+
+    let mut proof = Vec::with_capacity(zk_utils::BALANCE_PROOF_LEN);
+    proof.extend_from_slice(&y_p);
+    proof.extend_from_slice(&y_d);
+    proof.extend_from_slice(z.as_bytes());
+    Some(envelope(
+        ProofSystem::Sigma,
+        Circuit::Balance,
+        *domain,
+        zk_utils::balance_input_hash(&elgamal_pubkey, old_balance, new_balance, amount, direction),
+        proof,
+    ))
+}
+
+/// Encrypts the public `amount` to the pool auditor under fresh randomness and proves it,
+/// as `zk_utils::verify_auditor_amount` expects.
+pub fn prove_auditor_amount(
+    auditor_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    amount: u64,
+) -> Option<AuditorAmount> {
+    let auditor_point = decompress(auditor_pubkey)?;
+    let k = Scalar::random(&mut OsRng);
+    let mut ciphertext = [0u8; 64];
+    ciphertext[..32].copy_from_slice(&(Scalar::from(amount) * *G + k * *H).compress().to_bytes());
+    ciphertext[32..].copy_from_slice(&(k * auditor_point).compress().to_bytes());
+
+    let nonce = Scalar::random(&mut OsRng);
+    let y_h = (nonce * *H).compress().to_bytes();
+    let y_p = (nonce * auditor_point).compress().to_bytes();
+    let challenge =
+        zk_utils::auditor_amount_challenge(domain, auditor_pubkey, &ciphertext, amount, &y_h, &y_p);
+    let challenge = Scalar::from_canonical_bytes(challenge.0)?;
+
+    let mut proof = Vec::with_capacity(zk_utils::AUDITOR_AMOUNT_PROOF_LEN);
+    proof.extend_from_slice(&y_h);
+    proof.extend_from_slice(&y_p);
+    proof.extend_from_slice((nonce + challenge * k).as_bytes());
+    Some(AuditorAmount {
+        ciphertext,
+        proof: envelope(
+            ProofSystem::Sigma,
+            Circuit::AuditorAmount,
+            *domain,
+            zk_utils::auditor_amount_input_hash(auditor_pubkey, &ciphertext, amount),
+            proof,
+        ),
+    })
+}
+
+/// Fresh Pedersen commitments to the values of some ciphertexts, with their openings and the
+/// proof `zk_utils::verify_ciphertext_commitment_equality` expects for them.
+pub struct EqualityProof {
+    pub commitments: Vec<[u8; 32]>,
+    pub openings: Vec<PedersenOpening>,
+    pub proof: ProofEnvelope,
+}
+
+/// Commits afresh to the value of each `(ciphertext, value)` and proves that the commitment
+/// and the ciphertext, under `keypair`, hide the same value.
+///
+/// `value` is not checked against the ciphertext, so tests can build proofs for ciphertexts
+/// that do not decrypt to it (e.g. forged ones).
+pub fn prove_equality(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    ciphertexts: &[([u8; 64], u64)],
+) -> Option<EqualityProof> {
+    let secret = keypair.secret().get_scalar();
+    let pubkey_point = keypair.pubkey().get_point();
+    let elgamal_pubkey = keypair.pubkey().to_bytes();
+    let mut commitments = Vec::with_capacity(ciphertexts.len());
+    let mut openings = Vec::with_capacity(ciphertexts.len());
+    let mut proof = Vec::with_capacity(zk_utils::EQUALITY_PROOF_LEN * ciphertexts.len());
+    for (ciphertext, value) in ciphertexts {
+        let handle = decompress(&ciphertext[32..])?;
+        let opening = PedersenOpening::new_rand();
+        let commitment = Pedersen::with(*value, &opening).to_bytes();
+
+        let nonces: [Scalar; 3] = std::array::from_fn(|_| Scalar::random(&mut OsRng));
+        let mut nonce_commitments = [0u8; 96];
+        for (i, point) in [
+            nonces[0] * pubkey_point,
+            nonces[1] * *G + nonces[0] * handle,
+            nonces[1] * *G + nonces[2] * *H,
+        ]
+        .iter()
+        .enumerate()
+        {
+            nonce_commitments[32 * i..32 * (i + 1)].copy_from_slice(point.compress().as_bytes());
+        }
+        let challenge = zk_utils::equality_proof_challenge(
+            domain,
+            &elgamal_pubkey,
+            ciphertext,
+            &commitment,
+            &nonce_commitments,
+        );
+        let challenge = Scalar::from_canonical_bytes(challenge.0)?;
+
+        proof.extend_from_slice(&nonce_commitments);
+        for (nonce, witness) in [
+            (nonces[0], *secret),
+            (nonces[1], Scalar::from(*value)),
+            (nonces[2], *opening.get_scalar()),
+        ] {
+            proof.extend_from_slice((nonce + challenge * witness).as_bytes());
+        }
+        commitments.push(commitment);
+        openings.push(opening);
+    }
+    let ciphertexts: Vec<[u8; 64]> = ciphertexts
+        .iter()
+        .map(|(ciphertext, _)| *ciphertext)
+        .collect();
+    let proof = envelope(
+        ProofSystem::Sigma,
+        Circuit::CiphertextCommitmentEquality,
+        *domain,
+        zk_utils::equality_input_hash(&elgamal_pubkey, &ciphertexts, &commitments),
+        proof,
+    );
+    Some(EqualityProof {
+        commitments,
+        openings,
+        proof,
+    })
+}
+
+/// A stake: the re-randomized new balance, its balance-update proof and the commitment bound
+/// to it for the pool's commitment total.
+pub struct CreditProof {
+    pub new_balance: [u8; 64],
+    pub proof: ProofEnvelope,
+    pub balance_commitment: [u8; 32],
+    pub opening: PedersenOpening,
+    pub equality_proof: ProofEnvelope,
+}
+
+/// Credits `amount` to a balance currently holding `current_value`.
+pub fn prove_credit(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    old_balance: &[u8; 64],
+    current_value: u64,
+    amount: u64,
+) -> Option<CreditProof> {
+    let new_value = current_value.checked_add(amount)?;
+    let update = prove_balance_update(keypair, domain, old_balance, amount, BalanceUpdate::Credit)?;
+    let mut equality = prove_equality(keypair, domain, &[(update.new_balance, new_value)])?;
+    Some(CreditProof {
+        new_balance: update.new_balance,
+        proof: update.proof,
+        balance_commitment: equality.commitments[0],
+        opening: equality.openings.remove(0),
+        equality_proof: equality.proof,
+    })
+}
+
+/// A withdrawal: the fresh new balance, its balance-update proof, the commitment bound to it
+/// and the range proof over that commitment.
+pub struct DebitProof {
+    pub new_balance: [u8; 64],
+    pub proof: ProofEnvelope,
+    pub balance_commitment: [u8; 32],
+    pub opening: PedersenOpening,
+    pub equality_proof: ProofEnvelope,
+    pub range_proof: RangeProofData,
+}
+
+/// Debits `amount` from a balance currently holding `current_value`.
+///
+/// The range proof covers a fresh commitment bound to the new balance by an equality proof,
+/// not the ciphertext's own commitment, which is what stops a withdrawal from driving the
+/// balance negative.
+pub fn prove_debit(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    old_balance: &[u8; 64],
+    current_value: u64,
+    amount: u64,
+) -> Option<DebitProof> {
+    let new_value = current_value.checked_sub(amount)?;
+    let update = prove_balance_update(keypair, domain, old_balance, amount, BalanceUpdate::Debit)?;
+    let mut equality = prove_equality(keypair, domain, &[(update.new_balance, new_value)])?;
+    let range_proof = prove_range(&[(new_value, &equality.openings[0])])?;
+    Some(DebitProof {
+        new_balance: update.new_balance,
+        proof: update.proof,
+        balance_commitment: equality.commitments[0],
+        opening: equality.openings.remove(0),
+        equality_proof: equality.proof,
+        range_proof,
+    })
+}
+
+/// A reward claim: the whole tokens claimed, the fresh encryption of what is left over, the
+/// commitment bound to it and the proofs `claim_rewards` checks for them.
+pub struct RewardClaimProof {
+    pub amount: u64,
+    pub remainder: [u8; 64],
+    pub proof: ProofEnvelope,
+    pub remainder_commitment: [u8; 32],
+    pub equality_proof: ProofEnvelope,
+    pub range_proof: RangeProofData,
+}
+
+/// Claims the whole tokens of `pending`, the position's pending rewards at the claimed
+/// accumulator value, which hold `pending_value` in `2^-64` tokens. The owner works the value
+/// out from their balance and the pool's public accumulator, as it is too large to decrypt.
+pub fn prove_reward_claim(
+    keypair: &ElGamalKeypair,
+    domain: &[u8; 32],
+    pending: &[u8; 64],
+    pending_value: u128,
+) -> Option<RewardClaimProof> {
+    let amount = (pending_value >> 64) as u64;
+    let remainder_value = pending_value as u64;
+    let remainder = encrypt_balance(keypair, remainder_value);
+    let unclaimed = zk_utils::subtract_public_value(pending, (amount as u128) << 64)?;
+    let proof = prove_zero_delta(
+        keypair,
+        domain,
+        &unclaimed,
+        &remainder,
+        0,
+        BalanceUpdate::Debit,
+    )?;
+    let equality = prove_equality(keypair, domain, &[(remainder, remainder_value)])?;
+    let range_proof = prove_range(&[(remainder_value, &equality.openings[0])])?;
+    Some(RewardClaimProof {
+        amount,
+        remainder,
+        proof,
+        remainder_commitment: equality.commitments[0],
+        equality_proof: equality.proof,
+        range_proof,
+    })
+}
+
+/// A batched range proof, sized by how many 64-bit values it covers.
+#[derive(Clone, Copy)]
+pub enum RangeProofData {
+    U64(BatchedRangeProofU64Data),
+    U128(BatchedRangeProofU128Data),
+    U256(BatchedRangeProofU256Data),
+}
+
+impl RangeProofData {
+    /// The commitments and bit lengths the proof certifies.
+    pub fn context(&self) -> &BatchedRangeProofContext {
+        match self {
+            RangeProofData::U64(data) => data.context_data(),
+            RangeProofData::U128(data) => data.context_data(),
+            RangeProofData::U256(data) => data.context_data(),
+        }
+    }
+
+    /// Mutable access to the commitments and bit lengths, for building deliberately bad proofs.
+    pub fn context_mut(&mut self) -> &mut BatchedRangeProofContext {
+        match self {
+            RangeProofData::U64(data) => &mut data.context,
+            RangeProofData::U128(data) => &mut data.context,
+            RangeProofData::U256(data) => &mut data.context,
+        }
+    }
+
+    /// Mutable access to the raw Bulletproof bytes, for building deliberately bad proofs.
+    pub fn proof_bytes_mut(&mut self) -> &mut [u8] {
+        match self {
+            RangeProofData::U64(data) => &mut data.proof.0,
+            RangeProofData::U128(data) => &mut data.proof.0,
+            RangeProofData::U256(data) => &mut data.proof.0,
+        }
+    }
+
+    /// Runs the same verification the ZK Token Proof program performs.
+    pub fn verify(&self) -> bool {
+        match self {
+            RangeProofData::U64(data) => data.verify_proof().is_ok(),
+            RangeProofData::U128(data) => data.verify_proof().is_ok(),
+            RangeProofData::U256(data) => data.verify_proof().is_ok(),
+        }
+    }
+
+    /// The ZK Token Proof instruction that verifies this proof, optionally into a
+    /// context-state account so it can be consumed by a later transaction.
+    pub fn instruction(&self, context_state_info: Option<ContextStateInfo>) -> Instruction {
+        match self {
+            RangeProofData::U64(data) => ProofInstruction::VerifyBatchedRangeProofU64
+                .encode_verify_proof(context_state_info, data),
+            RangeProofData::U128(data) => ProofInstruction::VerifyBatchedRangeProofU128
+                .encode_verify_proof(context_state_info, data),
+            RangeProofData::U256(data) => ProofInstruction::VerifyBatchedRangeProofU256
+                .encode_verify_proof(context_state_info, data),
+        }
+    }
+}
+
+/// Proves that every `(value, opening)` commitment holds a 64-bit value.
+///
+/// Up to four values fit in one aggregated proof; three values are padded with a
+/// commitment to zero because the batched proofs must cover exactly 64, 128 or 256 bits.
+pub fn prove_range(values: &[(u64, &PedersenOpening)]) -> Option<RangeProofData> {
+    // The proof program rejects identity commitments, so the padding needs a real opening.
+    let padding = PedersenOpening::new_rand();
+    let mut amounts: Vec<u64> = values.iter().map(|(value, _)| *value).collect();
+    let mut openings: Vec<&PedersenOpening> = values.iter().map(|(_, opening)| *opening).collect();
+    if amounts.len() == 3 {
+        amounts.push(0);
+        openings.push(&padding);
+    }
+    let commitments: Vec<PedersenCommitment> = amounts
+        .iter()
+        .zip(openings.iter())
+        .map(|(value, opening)| Pedersen::with(*value, opening))
+        .collect();
+    let commitment_refs: Vec<&PedersenCommitment> = commitments.iter().collect();
+    let bit_lengths = vec![zk_utils::RANGE_PROOF_BIT_LENGTH as usize; amounts.len()];
+
+    match amounts.len() {
+        1 => BatchedRangeProofU64Data::new(commitment_refs, amounts, bit_lengths, openings)
+            .ok()
+            .map(RangeProofData::U64),
+        2 => BatchedRangeProofU128Data::new(commitment_refs, amounts, bit_lengths, openings)
+            .ok()
+            .map(RangeProofData::U128),
+        4 => BatchedRangeProofU256Data::new(commitment_refs, amounts, bit_lengths, openings)
+            .ok()
+            .map(RangeProofData::U256),
+        _ => None,
+    }
+}
+
+/// The envelope telling the program where to find a range proof over `commitments`: the
+/// offset of the proof instruction relative to the program instruction, or `0` for a
+/// context-state account.
+pub fn range_proof_envelope(
+    domain: &[u8; 32],
+    proof_instruction_offset: i8,
+    commitments: &[[u8; 32]],
+) -> ProofEnvelope {
+    envelope(
+        ProofSystem::Bulletproofs,
+        Circuit::Range,
+        *domain,
+        zk_utils::range_input_hash(commitments),
+        vec![proof_instruction_offset as u8],
+    )
+}
+
+/// Compressed Pedersen generators, for checking the constants baked into the program.
+pub fn pedersen_generators() -> ([u8; 32], [u8; 32]) {
+    (G.compress().to_bytes(), H.compress().to_bytes())
+}
@@ -0,0 +1,448 @@
+//! Off-chain side of encrypted governance voting.
+//!
+//! Ballots are ElGamal ciphertexts `(r·G, v·G + r·Y)` under the tally committee's election key
+//! `Y = x·G`. The secret `x` is Shamir-shared among the committee, and once voting closes any
+//! `threshold` members post partial decryptions `xᵢ·C₁` of the summed tally.
+//!
+//! Under stake weighting each voter first encrypts their weight under `Y` and proves it is
+//! backed by their snapshot stake ([`prove_stake_weight`]); ballots then encrypt 0 or that
+//! weight.
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use rand::rngs::OsRng;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::encryption::pedersen::{PedersenOpening, G, H};
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{Circuit, CommitteeMember, ProofEnvelope, ProofSystem};
+
+use crate::{envelope, prove_equality, prove_range, prove_zero_delta, RangeProofData};
+
+fn decompress(bytes: &[u8]) -> Option<RistrettoPoint> {
+    CompressedRistretto::from_slice(bytes).decompress()
+}
+
+fn split(ciphertext: &[u8; 64]) -> Option<(RistrettoPoint, RistrettoPoint)> {
+    Some((
+        decompress(&ciphertext[..32])?,
+        decompress(&ciphertext[32..])?,
+    ))
+}
+
+fn join(c_1: RistrettoPoint, c_2: RistrettoPoint) -> [u8; 64] {
+    let mut ciphertext = [0u8; 64];
+    ciphertext[..32].copy_from_slice(c_1.compress().as_bytes());
+    ciphertext[32..].copy_from_slice(c_2.compress().as_bytes());
+    ciphertext
+}
+
+/// Key material from a trusted-dealer committee setup.
+pub struct CommitteeKeys {
+    pub threshold: u8,
+    pub election_pubkey: [u8; 32],
+    /// `shares[i]` is `f(i + 1)` for member position `i`.
+    pub shares: Vec<Scalar>,
+}
+
+impl CommitteeKeys {
+    /// Shamir-shares a fresh election secret `f(0)` among `size` members so that any
+    /// `threshold` of them can decrypt.
+    pub fn generate(threshold: u8, size: usize) -> Self {
+        let coefficients: Vec<Scalar> =
+            (0..threshold).map(|_| Scalar::random(&mut OsRng)).collect();
+        let evaluate = |at: u64| {
+            coefficients
+                .iter()
+                .rev()
+                .fold(Scalar::zero(), |acc, coefficient| {
+                    acc * Scalar::from(at) + coefficient
+                })
+        };
+        CommitteeKeys {
+            threshold,
+            election_pubkey: (evaluate(0) * *G).compress().to_bytes(),
+            shares: (1..=size as u64).map(evaluate).collect(),
+        }
+    }
+
+    /// The public key `xᵢ·G` of the member at `position`.
+    pub fn share_pubkey(&self, position: usize) -> [u8; 32] {
+        (self.shares[position] * *G).compress().to_bytes()
+    }
+
+    /// The `members` argument of `initialize_tally_committee`, in share order.
+    pub fn members(&self, authorities: &[Pubkey]) -> Vec<CommitteeMember> {
+        authorities
+            .iter()
+            .enumerate()
+            .map(|(position, authority)| CommitteeMember {
+                authority: *authority,
+                share_pubkey: self.share_pubkey(position),
+            })
+            .collect()
+    }
+}
+
+/// An encrypted vote and its validity proof, as passed to `cast_vote`.
+#[derive(Clone, Debug)]
+pub struct Ballot {
+    pub ciphertext: [u8; 64],
+    pub proof: ProofEnvelope,
+}
+
+/// Encrypts `value` under the election key, returning the ciphertext and its randomness.
+pub fn encrypt_vote(election_pubkey: &[u8; 32], value: u64) -> Option<([u8; 64], Scalar)> {
+    let y = decompress(election_pubkey)?;
+    let randomness = Scalar::random(&mut OsRng);
+    let ciphertext = join(randomness * *G, Scalar::from(value) * *G + randomness * y);
+    Some((ciphertext, randomness))
+}
+
+/// A vote weight encrypted under the election key, which a ballot must match.
+#[derive(Clone, Debug)]
+pub struct Weight {
+    pub value: u64,
+    pub ciphertext: [u8; 64],
+    pub randomness: Scalar,
+}
+
+impl Weight {
+    /// A public weight, encrypted with zero randomness as `zk_utils::public_weight` does.
+    pub fn public(value: u64) -> Self {
+        Weight {
+            value,
+            ciphertext: join(RistrettoPoint::identity(), Scalar::from(value) * *G),
+            randomness: Scalar::zero(),
+        }
+    }
+
+    /// A fresh encryption of `value`, as registered by `register_stake_weight`.
+    pub fn encrypted(election_pubkey: &[u8; 32], value: u64) -> Option<Self> {
+        let (ciphertext, randomness) = encrypt_vote(election_pubkey, value)?;
+        Some(Weight {
+            value,
+            ciphertext,
+            randomness,
+        })
+    }
+}
+
+/// Encrypts a yes/no vote carrying `weight` votes and proves it is a valid ballot for the
+/// proposal behind `domain`.
+pub fn cast_ballot(
+    election_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    in_favour: bool,
+    weight: &Weight,
+) -> Option<Ballot> {
+    let value = if in_favour { weight.value } else { 0 };
+    let (ciphertext, randomness) = encrypt_vote(election_pubkey, value)?;
+    let proof = prove_ballot(
+        election_pubkey,
+        domain,
+        &ciphertext,
+        &randomness,
+        weight,
+        in_favour,
+    )?;
+    Some(Ballot { ciphertext, proof })
+}
+
+/// Proves that `ciphertext` encrypts 0 or the same value as `weight`, knowing it encrypts
+/// `weight.value` if `in_favour` (0 otherwise) with `randomness`.
+///
+/// The branch for the other value is simulated with a chosen challenge, so the proof only
+/// verifies if the real branch is honest. Exposed separately from [`cast_ballot`] so tests can
+/// attempt proofs for ciphertexts of other values.
+pub fn prove_ballot(
+    election_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    ciphertext: &[u8; 64],
+    randomness: &Scalar,
+    weight: &Weight,
+    in_favour: bool,
+) -> Option<ProofEnvelope> {
+    let y = decompress(election_pubkey)?;
+    let (c_1, c_2) = split(ciphertext)?;
+    let (w_1, w_2) = split(&weight.ciphertext)?;
+    let real = in_favour as usize;
+    let simulated = 1 - real;
+    // Branch 1 is the statement that `ciphertext - weight` encrypts zero.
+    let targets = [(c_1, c_2), (c_1 - w_1, c_2 - w_2)];
+    let witness = if in_favour {
+        randomness - weight.randomness
+    } else {
+        *randomness
+    };
+
+    let simulated_challenge = Scalar::random(&mut OsRng);
+    let simulated_response = Scalar::random(&mut OsRng);
+    let nonce = Scalar::random(&mut OsRng);
+    let mut a = [RistrettoPoint::identity(); 2];
+    let mut b = [RistrettoPoint::identity(); 2];
+    a[simulated] = simulated_response * *G - simulated_challenge * targets[simulated].0;
+    b[simulated] = simulated_response * y - simulated_challenge * targets[simulated].1;
+    a[real] = nonce * *G;
+    b[real] = nonce * y;
+
+    let mut commitments = [0u8; 128];
+    for (i, point) in [a[0], b[0], a[1], b[1]].iter().enumerate() {
+        commitments[32 * i..32 * (i + 1)].copy_from_slice(point.compress().as_bytes());
+    }
+    let challenge = zk_utils::ballot_proof_challenge(
+        domain,
+        election_pubkey,
+        ciphertext,
+        &weight.ciphertext,
+        &commitments,
+    );
+    let challenge = Scalar::from_canonical_bytes(challenge.0)?;
+    let real_challenge = challenge - simulated_challenge;
+    let real_response = nonce + real_challenge * witness;
+
+    let (c0, z) = if in_favour {
+        (simulated_challenge, [simulated_response, real_response])
+    } else {
+        (real_challenge, [real_response, simulated_response])
+    };
+    let mut proof = Vec::with_capacity(zk_utils::BALLOT_PROOF_LEN);
+    proof.extend_from_slice(&commitments);
+    proof.extend_from_slice(c0.as_bytes());
+    proof.extend_from_slice(z[0].as_bytes());
+    proof.extend_from_slice(z[1].as_bytes());
+    Some(envelope(
+        ProofSystem::Sigma,
+        Circuit::Vote,
+        *domain,
+        zk_utils::ballot_input_hash(election_pubkey, ciphertext, &weight.ciphertext),
+        proof,
+    ))
+}
+
+/// Sums ballots the way `Proposal::add_ballot` does, starting from the empty tally.
+pub fn sum_ballots(ballots: &[[u8; 64]]) -> Option<[u8; 64]> {
+    let mut c_1 = RistrettoPoint::identity();
+    let mut c_2 = RistrettoPoint::identity();
+    for ballot in ballots {
+        let (b_1, b_2) = split(ballot)?;
+        c_1 += b_1;
+        c_2 += b_2;
+    }
+    Some(join(c_1, c_2))
+}
+
+/// A committee member's partial decryptions, as passed to `submit_decryption_share`.
+#[derive(Clone, Debug)]
+pub struct DecryptionShare {
+    pub shares: Vec<[u8; 32]>,
+    pub proof: ProofEnvelope,
+}
+
+/// Computes `xᵢ·C₁` for each of a proposal's encrypted `totals` and proves they all used the
+/// secret behind `share_pubkey`.
+pub fn decrypt_share(
+    secret_share: &Scalar,
+    domain: &[u8; 32],
+    totals: &[[u8; 64]],
+) -> Option<DecryptionShare> {
+    let c1s = totals
+        .iter()
+        .map(|total| Some(split(total)?.0))
+        .collect::<Option<Vec<_>>>()?;
+    let share_pubkey = (secret_share * *G).compress().to_bytes();
+    let shares: Vec<[u8; 32]> = c1s
+        .iter()
+        .map(|c_1| (secret_share * c_1).compress().to_bytes())
+        .collect();
+    let ciphertexts_c1: Vec<[u8; 32]> = c1s.iter().map(|c_1| c_1.compress().to_bytes()).collect();
+
+    let nonce = Scalar::random(&mut OsRng);
+    let mut proof = Vec::with_capacity(zk_utils::decryption_share_proof_len(totals.len()));
+    proof.extend_from_slice((nonce * *G).compress().as_bytes());
+    for c_1 in &c1s {
+        proof.extend_from_slice((nonce * c_1).compress().as_bytes());
+    }
+    let challenge = zk_utils::decryption_share_challenge(
+        domain,
+        &share_pubkey,
+        &ciphertexts_c1,
+        &shares,
+        &proof,
+    );
+    let z = nonce + Scalar::from_canonical_bytes(challenge.0)? * secret_share;
+    proof.extend_from_slice(z.as_bytes());
+    Some(DecryptionShare {
+        proof: envelope(
+            ProofSystem::Sigma,
+            Circuit::DecryptionShare,
+            *domain,
+            zk_utils::decryption_share_input_hash(&share_pubkey, &ciphertexts_c1, &shares),
+            proof,
+        ),
+        shares,
+    })
+}
+
+/// Recovers the votes in an encrypted total from `(Shamir index, share)` pairs by searching
+/// `0..=max_votes`; these are the `votes_for` and `votes_cast` arguments of
+/// `finalize_proposal`.
+pub fn decrypt_tally(tally: &[u8; 64], shares: &[(u64, [u8; 32])], max_votes: u64) -> Option<u64> {
+    let (_, c_2) = split(tally)?;
+    let mut mask = RistrettoPoint::identity();
+    for (index, share) in shares {
+        let mut coefficient = Scalar::one();
+        for (other, _) in shares {
+            if other != index {
+                let numerator = -Scalar::from(*other);
+                let denominator = Scalar::from(*index) - Scalar::from(*other);
+                coefficient *= numerator * denominator.invert();
+            }
+        }
+        mask += coefficient * decompress(share)?;
+    }
+    let message = c_2 - mask;
+    let mut candidate = RistrettoPoint::identity();
+    for votes in 0..=max_votes {
+        if candidate == message {
+            return Some(votes);
+        }
+        candidate += *G;
+    }
+    None
+}
+
+/// The arguments of `register_stake_weight`, plus the weight the voter's ballot must match.
+pub struct StakeWeightProof {
+    pub weight: Weight,
+    pub stake_split: [u8; 128],
+    pub split_commitments: [u8; 64],
+    pub split_proof: ProofEnvelope,
+    pub equality_proof: ProofEnvelope,
+    pub range_proof: RangeProofData,
+    pub weight_proof: ProofEnvelope,
+}
+
+/// Claims `weight` votes against a snapshot `balance` holding `balance_value` under
+/// `keypair`, splitting it into `weight·stake_per_vote` and a remainder. Returns `None` if
+/// the balance does not cover the weight.
+pub fn prove_stake_weight(
+    keypair: &ElGamalKeypair,
+    election_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    stake_per_vote: u64,
+    balance: &[u8; 64],
+    balance_value: u64,
+    weight: u64,
+) -> Option<StakeWeightProof> {
+    let remainder = balance_value.checked_sub(weight.checked_mul(stake_per_vote)?)?;
+    let weight_opening = PedersenOpening::new_rand();
+    let remainder_opening = PedersenOpening::new_rand();
+    let weight_part = keypair.pubkey().encrypt_with(weight, &weight_opening);
+    let remainder_part = keypair.pubkey().encrypt_with(remainder, &remainder_opening);
+    let mut stake_split = [0u8; 128];
+    stake_split[..64].copy_from_slice(&weight_part.to_bytes());
+    stake_split[64..].copy_from_slice(&remainder_part.to_bytes());
+
+    // stake_per_vote·E_w + E_rem, as `register_stake_weight` recombines it.
+    let scale = Scalar::from(stake_per_vote);
+    let recombined = join(
+        scale * weight_part.commitment.get_point() + remainder_part.commitment.get_point(),
+        scale * weight_part.handle.get_point() + remainder_part.handle.get_point(),
+    );
+    let split_proof = prove_zero_delta(
+        keypair,
+        domain,
+        balance,
+        &recombined,
+        0,
+        BalanceUpdate::Debit,
+    )?;
+    let equality = prove_equality(
+        keypair,
+        domain,
+        &[
+            (weight_part.to_bytes(), weight),
+            (remainder_part.to_bytes(), remainder),
+        ],
+    )?;
+    let range_proof = prove_range(&[
+        (weight, &equality.openings[0]),
+        (remainder, &equality.openings[1]),
+    ])?;
+    let mut split_commitments = [0u8; 64];
+    split_commitments[..32].copy_from_slice(&equality.commitments[0]);
+    split_commitments[32..].copy_from_slice(&equality.commitments[1]);
+
+    let encrypted_weight = Weight::encrypted(election_pubkey, weight)?;
+    let weight_proof = prove_weight_commitment(
+        election_pubkey,
+        domain,
+        &equality.commitments[0],
+        equality.openings[0].get_scalar(),
+        &encrypted_weight,
+    )?;
+    Some(StakeWeightProof {
+        weight: encrypted_weight,
+        stake_split,
+        split_commitments,
+        split_proof,
+        equality_proof: equality.proof,
+        range_proof,
+        weight_proof,
+    })
+}
+
+/// Proves that the Pedersen commitment `weight_commitment = w·G + opening·H` and
+/// `weight.ciphertext` hide the same `w = weight.value`.
+pub fn prove_weight_commitment(
+    election_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    weight_commitment: &[u8; 32],
+    opening: &Scalar,
+    weight: &Weight,
+) -> Option<ProofEnvelope> {
+    let y = decompress(election_pubkey)?;
+    let nonces: [Scalar; 3] = std::array::from_fn(|_| Scalar::random(&mut OsRng));
+    let mut commitments = [0u8; 96];
+    for (i, point) in [
+        nonces[0] * *G + nonces[1] * *H,
+        nonces[2] * *G,
+        nonces[0] * *G + nonces[2] * y,
+    ]
+    .iter()
+    .enumerate()
+    {
+        commitments[32 * i..32 * (i + 1)].copy_from_slice(point.compress().as_bytes());
+    }
+    let challenge = zk_utils::weight_commitment_challenge(
+        domain,
+        election_pubkey,
+        weight_commitment,
+        &weight.ciphertext,
+        &commitments,
+    );
+    let challenge = Scalar::from_canonical_bytes(challenge.0)?;
+
+    let mut proof = Vec::with_capacity(zk_utils::WEIGHT_COMMITMENT_PROOF_LEN);
+    proof.extend_from_slice(&commitments);
+    for (nonce, secret) in [
+        (nonces[0], Scalar::from(weight.value)),
+        (nonces[1], *opening),
+        (nonces[2], weight.randomness),
+    ] {
+        proof.extend_from_slice((nonce + challenge * secret).as_bytes());
+    }
+    Some(envelope(
+        ProofSystem::Sigma,
+        Circuit::StakeWeight,
+        *domain,
+        zk_utils::weight_commitment_input_hash(
+            election_pubkey,
+            weight_commitment,
+            &weight.ciphertext,
+        ),
+        proof,
+    ))
+}
@@ -0,0 +1,389 @@
+use curve25519_dalek::ristretto::CompressedRistretto;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::encryption::pedersen::{PedersenOpening, G};
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{ErrorCode, ProofEnvelope, UserStake};
+use zk_liquidity_pool_prover::*;
+
+mod common;
+
+fn domain() -> [u8; 32] {
+    zk_utils::domain_separator("stake", &Pubkey::new_from_array([7; 32]))
+}
+
+fn verify(
+    keypair: &ElGamalKeypair,
+    proof: &ProofEnvelope,
+    amount: u64,
+    direction: BalanceUpdate,
+    old_balance: [u8; 64],
+    new_balance: [u8; 64],
+) -> bool {
+    zk_utils::verify_confidential_balance(
+        proof,
+        &domain(),
+        amount,
+        direction,
+        keypair.pubkey().to_bytes(),
+        old_balance,
+        new_balance,
+    )
+    .is_ok()
+}
+
+#[test]
+fn program_generators_match_sdk() {
+    let (g, h) = pedersen_generators();
+    assert_eq!(zk_utils::PEDERSEN_G.0, g);
+    assert_eq!(zk_utils::PEDERSEN_H.0, h);
+}
+
+#[test]
+fn credit_then_debit_round_trip() {
+    let keypair = ElGamalKeypair::new_rand();
+
+    let staked = prove_balance_update(
+        &keypair,
+        &domain(),
+        &ZERO_BALANCE,
+        1_000,
+        BalanceUpdate::Credit,
+    )
+    .expect("credit proof");
+    assert!(verify(
+        &keypair,
+        &staked.proof,
+        1_000,
+        BalanceUpdate::Credit,
+        ZERO_BALANCE,
+        staked.new_balance,
+    ));
+    assert_eq!(decrypt_balance(&keypair, &staked.new_balance), Some(1_000));
+
+    let withdrawn = prove_balance_update(
+        &keypair,
+        &domain(),
+        &staked.new_balance,
+        400,
+        BalanceUpdate::Debit,
+    )
+    .expect("debit proof");
+    assert!(verify(
+        &keypair,
+        &withdrawn.proof,
+        400,
+        BalanceUpdate::Debit,
+        staked.new_balance,
+        withdrawn.new_balance,
+    ));
+    assert_eq!(decrypt_balance(&keypair, &withdrawn.new_balance), Some(600));
+}
+
+#[test]
+fn repeated_stakes_accumulate_in_one_position() {
+    let keypair = ElGamalKeypair::new_rand();
+    let mut position = UserStake {
+        confidential_balance: ZERO_BALANCE,
+        encrypted_data: Vec::new(),
+        stake_timestamp: 0,
+        asset_mint: Pubkey::new_unique(),
+        elgamal_pubkey: keypair.pubkey().to_bytes(),
+        balance_updated_slot: 0,
+        prior_balance: ZERO_BALANCE,
+        prior_balance_slot: 0,
+        unbonding_amount: 0,
+        unbonding_penalty: 0,
+        unbond_timestamp: 0,
+        reward_debt: [0u8; 64],
+        reward_timestamp: 0,
+        reward_acc: 0,
+        balance_commitment: [0u8; 32],
+    };
+
+    let first = prove_credit(&keypair, &domain(), &position.confidential_balance, 0, 300).unwrap();
+    position.record_stake(first.new_balance, first.balance_commitment, 10, 1_000);
+
+    // A proof made before the first stake landed is against a balance that has moved on.
+    let stale = prove_balance_update(
+        &keypair,
+        &domain(),
+        &ZERO_BALANCE,
+        200,
+        BalanceUpdate::Credit,
+    )
+    .unwrap();
+    assert!(!verify(
+        &keypair,
+        &stale.proof,
+        200,
+        BalanceUpdate::Credit,
+        position.confidential_balance,
+        stale.new_balance,
+    ));
+
+    let second = prove_credit(
+        &keypair,
+        &domain(),
+        &position.confidential_balance,
+        300,
+        200,
+    )
+    .unwrap();
+    assert!(verify(
+        &keypair,
+        &second.proof,
+        200,
+        BalanceUpdate::Credit,
+        position.confidential_balance,
+        second.new_balance,
+    ));
+    zk_utils::verify_ciphertext_commitment_equality(
+        &second.equality_proof,
+        &domain(),
+        &position.elgamal_pubkey,
+        &[second.new_balance],
+        &[second.balance_commitment],
+    )
+    .unwrap();
+    position.record_stake(second.new_balance, second.balance_commitment, 20, 2_000);
+
+    assert_eq!(
+        decrypt_balance(&keypair, &position.confidential_balance),
+        Some(500)
+    );
+    assert_eq!(position.balance_commitment, second.balance_commitment);
+    // The lockup restarts with every stake.
+    assert_eq!(position.stake_timestamp, 2_000);
+    assert_eq!(position.balance_at(15), Some(first.new_balance));
+}
+
+#[test]
+fn rejects_tampered_commitment() {
+    let keypair = ElGamalKeypair::new_rand();
+    let old_balance = encrypt_balance(&keypair, 50);
+    let update =
+        prove_balance_update(&keypair, &domain(), &old_balance, 10, BalanceUpdate::Credit).unwrap();
+
+    // Bump the committed value by one without touching the handle.
+    let mut tampered = update.new_balance;
+    let commitment = CompressedRistretto::from_slice(&tampered[..32])
+        .decompress()
+        .unwrap()
+        + *G;
+    tampered[..32].copy_from_slice(&commitment.compress().to_bytes());
+
+    assert!(!verify(
+        &keypair,
+        &update.proof,
+        10,
+        BalanceUpdate::Credit,
+        old_balance,
+        tampered,
+    ));
+
+    // Even a freshly generated proof cannot vouch for the tampered ciphertext.
+    let forged = prove_zero_delta(
+        &keypair,
+        &domain(),
+        &old_balance,
+        &tampered,
+        10,
+        BalanceUpdate::Credit,
+    )
+    .unwrap();
+    assert!(!verify(
+        &keypair,
+        &forged,
+        10,
+        BalanceUpdate::Credit,
+        old_balance,
+        tampered,
+    ));
+}
+
+fn verify_equality(
+    keypair: &ElGamalKeypair,
+    ciphertexts: &[[u8; 64]],
+    equality: &EqualityProof,
+) -> anchor_lang::Result<()> {
+    zk_utils::verify_ciphertext_commitment_equality(
+        &equality.proof,
+        &domain(),
+        &keypair.pubkey().to_bytes(),
+        ciphertexts,
+        &equality.commitments,
+    )
+}
+
+#[test]
+fn equality_proofs_bind_fresh_commitments_to_the_plaintext() {
+    let keypair = ElGamalKeypair::new_rand();
+    let update = prove_balance_update(
+        &keypair,
+        &domain(),
+        &ZERO_BALANCE,
+        600,
+        BalanceUpdate::Credit,
+    )
+    .unwrap();
+    let ciphertexts = [update.new_balance, encrypt_balance(&keypair, 0)];
+    let equality = prove_equality(
+        &keypair,
+        &domain(),
+        &[(ciphertexts[0], 600), (ciphertexts[1], 0)],
+    )
+    .unwrap();
+    verify_equality(&keypair, &ciphertexts, &equality).unwrap();
+    // The commitments are fresh, not the ciphertexts' own.
+    assert_ne!(equality.commitments[0], update.new_balance[..32]);
+    let range = prove_range(&[(600, &equality.openings[0]), (0, &equality.openings[1])]).unwrap();
+    assert!(range.verify());
+    assert_eq!(range.context().commitments[0].0, equality.commitments[0]);
+
+    // A commitment to any other value does not match the plaintext.
+    let wrong = prove_equality(&keypair, &domain(), &[(ciphertexts[0], 601)]).unwrap();
+    assert_eq!(
+        verify_equality(&keypair, &ciphertexts[..1], &wrong).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+    let other = ElGamalKeypair::new_rand();
+    let mut rekeyed = prove_equality(&other, &domain(), &[(ciphertexts[0], 600)]).unwrap();
+    rekeyed.proof.public_input_hash = zk_utils::equality_input_hash(
+        &keypair.pubkey().to_bytes(),
+        &ciphertexts[..1],
+        &rekeyed.commitments,
+    );
+    assert_eq!(
+        verify_equality(&keypair, &ciphertexts[..1], &rekeyed).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+
+    assert_eq!(
+        verify_equality(&keypair, &ciphertexts[..1], &equality).unwrap_err(),
+        ErrorCode::InvalidPublicInputs.into()
+    );
+    let mut truncated = equality;
+    truncated.proof.proof.truncate(zk_utils::EQUALITY_PROOF_LEN);
+    assert_eq!(
+        verify_equality(&keypair, &ciphertexts, &truncated).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+}
+
+/// Known answer for a forged debit: a position holding 10 debits 1,000,000 by moving only its
+/// decrypt handle, so `C` still opens to 10.
+#[test]
+fn forged_handle_does_not_bind_the_commitment() {
+    let keypair = ElGamalKeypair::new_rand();
+    let opening = PedersenOpening::new_rand();
+    let old_balance = keypair.pubkey().encrypt_with(10_u64, &opening).to_bytes();
+    let target = zk_utils::subtract_public_value(&old_balance, 1_000_000).unwrap();
+    let old_commitment: [u8; 32] = old_balance[..32].try_into().unwrap();
+    let forged = common::forge_ciphertext(&keypair, &target, &old_commitment);
+    assert_eq!(forged[..32], old_commitment);
+
+    // The balance-update proof accepts it, and a range proof over `C` still goes through.
+    let proof = prove_zero_delta(
+        &keypair,
+        &domain(),
+        &old_balance,
+        &forged,
+        1_000_000,
+        BalanceUpdate::Debit,
+    )
+    .unwrap();
+    assert!(verify(
+        &keypair,
+        &proof,
+        1_000_000,
+        BalanceUpdate::Debit,
+        old_balance,
+        forged,
+    ));
+    let range = prove_range(&[(10, &opening)]).unwrap();
+    assert!(range.verify());
+    assert_eq!(range.context().commitments[0].0, old_commitment);
+
+    // A commitment to 10 cannot be bound to the forged plaintext, which is `10 - 1,000,000`
+    // modulo the group order and so has no 64-bit range proof either.
+    let equality = prove_equality(&keypair, &domain(), &[(forged, 10)]).unwrap();
+    assert_eq!(
+        verify_equality(&keypair, &[forged], &equality).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn rejects_wrong_amount_direction_and_key() {
+    let keypair = ElGamalKeypair::new_rand();
+    let old_balance = encrypt_balance(&keypair, 500);
+    let update =
+        prove_balance_update(&keypair, &domain(), &old_balance, 200, BalanceUpdate::Debit).unwrap();
+
+    assert!(!verify(
+        &keypair,
+        &update.proof,
+        201,
+        BalanceUpdate::Debit,
+        old_balance,
+        update.new_balance,
+    ));
+    assert!(!verify(
+        &keypair,
+        &update.proof,
+        200,
+        BalanceUpdate::Credit,
+        old_balance,
+        update.new_balance,
+    ));
+
+    let other = ElGamalKeypair::new_rand();
+    assert!(!verify(
+        &other,
+        &update.proof,
+        200,
+        BalanceUpdate::Debit,
+        old_balance,
+        update.new_balance,
+    ));
+}
+
+#[test]
+fn rejects_malformed_proofs() {
+    let keypair = ElGamalKeypair::new_rand();
+    let update =
+        prove_balance_update(&keypair, &domain(), &ZERO_BALANCE, 7, BalanceUpdate::Credit).unwrap();
+
+    let mut empty = update.proof.clone();
+    empty.proof.clear();
+    assert!(!verify(
+        &keypair,
+        &empty,
+        7,
+        BalanceUpdate::Credit,
+        ZERO_BALANCE,
+        update.new_balance,
+    ));
+    let mut truncated = update.proof.clone();
+    truncated.proof.truncate(64);
+    assert!(!verify(
+        &keypair,
+        &truncated,
+        7,
+        BalanceUpdate::Credit,
+        ZERO_BALANCE,
+        update.new_balance,
+    ));
+
+    let mut flipped = update.proof.clone();
+    flipped.proof[70] ^= 1;
+    assert!(!verify(
+        &keypair,
+        &flipped,
+        7,
+        BalanceUpdate::Credit,
+        ZERO_BALANCE,
+        update.new_balance,
+    ));
+}
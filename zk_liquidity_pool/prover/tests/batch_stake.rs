@@ -0,0 +1,262 @@
+use anchor_lang::Key;
+use anchor_spl::token_2022::spl_token_2022;
+use solana_program::account_info::AccountInfo;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use spl_token_2022::state::{Account, AccountState, Mint};
+use zk_liquidity_pool::{
+    load_stake_entries, split_proof_buffers, AssetVault, ErrorCode, LiquidityPool, LockupPolicy,
+    ProofSource, RewardTiers, UserStake,
+};
+
+mod common;
+
+use common::{account, anchor_data};
+
+fn token_account(mint: &Pubkey, owner: &Pubkey) -> Vec<u8> {
+    let mut data = vec![0u8; Account::LEN];
+    Account::pack(
+        Account {
+            mint: *mint,
+            owner: *owner,
+            state: AccountState::Initialized,
+            ..Account::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    data
+}
+
+/// The accounts of one `stake` into a fresh pool for `token_mint`, in `StakeAccounts` order.
+fn stake_accounts(user: &Pubkey, token_mint: &Pubkey) -> Vec<AccountInfo<'static>> {
+    asset_accounts(user, token_mint, token_mint)
+}
+
+/// The accounts of one `stake` of `token_mint` into a fresh copy of the pool created for
+/// `pool_mint`, in `StakeAccounts` order.
+fn asset_accounts(
+    user: &Pubkey,
+    pool_mint: &Pubkey,
+    token_mint: &Pubkey,
+) -> Vec<AccountInfo<'static>> {
+    let (pool_key, bump) = LiquidityPool::find_address(pool_mint);
+    let pool = LiquidityPool {
+        authority: Pubkey::new_unique(),
+        token_mint: *pool_mint,
+        total_commitment: [0u8; 32],
+        auditor_elgamal_pubkey: None,
+        auditor_total: [0u8; 64],
+        lockup: LockupPolicy::default(),
+        treasury: Pubkey::new_unique(),
+        reward_tiers: RewardTiers::default(),
+        reward_rate: 0,
+        acc_reward_per_share: 0,
+        last_reward_timestamp: 0,
+        reward_rate_history: Vec::new(),
+        bump,
+    };
+    let (vault_key, vault_bump) = Pubkey::find_program_address(
+        &[AssetVault::SEED, pool_key.as_ref(), token_mint.as_ref()],
+        &zk_liquidity_pool::ID,
+    );
+    let pool_token_account = Pubkey::new_unique();
+    let vault = AssetVault {
+        pool: pool_key,
+        mint: *token_mint,
+        token_account: pool_token_account,
+        reward_weight: 1,
+        bump: vault_bump,
+    };
+    let (position_key, _) = Pubkey::find_program_address(
+        &[
+            b"user_stake",
+            user.as_ref(),
+            pool_key.as_ref(),
+            token_mint.as_ref(),
+        ],
+        &zk_liquidity_pool::ID,
+    );
+    let position = UserStake {
+        confidential_balance: [0u8; 64],
+        encrypted_data: Vec::new(),
+        stake_timestamp: 0,
+        asset_mint: *token_mint,
+        elgamal_pubkey: [0u8; 32],
+        balance_updated_slot: 0,
+        prior_balance: [0u8; 64],
+        prior_balance_slot: 0,
+        unbonding_amount: 0,
+        unbonding_penalty: 0,
+        unbond_timestamp: 0,
+        reward_debt: [0u8; 64],
+        reward_timestamp: 0,
+        reward_acc: 0,
+        balance_commitment: [0u8; 32],
+    };
+    let mut mint = vec![0u8; Mint::LEN];
+    Mint::pack(
+        Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Mint::default()
+        },
+        &mut mint,
+    )
+    .unwrap();
+
+    let program = zk_liquidity_pool::ID;
+    let token = spl_token_2022::ID;
+    vec![
+        account(pool_key, program, anchor_data(&pool), false, false),
+        account(vault_key, program, anchor_data(&vault), false, false),
+        account(*user, Pubkey::default(), Vec::new(), true, false),
+        account(position_key, program, anchor_data(&position), false, false),
+        account(
+            Pubkey::new_unique(),
+            token,
+            token_account(token_mint, user),
+            false,
+            false,
+        ),
+        account(
+            pool_token_account,
+            token,
+            token_account(token_mint, &pool_key),
+            false,
+            false,
+        ),
+        account(*token_mint, token, mint, false, false),
+        account(token, Pubkey::default(), Vec::new(), false, true),
+    ]
+}
+
+fn leak(accounts: Vec<AccountInfo<'static>>) -> &'static [AccountInfo<'static>] {
+    Box::leak(accounts.into_boxed_slice())
+}
+
+#[test]
+fn entries_are_loaded_with_the_stake_checks() {
+    let user = Pubkey::new_unique();
+    let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let accounts = leak(
+        mints
+            .iter()
+            .flat_map(|mint| stake_accounts(&user, mint))
+            .collect(),
+    );
+    let entries = load_stake_entries(accounts, 2).unwrap();
+    assert_eq!(entries.len(), 2);
+    for (entry, mint) in entries.iter().zip(mints) {
+        assert_eq!(entry.pool.token_mint, mint);
+        assert_eq!(entry.user_stake.asset_mint, mint);
+        assert_eq!(*entry.user.key, user);
+    }
+
+    // A position belonging to another pool is rejected as it would be by `stake`.
+    let mut crossed = stake_accounts(&user, &mints[0]);
+    crossed[3] = stake_accounts(&user, &mints[1])[3].clone();
+    assert_eq!(
+        load_stake_entries(leak(crossed), 1).map(drop).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+    );
+
+    // So is a pool token account other than the mint's vault.
+    let mut unlisted = stake_accounts(&user, &mints[0]);
+    unlisted[5] = account(
+        Pubkey::new_unique(),
+        spl_token_2022::ID,
+        token_account(&mints[0], unlisted[0].key),
+        false,
+        false,
+    );
+    assert_eq!(
+        load_stake_entries(leak(unlisted), 1).map(drop).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintAddress.into()
+    );
+}
+
+#[test]
+fn entries_must_match_the_arguments_and_not_repeat_a_position() {
+    let user = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+
+    let one = leak(stake_accounts(&user, &mint));
+    assert_eq!(
+        load_stake_entries(one, 2).map(drop).unwrap_err(),
+        anchor_lang::error::ErrorCode::AccountNotEnoughKeys.into()
+    );
+
+    let mut two = stake_accounts(&user, &mint);
+    two.extend(stake_accounts(&user, &Pubkey::new_unique()));
+    assert_eq!(
+        load_stake_entries(leak(two), 1).map(drop).unwrap_err(),
+        ErrorCode::BatchLengthMismatch.into()
+    );
+
+    let repeated = stake_accounts(&user, &mint);
+    let twice = leak(repeated.iter().chain(&repeated).cloned().collect());
+    assert_eq!(
+        load_stake_entries(twice, 2).map(drop).unwrap_err(),
+        ErrorCode::DuplicateBatchEntry.into()
+    );
+}
+
+#[test]
+fn entries_may_stake_several_mints_into_one_pool() {
+    let user = Pubkey::new_unique();
+    let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut accounts = stake_accounts(&user, &mints[0]);
+    let mut second = asset_accounts(&user, &mints[0], &mints[1]);
+    // The pool is one account, passed to both entries.
+    second[0] = accounts[0].clone();
+    accounts.extend(second);
+    let entries = load_stake_entries(leak(accounts), 2).unwrap();
+    assert_eq!(entries[0].pool.key(), entries[1].pool.key());
+    for (entry, mint) in entries.iter().zip(mints) {
+        assert_eq!(entry.vault.mint, mint);
+        assert_eq!(entry.user_stake.asset_mint, mint);
+    }
+}
+
+#[test]
+fn shared_proof_buffers_are_passed_once() {
+    let user = Pubkey::new_unique();
+    let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let buffers = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let with_buffers = |count: usize| {
+        let mut accounts: Vec<_> = mints
+            .iter()
+            .flat_map(|mint| stake_accounts(&user, mint))
+            .collect();
+        accounts.extend(
+            buffers[..count]
+                .iter()
+                .map(|key| account(*key, zk_liquidity_pool::ID, Vec::new(), false, false)),
+        );
+        leak(accounts)
+    };
+
+    // Both entries read their proof from the same buffer.
+    let shared = [
+        ProofSource::Buffer(buffers[0]),
+        ProofSource::Buffer(buffers[0]),
+    ];
+    let (entry_accounts, proof_buffers) = split_proof_buffers(with_buffers(1), &shared).unwrap();
+    assert_eq!(proof_buffers.len(), 1);
+    assert_eq!(*proof_buffers[0].key, buffers[0]);
+    assert_eq!(load_stake_entries(entry_accounts, 2).unwrap().len(), 2);
+
+    let distinct = [
+        ProofSource::Buffer(buffers[0]),
+        ProofSource::Buffer(buffers[1]),
+    ];
+    let (entry_accounts, proof_buffers) = split_proof_buffers(with_buffers(2), &distinct).unwrap();
+    assert_eq!(proof_buffers.len(), 2);
+    assert_eq!(load_stake_entries(entry_accounts, 2).unwrap().len(), 2);
+
+    assert_eq!(
+        split_proof_buffers(&[], &shared).map(drop).unwrap_err(),
+        ErrorCode::BatchLengthMismatch.into()
+    );
+}
@@ -0,0 +1,89 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use anchor_lang::AccountSerialize;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use zk_liquidity_pool::{
+    Proposal, ProposalPayload, ProposalStatus, VoteWeighting, MAX_COMMITTEE_SIZE,
+};
+
+/// An account for an instruction's `AccountInfo`s to borrow for the rest of the test.
+pub fn account(
+    key: Pubkey,
+    owner: Pubkey,
+    data: Vec<u8>,
+    is_signer: bool,
+    executable: bool,
+) -> AccountInfo<'static> {
+    AccountInfo::new(
+        Box::leak(Box::new(key)),
+        is_signer,
+        true,
+        Box::leak(Box::new(1_000_000)),
+        Box::leak(data.into_boxed_slice()),
+        Box::leak(Box::new(owner)),
+        executable,
+        0,
+    )
+}
+
+/// `account`'s data as Anchor stores it, discriminator first.
+pub fn anchor_data<T: AccountSerialize>(account: &T) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data
+}
+
+/// An active, stake-weighted proposal open for slots 100 to 200.
+pub fn proposal(quorum: u64, threshold_bps: u16) -> Proposal {
+    Proposal {
+        governance: Pubkey::new_unique(),
+        id: 0,
+        proposer: Pubkey::new_unique(),
+        payload: ProposalPayload::UpdateRewardParams { new_reward_rate: 5 },
+        start_slot: 100,
+        end_slot: 200,
+        quorum,
+        threshold_bps,
+        vote_weighting: VoteWeighting::Stake {
+            stake_per_vote: 1_000,
+        },
+        votes_for: 0,
+        votes_against: 0,
+        election_pubkey: [0u8; 32],
+        encrypted_tally: [0u8; 64],
+        encrypted_turnout: [0u8; 64],
+        votes_cast: 0,
+        ballots_cast: 0,
+        decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        turnout_decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        decryption_share_mask: 0,
+        status: ProposalStatus::Active,
+        bump: 255,
+    }
+}
+
+fn point(bytes: &[u8]) -> RistrettoPoint {
+    CompressedRistretto::from_slice(bytes).decompress().unwrap()
+}
+
+/// A ciphertext under `keypair` with `commitment` as its `C` that decrypts to the same value
+/// as `target`, made by moving the handle by `s⁻¹·(commitment - C_target)`.
+///
+/// This is what the owner of the secret key `s` can do to any balance: the commitment half
+/// can be left opening to whatever they like while the plaintext is something else.
+pub fn forge_ciphertext(
+    keypair: &ElGamalKeypair,
+    target: &[u8; 64],
+    commitment: &[u8; 32],
+) -> [u8; 64] {
+    let shift = point(commitment) - point(&target[..32]);
+    let handle = point(&target[32..]) + keypair.secret().get_scalar().invert() * shift;
+    let mut forged = [0u8; 64];
+    forged[..32].copy_from_slice(commitment);
+    forged[32..].copy_from_slice(handle.compress().as_bytes());
+    forged
+}
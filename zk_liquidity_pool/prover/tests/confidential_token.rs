@@ -0,0 +1,391 @@
+use anchor_lang::{AccountDeserialize, Id, Result};
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_interface::TokenAccount;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use spl_token_2022::extension::confidential_transfer::instruction::{
+    ApplyPendingBalanceData, ConfidentialTransferInstruction, ConfigureAccountInstructionData,
+    DepositInstructionData, TransferInstructionData, WithdrawInstructionData,
+};
+use spl_token_2022::extension::confidential_transfer::ConfidentialTransferAccount;
+use spl_token_2022::extension::{
+    BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+};
+use spl_token_2022::instruction::{
+    decode_instruction_data, decode_instruction_type, TokenInstruction,
+};
+use spl_token_2022::solana_zk_token_sdk::zk_token_elgamal::pod::{
+    AeCiphertext, ElGamalCiphertext, ElGamalPubkey,
+};
+use spl_token_2022::state::{Account, AccountState};
+use zk_liquidity_pool::confidential_token::ConfidentialTokenAccount;
+use zk_liquidity_pool::{confidential_token, ConfidentialTokenProgram, ErrorCode};
+
+const DECIMALS: u8 = 6;
+const DECRYPTABLE_BALANCE: [u8; 36] = [9u8; 36];
+
+/// Checks an instruction targets Token-2022 and returns its confidential-transfer data,
+/// after the extension's instruction tag.
+fn confidential_data(instruction: &Instruction) -> (u8, &[u8]) {
+    assert_eq!(instruction.program_id, spl_token_2022::ID);
+    assert!(matches!(
+        TokenInstruction::unpack(&instruction.data).unwrap(),
+        TokenInstruction::ConfidentialTransferExtension
+    ));
+    let data = &instruction.data[1..];
+    let kind: ConfidentialTransferInstruction = decode_instruction_type(data).unwrap();
+    (kind as u8, data)
+}
+
+/// Packs a Token-2022 account, with the confidential transfer extension if one is given.
+fn token_account(
+    mint: &Pubkey,
+    owner: &Pubkey,
+    confidential: Option<ConfidentialTransferAccount>,
+) -> Vec<u8> {
+    let extensions: &[ExtensionType] = match confidential {
+        Some(_) => &[ExtensionType::ConfidentialTransferAccount],
+        None => &[],
+    };
+    let len = ExtensionType::try_calculate_account_len::<Account>(extensions).unwrap();
+    let mut data = vec![0u8; len];
+    let mut state = StateWithExtensionsMut::<Account>::unpack_uninitialized(&mut data).unwrap();
+    state.base = Account {
+        mint: *mint,
+        owner: *owner,
+        amount: 500,
+        state: AccountState::Initialized,
+        ..Account::default()
+    };
+    state.pack_base();
+    state.init_account_type().unwrap();
+    if let Some(confidential) = confidential {
+        *state
+            .init_extension::<ConfidentialTransferAccount>(true)
+            .unwrap() = confidential;
+    }
+    data
+}
+
+fn confidential_extension() -> ConfidentialTransferAccount {
+    ConfidentialTransferAccount {
+        approved: true.into(),
+        elgamal_pubkey: ElGamalPubkey([7u8; 32]),
+        pending_balance_lo: ElGamalCiphertext([1u8; 64]),
+        pending_balance_hi: ElGamalCiphertext([2u8; 64]),
+        available_balance: ElGamalCiphertext([3u8; 64]),
+        decryptable_available_balance: AeCiphertext(DECRYPTABLE_BALANCE),
+        allow_confidential_credits: true.into(),
+        allow_non_confidential_credits: true.into(),
+        pending_balance_credit_counter: 4u64.into(),
+        maximum_pending_balance_credit_counter: 65_536u64.into(),
+        expected_pending_balance_credit_counter: 0u64.into(),
+        actual_pending_balance_credit_counter: 0u64.into(),
+    }
+}
+
+#[test]
+fn program_id_is_token_2022() {
+    assert_eq!(ConfidentialTokenProgram::id(), spl_token_2022::ID);
+}
+
+#[test]
+fn deposit_and_apply_pending_balance_are_signed_by_the_authority() {
+    let (account, mint, authority) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let deposit = confidential_token::deposit_instruction(
+        &spl_token_2022::ID,
+        &account,
+        &mint,
+        &authority,
+        1_000,
+        DECIMALS,
+    )
+    .unwrap();
+    let (kind, data) = confidential_data(&deposit);
+    assert_eq!(kind, ConfidentialTransferInstruction::Deposit as u8);
+    let data = decode_instruction_data::<DepositInstructionData>(data).unwrap();
+    assert_eq!((u64::from(data.amount), data.decimals), (1_000, DECIMALS));
+    assert_eq!(
+        deposit.accounts,
+        vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+
+    let apply = confidential_token::apply_pending_balance_instruction(
+        &spl_token_2022::ID,
+        &account,
+        &authority,
+        3,
+        DECRYPTABLE_BALANCE,
+    )
+    .unwrap();
+    let (kind, data) = confidential_data(&apply);
+    assert_eq!(
+        kind,
+        ConfidentialTransferInstruction::ApplyPendingBalance as u8
+    );
+    let data = decode_instruction_data::<ApplyPendingBalanceData>(data).unwrap();
+    assert_eq!(u64::from(data.expected_pending_balance_credit_counter), 3);
+    assert_eq!(
+        data.new_decryptable_available_balance.0,
+        DECRYPTABLE_BALANCE
+    );
+    assert_eq!(
+        apply.accounts,
+        vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+}
+
+#[test]
+fn withdraw_and_transfer_read_proofs_from_context_state_accounts() {
+    let (source, destination, mint, authority, proof_context) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let withdraw = confidential_token::withdraw_instruction(
+        &spl_token_2022::ID,
+        &source,
+        &mint,
+        &authority,
+        250,
+        DECIMALS,
+        DECRYPTABLE_BALANCE,
+        &proof_context,
+    )
+    .unwrap();
+    let (kind, data) = confidential_data(&withdraw);
+    assert_eq!(kind, ConfidentialTransferInstruction::Withdraw as u8);
+    let data = decode_instruction_data::<WithdrawInstructionData>(data).unwrap();
+    assert_eq!((u64::from(data.amount), data.decimals), (250, DECIMALS));
+    assert_eq!(
+        data.new_decryptable_available_balance.0,
+        DECRYPTABLE_BALANCE
+    );
+    // An offset of 0 tells Token-2022 to read the proof from a context-state account.
+    assert_eq!(data.proof_instruction_offset, 0);
+    assert_eq!(
+        withdraw.accounts,
+        vec![
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(proof_context, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+
+    let transfer = confidential_token::transfer_instruction(
+        &spl_token_2022::ID,
+        &source,
+        &mint,
+        &destination,
+        &authority,
+        DECRYPTABLE_BALANCE,
+        &proof_context,
+    )
+    .unwrap();
+    let (kind, data) = confidential_data(&transfer);
+    assert_eq!(kind, ConfidentialTransferInstruction::Transfer as u8);
+    let data = decode_instruction_data::<TransferInstructionData>(data).unwrap();
+    assert_eq!(
+        data.new_source_decryptable_available_balance.0,
+        DECRYPTABLE_BALANCE
+    );
+    assert_eq!(data.proof_instruction_offset, 0);
+    assert_eq!(
+        transfer.accounts,
+        vec![
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(destination, false),
+            AccountMeta::new_readonly(proof_context, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+}
+
+#[test]
+fn accounts_are_configured_under_a_key_proven_in_a_context_state_account() {
+    let (account, payer, mint, authority, proof_context) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let reallocate = confidential_token::reallocate_instruction(
+        &spl_token_2022::ID,
+        &account,
+        &payer,
+        &authority,
+    )
+    .unwrap();
+    assert_eq!(
+        TokenInstruction::unpack(&reallocate.data).unwrap(),
+        TokenInstruction::Reallocate {
+            extension_types: vec![ExtensionType::ConfidentialTransferAccount],
+        }
+    );
+    assert_eq!(
+        reallocate.accounts,
+        vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new(payer, true),
+            AccountMeta::new_readonly(solana_program::system_program::ID, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+
+    let configure = confidential_token::configure_account_instruction(
+        &spl_token_2022::ID,
+        &account,
+        &mint,
+        &authority,
+        DECRYPTABLE_BALANCE,
+        &proof_context,
+    )
+    .unwrap();
+    let (kind, data) = confidential_data(&configure);
+    assert_eq!(
+        kind,
+        ConfidentialTransferInstruction::ConfigureAccount as u8
+    );
+    let data = decode_instruction_data::<ConfigureAccountInstructionData>(data).unwrap();
+    assert_eq!(data.decryptable_zero_balance.0, DECRYPTABLE_BALANCE);
+    assert_eq!(data.proof_instruction_offset, 0);
+    assert_eq!(
+        configure.accounts,
+        vec![
+            AccountMeta::new(account, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(proof_context, false),
+            AccountMeta::new_readonly(authority, true),
+        ]
+    );
+}
+
+#[test]
+fn instructions_for_other_token_programs_are_rejected() {
+    let key = Pubkey::new_unique();
+    let incorrect = |result: Result<Instruction>| {
+        assert_eq!(result.unwrap_err(), ProgramError::IncorrectProgramId.into());
+    };
+    for program in [anchor_spl::token::ID, Pubkey::new_unique()] {
+        incorrect(confidential_token::transfer_checked_instruction(
+            &program, &key, &key, &key, &key, 1, DECIMALS,
+        ));
+        incorrect(confidential_token::mint_to_checked_instruction(
+            &program, &key, &key, &key, 1, DECIMALS,
+        ));
+        incorrect(confidential_token::deposit_instruction(
+            &program, &key, &key, &key, 1, DECIMALS,
+        ));
+        incorrect(confidential_token::reallocate_instruction(
+            &program, &key, &key, &key,
+        ));
+        incorrect(confidential_token::configure_account_instruction(
+            &program,
+            &key,
+            &key,
+            &key,
+            DECRYPTABLE_BALANCE,
+            &key,
+        ));
+        incorrect(confidential_token::apply_pending_balance_instruction(
+            &program,
+            &key,
+            &key,
+            0,
+            DECRYPTABLE_BALANCE,
+        ));
+        incorrect(confidential_token::transfer_instruction(
+            &program,
+            &key,
+            &key,
+            &key,
+            &key,
+            DECRYPTABLE_BALANCE,
+            &key,
+        ));
+        incorrect(confidential_token::withdraw_instruction(
+            &program,
+            &key,
+            &key,
+            &key,
+            1,
+            DECIMALS,
+            DECRYPTABLE_BALANCE,
+            &key,
+        ));
+    }
+}
+
+#[test]
+fn confidential_state_is_read_from_the_account_extension() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let data = token_account(&mint, &owner, Some(confidential_extension()));
+
+    // The same bytes are what `InterfaceAccount<TokenAccount>` checks mint and owner against.
+    let base = TokenAccount::try_deserialize(&mut data.as_slice()).unwrap();
+    assert_eq!((base.mint, base.owner, base.amount), (mint, owner, 500));
+
+    let account = ConfidentialTokenAccount::unpack(&data).unwrap();
+    assert_eq!(
+        account,
+        ConfidentialTokenAccount {
+            approved: true,
+            elgamal_pubkey: [7u8; 32],
+            pending_balance_lo: [1u8; 64],
+            pending_balance_hi: [2u8; 64],
+            available_balance: [3u8; 64],
+            decryptable_available_balance: DECRYPTABLE_BALANCE,
+            allow_confidential_credits: true,
+            pending_balance_credit_counter: 4,
+        }
+    );
+    account.require_credits_allowed().unwrap();
+
+    assert_eq!(
+        ConfidentialTokenAccount::unpack(&token_account(&mint, &owner, None)).unwrap_err(),
+        ErrorCode::ConfidentialTransferNotConfigured.into()
+    );
+}
+
+#[test]
+fn unapproved_or_closed_accounts_cannot_take_deposits() {
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let unpack = |extension: ConfidentialTransferAccount| {
+        ConfidentialTokenAccount::unpack(&token_account(&mint, &owner, Some(extension))).unwrap()
+    };
+
+    let unapproved = unpack(ConfidentialTransferAccount {
+        approved: false.into(),
+        ..confidential_extension()
+    });
+    assert_eq!(
+        unapproved.require_credits_allowed().unwrap_err(),
+        ErrorCode::ConfidentialAccountNotApproved.into()
+    );
+
+    let credits_disabled = unpack(ConfidentialTransferAccount {
+        allow_confidential_credits: false.into(),
+        ..confidential_extension()
+    });
+    assert_eq!(
+        credits_disabled.require_credits_allowed().unwrap_err(),
+        ErrorCode::ConfidentialCreditsDisabled.into()
+    );
+}
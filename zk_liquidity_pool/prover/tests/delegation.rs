@@ -0,0 +1,168 @@
+use anchor_lang::{AccountSerialize, AnchorSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{
+    load_optional_account, DelegateWeight, Delegation, ErrorCode, WeightCheckpoint,
+    MAX_DELEGATE_CHECKPOINTS,
+};
+use zk_liquidity_pool_prover::tally::{self, CommitteeKeys, Weight};
+
+const VOTING_PERIOD: u64 = 100;
+
+fn delegation(delegated_slot: u64) -> Delegation {
+    Delegation {
+        governance: Pubkey::new_unique(),
+        delegate: [7u8; 32],
+        delegated_slot,
+        revoked_slot: 0,
+        active: true,
+        bump: 255,
+    }
+}
+
+#[test]
+fn delegations_apply_to_proposals_created_while_they_were_active() {
+    let mut delegation = delegation(10);
+    // Not for proposals created in the delegation slot or before.
+    assert!(!delegation.is_effective_at(10));
+    assert!(delegation.is_effective_at(11));
+    assert!(delegation.is_effective_at(1_000));
+
+    delegation.active = false;
+    delegation.revoked_slot = 50;
+    assert!(delegation.is_effective_at(50));
+    assert!(!delegation.is_effective_at(51));
+}
+
+#[test]
+fn delegate_weight_is_read_at_the_snapshot() {
+    let mut weight = DelegateWeight::default();
+    assert_eq!(weight.weight_at(100), 0);
+
+    weight.record(10, 1, VOTING_PERIOD).unwrap();
+    weight.record(20, 2, VOTING_PERIOD).unwrap();
+    weight.record(20, 3, VOTING_PERIOD).unwrap();
+    weight.record(30, 2, VOTING_PERIOD).unwrap();
+    assert_eq!(weight.checkpoints.len(), 3);
+    assert_eq!(weight.current(), 2);
+    assert_eq!(weight.weight_at(10), 0);
+    assert_eq!(weight.weight_at(11), 1);
+    assert_eq!(weight.weight_at(21), 3);
+    assert_eq!(weight.weight_at(31), 2);
+}
+
+#[test]
+fn old_checkpoints_are_only_dropped_once_no_open_proposal_needs_them() {
+    let mut weight = DelegateWeight::default();
+    for slot in 1..=MAX_DELEGATE_CHECKPOINTS as u64 {
+        weight.record(slot, slot, VOTING_PERIOD).unwrap();
+    }
+    assert_eq!(
+        weight
+            .record(VOTING_PERIOD + 1, 0, VOTING_PERIOD)
+            .unwrap_err(),
+        ErrorCode::DelegateCheckpointsFull.into()
+    );
+
+    weight.record(VOTING_PERIOD + 2, 0, VOTING_PERIOD).unwrap();
+    assert_eq!(weight.checkpoints.len(), MAX_DELEGATE_CHECKPOINTS);
+    assert_eq!(
+        weight.checkpoints[0],
+        WeightCheckpoint { slot: 2, weight: 2 }
+    );
+    // The oldest open proposal was created after slot 2 and still reads weight 2.
+    assert_eq!(weight.weight_at(3), 2);
+
+    weight.governance = Pubkey::new_unique();
+    assert_eq!(weight.try_to_vec().unwrap().len(), DelegateWeight::LEN);
+    assert_eq!(delegation(1).try_to_vec().unwrap().len(), Delegation::LEN);
+}
+
+#[test]
+fn missing_delegation_accounts_load_as_none() {
+    let key = Pubkey::new_unique();
+    let system = solana_program::system_program::ID;
+    let mut lamports = 0;
+    let mut empty = [];
+    let uninitialized = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut empty,
+        &system,
+        false,
+        0,
+    );
+    assert!(load_optional_account::<Delegation>(&uninitialized)
+        .unwrap()
+        .is_none());
+
+    let delegation = delegation(10);
+    let mut data = Vec::new();
+    delegation.try_serialize(&mut data).unwrap();
+    let mut lamports = 1;
+    let initialized = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &zk_liquidity_pool::ID,
+        false,
+        0,
+    );
+    let loaded = load_optional_account::<Delegation>(&initialized)
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.delegate, delegation.delegate);
+    assert_eq!(
+        load_optional_account::<DelegateWeight>(&initialized)
+            .map(drop)
+            .unwrap_err(),
+        anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch.into()
+    );
+}
+
+#[test]
+fn delegated_weight_is_part_of_the_ballot_statement() {
+    let keys = CommitteeKeys::generate(1, 1);
+    let domain = zk_utils::domain_separator("cast_vote", &Pubkey::new_unique());
+    let verify = |ballot: &tally::Ballot, weight: u64| {
+        zk_utils::verify_ballot(
+            &ballot.proof,
+            &domain,
+            &keys.election_pubkey,
+            &ballot.ciphertext,
+            &zk_utils::public_weight(weight).unwrap(),
+        )
+    };
+
+    let delegate =
+        tally::cast_ballot(&keys.election_pubkey, &domain, true, &Weight::public(3)).unwrap();
+    verify(&delegate, 3).unwrap();
+    assert_eq!(
+        verify(&delegate, 4).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    // A single vote cannot be passed off as a delegate's weight.
+    let (ciphertext, randomness) = tally::encrypt_vote(&keys.election_pubkey, 1).unwrap();
+    let inflated = tally::Ballot {
+        ciphertext,
+        proof: tally::prove_ballot(
+            &keys.election_pubkey,
+            &domain,
+            &ciphertext,
+            &randomness,
+            &Weight::public(3),
+            true,
+        )
+        .unwrap(),
+    };
+    assert_eq!(
+        verify(&inflated, 3).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
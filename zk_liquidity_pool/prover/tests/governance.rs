@@ -0,0 +1,75 @@
+use anchor_lang::AnchorSerialize;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::{
+    Governance, Proposal, ProposalPayload, ProposalStatus, RewardTier, RewardTiers, VoteWeighting,
+    MAX_REWARD_TIERS,
+};
+
+mod common;
+
+use common::proposal;
+
+/// Adds decrypted votes, as `finalize_proposal` records them.
+fn tally(proposal: &mut Proposal, votes_for: u64, votes_against: u64) {
+    proposal.votes_for += votes_for;
+    proposal.votes_against += votes_against;
+}
+
+#[test]
+fn passes_only_with_quorum_and_threshold() {
+    // Simple majority (50%) with a quorum of four votes.
+    let mut short_of_quorum = proposal(4, 5_000);
+    tally(&mut short_of_quorum, 3, 0);
+    assert_eq!(short_of_quorum.outcome(), ProposalStatus::Rejected);
+
+    let mut tied = proposal(4, 5_000);
+    tally(&mut tied, 2, 2);
+    assert_eq!(tied.outcome(), ProposalStatus::Passed);
+
+    let mut below_threshold = proposal(4, 5_001);
+    tally(&mut below_threshold, 2, 2);
+    assert_eq!(below_threshold.outcome(), ProposalStatus::Rejected);
+
+    // Two-thirds supermajority.
+    let mut supermajority = proposal(3, 6_667);
+    tally(&mut supermajority, 2, 1);
+    assert_eq!(supermajority.outcome(), ProposalStatus::Rejected);
+    tally(&mut supermajority, 1, 0);
+    assert_eq!(supermajority.outcome(), ProposalStatus::Passed);
+}
+
+#[test]
+fn zero_quorum_still_needs_a_vote() {
+    assert_eq!(proposal(0, 1).outcome(), ProposalStatus::Rejected);
+}
+
+#[test]
+fn account_sizes_match_their_serialized_layout() {
+    let mut proposal = proposal(1, 1);
+    proposal.payload = ProposalPayload::UpdateRewardTiers {
+        reward_tiers: RewardTiers {
+            tiers: vec![
+                RewardTier {
+                    min_stake_seconds: 0,
+                    multiplier_bps: 10_000,
+                };
+                MAX_REWARD_TIERS
+            ],
+        },
+    };
+    assert_eq!(proposal.try_to_vec().unwrap().len(), Proposal::LEN);
+
+    let governance = Governance {
+        authority: Pubkey::new_unique(),
+        pool: Pubkey::new_unique(),
+        quorum: 1,
+        threshold_bps: 5_000,
+        voting_period_slots: 1_000,
+        vote_weighting: VoteWeighting::Stake {
+            stake_per_vote: 1_000,
+        },
+        proposal_count: 0,
+        bump: 255,
+    };
+    assert_eq!(governance.try_to_vec().unwrap().len(), Governance::LEN);
+}
@@ -0,0 +1,118 @@
+use ark_bn254::Fr;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{Circuit, RegisteredVerifyingKey, VerifyingKeyRegistry};
+use zk_liquidity_pool_prover::groth16::*;
+
+struct Fixture {
+    registry: VerifyingKeyRegistry,
+    pool: [u8; 32],
+    total_commitment: [u8; 32],
+    domain: [u8; 32],
+    proof: Vec<u8>,
+}
+
+impl Fixture {
+    fn verify(&self, circuit: Circuit, proof: &[u8], inputs: &[[u8; 32]]) -> bool {
+        // Frame the proof for exactly these inputs so only the pairing check can reject it.
+        let envelope = envelope(circuit, &self.domain, inputs, proof.to_vec());
+        zk_utils::verify_groth16(&self.registry, circuit, &envelope, &self.domain, inputs).is_ok()
+    }
+}
+
+/// Registers a reward-circuit key and proves a 6 × 7 = 42 reward for a random pool.
+fn reward_fixture() -> Fixture {
+    let mut rng = StdRng::seed_from_u64(7);
+    let pool_key = Pubkey::new_unique();
+    let pool = zk_utils::hash_to_field(&[pool_key.as_ref()]);
+    let total_commitment = zk_utils::hash_to_field(&[&[5u8; 32]]);
+    let circuit = RewardCircuit {
+        pool: bytes_to_fr(&pool),
+        reward: Fr::from(42u64),
+        total_commitment: bytes_to_fr(&total_commitment),
+        balance: Fr::from(6u64),
+        acc_delta: Fr::from(7u64),
+    };
+    let (pk, vk) = setup(circuit, &mut rng).unwrap();
+    let proof = prove(&pk, circuit, &mut rng).unwrap();
+
+    let registry = VerifyingKeyRegistry {
+        pool: pool_key,
+        bump: 255,
+        keys: vec![RegisteredVerifyingKey {
+            circuit: Circuit::Reward,
+            version: 1,
+            key: verifying_key(&vk),
+        }],
+    };
+    Fixture {
+        registry,
+        pool,
+        total_commitment,
+        domain: zk_utils::domain_separator("distribute_rewards", &pool_key),
+        proof,
+    }
+}
+
+#[test]
+fn accepts_a_valid_reward_proof() {
+    let fixture = reward_fixture();
+    assert!(fixture.verify(
+        Circuit::Reward,
+        &fixture.proof,
+        &[
+            fixture.pool,
+            zk_utils::u64_to_field(42),
+            fixture.total_commitment
+        ],
+    ));
+}
+
+#[test]
+fn rejects_wrong_public_inputs() {
+    let fixture = reward_fixture();
+    let verify = |inputs: &[[u8; 32]]| fixture.verify(Circuit::Reward, &fixture.proof, inputs);
+
+    let reward = zk_utils::u64_to_field(42);
+    let total = fixture.total_commitment;
+    assert!(!verify(&[fixture.pool, zk_utils::u64_to_field(43), total]));
+    let other_pool = zk_utils::hash_to_field(&[Pubkey::new_unique().as_ref()]);
+    assert!(!verify(&[other_pool, reward, total]));
+    // A proof made before the pool's total changed does not verify against the new total.
+    let other_total = zk_utils::hash_to_field(&[&[6u8; 32]]);
+    assert!(!verify(&[fixture.pool, reward, other_total]));
+    assert!(!verify(&[fixture.pool, reward]));
+    assert!(!verify(&[
+        fixture.pool,
+        zk_utils::BN254_SCALAR_MODULUS,
+        total
+    ]));
+}
+
+#[test]
+fn rejects_tampered_proofs_and_unknown_circuits() {
+    let fixture = reward_fixture();
+    let inputs = [
+        fixture.pool,
+        zk_utils::u64_to_field(42),
+        fixture.total_commitment,
+    ];
+
+    let mut tampered = fixture.proof.clone();
+    tampered[200] ^= 1;
+    assert!(!fixture.verify(Circuit::Reward, &tampered, &inputs));
+    assert!(!fixture.verify(Circuit::Reward, &fixture.proof[..128], &inputs));
+    assert!(!fixture.verify(Circuit::Vote, &fixture.proof, &inputs));
+}
+
+#[test]
+fn negation_is_an_involution() {
+    let fixture = reward_fixture();
+    let mut point = [0u8; 64];
+    point.copy_from_slice(&fixture.proof[..64]);
+    assert_ne!(zk_utils::negate_g1(&point), point);
+    assert_eq!(zk_utils::negate_g1(&zk_utils::negate_g1(&point)), point);
+    assert_eq!(zk_utils::negate_g1(&[0u8; 64]), [0u8; 64]);
+}
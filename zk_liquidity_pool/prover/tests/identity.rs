@@ -0,0 +1,190 @@
+use anchor_lang::Discriminator;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{
+    spend_nullifier, Circuit, ErrorCode, IdentityRegistry, Nullifier, RegisteredVerifyingKey,
+    VerifyingKeyRegistry, IDENTITY_ROOT_HISTORY, IDENTITY_TREE_DEPTH,
+};
+use zk_liquidity_pool_prover::groth16::{self, IdentityCircuit};
+use zk_liquidity_pool_prover::identity::IdentityTree;
+
+fn commitment(seed: u64) -> [u8; 32] {
+    zk_utils::hash_to_field(&[&seed.to_le_bytes()])
+}
+
+fn empty_registry() -> IdentityRegistry {
+    let mut registry = IdentityRegistry::default();
+    registry.initialize_tree().unwrap();
+    registry
+}
+
+#[test]
+fn on_chain_tree_matches_the_off_chain_mirror() {
+    let mut registry = empty_registry();
+    let mut tree = IdentityTree::new();
+    assert_eq!(registry.root(), tree.root());
+
+    for seed in 0..5 {
+        let leaf = commitment(seed);
+        assert_eq!(registry.append(leaf).unwrap(), tree.append(leaf));
+        assert_eq!(registry.root(), tree.root());
+        assert!(registry.is_known_root(&tree.root()));
+    }
+    for index in 0..5 {
+        let path = tree.path(index).unwrap();
+        assert_eq!(path.siblings.len(), IDENTITY_TREE_DEPTH);
+        assert_eq!(path.root(&commitment(index)), tree.root());
+        assert_ne!(path.root(&commitment(index + 1)), tree.root());
+    }
+    assert!(tree.path(5).is_none());
+}
+
+#[test]
+fn root_history_is_a_ring_buffer() {
+    let mut registry = empty_registry();
+    let empty_root = registry.root();
+    assert!(registry.is_known_root(&empty_root));
+    assert!(!registry.is_known_root(&[0u8; 32]));
+
+    registry.append(commitment(0)).unwrap();
+    let first_root = registry.root();
+    for seed in 1..IDENTITY_ROOT_HISTORY as u64 {
+        registry.append(commitment(seed)).unwrap();
+    }
+    // The empty root has been overwritten; the first member root is the oldest one left.
+    assert!(!registry.is_known_root(&empty_root));
+    assert!(registry.is_known_root(&first_root));
+    registry.append(commitment(99)).unwrap();
+    assert!(!registry.is_known_root(&first_root));
+}
+
+#[test]
+fn rejects_non_field_commitments_and_full_trees() {
+    let mut registry = empty_registry();
+    assert_eq!(
+        registry.append(zk_utils::BN254_SCALAR_MODULUS).unwrap_err(),
+        ErrorCode::InvalidIdentityCommitment.into()
+    );
+    registry.next_index = 1 << IDENTITY_TREE_DEPTH;
+    assert_eq!(
+        registry.append(commitment(0)).unwrap_err(),
+        ErrorCode::IdentityTreeFull.into()
+    );
+}
+
+#[test]
+fn identity_proofs_must_reference_a_known_root() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut registry = empty_registry();
+    registry.append(commitment(1)).unwrap();
+    let root = registry.root();
+
+    let proposal = Pubkey::new_unique();
+    let domain = zk_utils::domain_separator("cast_vote", &proposal);
+    let nullifier_hash = commitment(42);
+    let vote = [1u8];
+    let inputs = zk_utils::identity_public_inputs(&domain, root, nullifier_hash, &vote);
+    let (pk, vk) = groth16::setup(IdentityCircuit::new(&inputs), &mut rng).unwrap();
+    let proof = groth16::prove(&pk, IdentityCircuit::new(&inputs), &mut rng).unwrap();
+    let envelope = groth16::envelope(Circuit::Identity, &domain, &inputs, proof);
+
+    let vk_registry = VerifyingKeyRegistry {
+        pool: registry.pool,
+        bump: 255,
+        keys: vec![RegisteredVerifyingKey {
+            circuit: Circuit::Identity,
+            version: 1,
+            key: groth16::verifying_key(&vk),
+        }],
+    };
+    let verify = |root: [u8; 32], nullifier_hash: [u8; 32], signal: &[u8]| {
+        zk_utils::verify_identity_proof(
+            &registry,
+            &vk_registry,
+            &envelope,
+            &domain,
+            root,
+            nullifier_hash,
+            signal,
+        )
+    };
+
+    verify(root, nullifier_hash, &vote).unwrap();
+    assert_eq!(
+        verify(commitment(7), nullifier_hash, &vote).unwrap_err(),
+        ErrorCode::UnknownIdentityRoot.into()
+    );
+    // The proof authorizes exactly one signal under exactly one nullifier.
+    assert_eq!(
+        verify(root, nullifier_hash, &[0u8]).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+    assert_eq!(
+        verify(root, commitment(43), &vote).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+}
+
+#[test]
+fn spent_nullifiers_are_rejected() {
+    let proposal = Pubkey::new_unique();
+    let nullifier_hash = commitment(42);
+    let (address, bump) = Pubkey::find_program_address(
+        &[b"nullifier", proposal.as_ref(), nullifier_hash.as_ref()],
+        &zk_liquidity_pool::ID,
+    );
+    let payer_key = Pubkey::new_unique();
+    let system_key = solana_program::system_program::ID;
+    let (mut nullifier_lamports, mut payer_lamports, mut system_lamports) = (1, 1, 1);
+    let mut nullifier_data = Nullifier::DISCRIMINATOR.to_vec();
+    let (mut payer_data, mut system_data) = (vec![], vec![]);
+    let nullifier = AccountInfo::new(
+        &address,
+        false,
+        true,
+        &mut nullifier_lamports,
+        &mut nullifier_data,
+        &zk_liquidity_pool::ID,
+        false,
+        0,
+    );
+    let payer = AccountInfo::new(
+        &payer_key,
+        true,
+        true,
+        &mut payer_lamports,
+        &mut payer_data,
+        &system_key,
+        false,
+        0,
+    );
+    let system_program = AccountInfo::new(
+        &system_key,
+        false,
+        false,
+        &mut system_lamports,
+        &mut system_data,
+        &system_key,
+        true,
+        0,
+    );
+
+    assert_eq!(
+        spend_nullifier(
+            &nullifier,
+            &payer,
+            &system_program,
+            &[
+                b"nullifier",
+                proposal.as_ref(),
+                nullifier_hash.as_ref(),
+                &[bump],
+            ],
+        )
+        .unwrap_err(),
+        ErrorCode::NullifierAlreadyUsed.into()
+    );
+}
@@ -0,0 +1,553 @@
+use anchor_lang::AnchorSerialize;
+use curve25519_dalek::scalar::Scalar;
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::curve25519::ristretto::{
+    add_ristretto, multiply_ristretto, PodRistrettoPoint,
+};
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::encryption::pedersen::{Pedersen, PedersenOpening};
+use solana_zk_token_sdk::instruction::{BatchedRangeProofContext, ProofType};
+use solana_zk_token_sdk::zk_token_proof_program;
+use solana_zk_token_sdk::zk_token_proof_state::ProofContextState;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{
+    AssetVault, ErrorCode, LiquidityPool, LockupPolicy, RewardTier, RewardTiers, UserStake,
+    BASE_REWARD_MULTIPLIER_BPS, MAX_REWARD_RATE_HISTORY, MAX_REWARD_TIERS,
+};
+use zk_liquidity_pool_prover::*;
+
+mod common;
+
+fn pool(token_mint: Pubkey, bump: u8) -> LiquidityPool {
+    LiquidityPool {
+        authority: Pubkey::new_unique(),
+        token_mint,
+        total_commitment: [0u8; 32],
+        auditor_elgamal_pubkey: None,
+        auditor_total: [0u8; 64],
+        lockup: LockupPolicy::default(),
+        treasury: Pubkey::new_unique(),
+        reward_tiers: RewardTiers::default(),
+        reward_rate: 0,
+        acc_reward_per_share: 0,
+        last_reward_timestamp: 0,
+        reward_rate_history: Vec::new(),
+        bump,
+    }
+}
+
+#[test]
+fn signer_seeds_derive_the_pool_address() {
+    let token_mint = Pubkey::new_unique();
+    let (address, bump) = LiquidityPool::find_address(&token_mint);
+    assert_eq!(
+        Pubkey::find_program_address(&[b"pool", token_mint.as_ref()], &zk_liquidity_pool::ID),
+        (address, bump)
+    );
+
+    let pool = pool(token_mint, bump);
+    assert_eq!(
+        Pubkey::create_program_address(&pool.signer_seeds(), &zk_liquidity_pool::ID).unwrap(),
+        address
+    );
+
+    // Seeding with the pool's own address, as withdraw used to, signs for a different account.
+    let by_pool_key = Pubkey::create_program_address(
+        &[b"pool", address.as_ref(), &[bump]],
+        &zk_liquidity_pool::ID,
+    );
+    assert_ne!(by_pool_key.ok(), Some(address));
+}
+
+#[test]
+fn signer_seeds_need_the_canonical_bump() {
+    let token_mint = Pubkey::new_unique();
+    let (address, bump) = LiquidityPool::find_address(&token_mint);
+    for other in (0..bump).rev().take(8) {
+        let pool = pool(token_mint, other);
+        let derived = Pubkey::create_program_address(&pool.signer_seeds(), &zk_liquidity_pool::ID);
+        assert_ne!(derived.ok(), Some(address));
+    }
+}
+
+/// Runs the checks `withdraw` makes on the new balance, with the range proof read from a
+/// context-state account.
+fn verify_withdrawal(
+    owner: &ElGamalKeypair,
+    domain: &[u8; 32],
+    old_balance: &[u8; 64],
+    amount: u64,
+    debit: &DebitProof,
+) -> anchor_lang::Result<()> {
+    zk_utils::verify_confidential_balance(
+        &debit.proof,
+        domain,
+        amount,
+        BalanceUpdate::Debit,
+        owner.pubkey().to_bytes(),
+        *old_balance,
+        debit.new_balance,
+    )?;
+    let mut data = ProofContextState::<BatchedRangeProofContext>::encode(
+        &Pubkey::new_unique(),
+        ProofType::BatchedRangeProofU64,
+        debit.range_proof.context(),
+    );
+    let key = Pubkey::new_unique();
+    let proof_program = zk_token_proof_program::id();
+    let mut lamports = 0;
+    let proof_account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &proof_program,
+        false,
+        0,
+    );
+    zk_utils::verify_bounded_ciphertexts(
+        &proof_account,
+        &debit.equality_proof,
+        &range_proof_envelope(domain, 0, &[debit.balance_commitment]),
+        domain,
+        &owner.pubkey().to_bytes(),
+        &[debit.new_balance],
+        &[debit.balance_commitment],
+    )
+}
+
+/// A position staked into `pool`, with the opening of its balance commitment.
+struct Position {
+    owner: ElGamalKeypair,
+    value: u64,
+    balance: [u8; 64],
+    commitment: [u8; 32],
+    opening: PedersenOpening,
+    reward_weight: u64,
+}
+
+fn stake_positions(
+    pool: &mut LiquidityPool,
+    auditor: &ElGamalKeypair,
+    stakes: &[(u64, u64)],
+) -> Vec<Position> {
+    let stake_domain = zk_utils::domain_separator("stake", &Pubkey::new_unique());
+    let mut positions = Vec::new();
+    for &(amount, reward_weight) in stakes {
+        let owner = ElGamalKeypair::new_rand();
+        let staked = prove_credit(&owner, &stake_domain, &ZERO_BALANCE, 0, amount).unwrap();
+        let auditor_amount =
+            prove_auditor_amount(&auditor.pubkey().to_bytes(), &stake_domain, amount).unwrap();
+        let ciphertext = pool
+            .verify_auditor_amount(Some(&auditor_amount), &stake_domain, amount)
+            .unwrap();
+        pool.record_balance_change(
+            &[0u8; 32],
+            &staked.balance_commitment,
+            BalanceUpdate::Credit,
+            ciphertext.as_ref(),
+            reward_weight,
+        )
+        .unwrap();
+        positions.push(Position {
+            owner,
+            value: amount,
+            balance: staked.new_balance,
+            commitment: staked.balance_commitment,
+            opening: staked.opening,
+            reward_weight,
+        });
+    }
+    positions
+}
+
+/// Debits `amount` from `position` through the `withdraw` checks, recording it in `pool` if
+/// they pass.
+fn withdraw(
+    pool: &mut LiquidityPool,
+    auditor: &ElGamalKeypair,
+    position: &mut Position,
+    domain: &[u8; 32],
+    amount: u64,
+    debit: DebitProof,
+) -> anchor_lang::Result<()> {
+    verify_withdrawal(&position.owner, domain, &position.balance, amount, &debit)?;
+    let auditor_amount =
+        prove_auditor_amount(&auditor.pubkey().to_bytes(), domain, amount).unwrap();
+    let ciphertext = pool.verify_auditor_amount(Some(&auditor_amount), domain, amount)?;
+    pool.record_balance_change(
+        &position.commitment,
+        &debit.balance_commitment,
+        BalanceUpdate::Debit,
+        ciphertext.as_ref(),
+        position.reward_weight,
+    )?;
+    position.value -= amount;
+    position.balance = debit.new_balance;
+    position.commitment = debit.balance_commitment;
+    position.opening = debit.opening;
+    Ok(())
+}
+
+/// The commitment the positions' weighted balances add up to, from their openings.
+fn weighted_total(positions: &[Position]) -> [u8; 32] {
+    let (value, opening) = positions.iter().fold(
+        (0, PedersenOpening::new(Scalar::zero())),
+        |(value, opening), position| {
+            let weight = Scalar::from(position.reward_weight);
+            (
+                value + position.value * position.reward_weight,
+                &opening + &(&position.opening * weight),
+            )
+        },
+    );
+    Pedersen::with(value, &opening).to_bytes()
+}
+
+#[test]
+fn pool_totals_follow_position_balances() {
+    let auditor = ElGamalKeypair::new_rand();
+    let mut pool = pool(Pubkey::new_unique(), 255);
+    pool.auditor_elgamal_pubkey = Some(auditor.pubkey().to_bytes());
+    let vault = AssetVault {
+        pool: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        token_account: Pubkey::new_unique(),
+        reward_weight: 3,
+        bump: 255,
+    };
+    assert_eq!(vault.try_to_vec().unwrap().len(), AssetVault::LEN);
+
+    // The second position is in a mint that counts three times towards the total.
+    let mut positions = stake_positions(&mut pool, &auditor, &[(300, 1), (200, 3)]);
+    let withdraw_domain = zk_utils::domain_separator("withdraw", &Pubkey::new_unique());
+    let position = &mut positions[0];
+    let debit = prove_debit(
+        &position.owner,
+        &withdraw_domain,
+        &position.balance,
+        position.value,
+        100,
+    )
+    .unwrap();
+    withdraw(&mut pool, &auditor, position, &withdraw_domain, 100, debit).unwrap();
+
+    // The commitment total is the weighted sum of the positions' commitments...
+    let weighted = multiply_ristretto(
+        &zk_utils::amount_scalar(3),
+        &PodRistrettoPoint(positions[1].commitment),
+    );
+    let sum = add_ristretto(
+        &PodRistrettoPoint(positions[0].commitment),
+        &weighted.unwrap(),
+    )
+    .unwrap();
+    assert_eq!(pool.total_commitment, sum.0);
+    assert_eq!(pool.total_commitment, weighted_total(&positions));
+    // ...and the auditor can decrypt the weighted total.
+    assert_eq!(
+        decrypt_balance(&auditor, &pool.auditor_total),
+        Some(200 + 3 * 200)
+    );
+}
+
+#[test]
+fn forged_debits_leave_the_totals_matching_the_deposits() {
+    let auditor = ElGamalKeypair::new_rand();
+    let mut pool = pool(Pubkey::new_unique(), 255);
+    pool.auditor_elgamal_pubkey = Some(auditor.pubkey().to_bytes());
+    let mut positions = stake_positions(&mut pool, &auditor, &[(10, 1), (5_000, 1)]);
+    let withdraw_domain = zk_utils::domain_separator("withdraw", &Pubkey::new_unique());
+
+    // The first position tries to take 1,000 out of its 10 by moving only its decrypt handle,
+    // range-proving its old commitment as the new balance's.
+    let position = &mut positions[0];
+    let target = zk_utils::subtract_public_value(&position.balance, 1_000).unwrap();
+    let new_balance = common::forge_ciphertext(&position.owner, &target, &position.commitment);
+    let proof = prove_zero_delta(
+        &position.owner,
+        &withdraw_domain,
+        &position.balance,
+        &new_balance,
+        1_000,
+        BalanceUpdate::Debit,
+    )
+    .unwrap();
+    let equality = prove_equality(&position.owner, &withdraw_domain, &[(new_balance, 10)]).unwrap();
+    let forged = DebitProof {
+        new_balance,
+        proof,
+        balance_commitment: equality.commitments[0],
+        range_proof: prove_range(&[(10, &equality.openings[0])]).unwrap(),
+        opening: PedersenOpening::new_rand(),
+        equality_proof: equality.proof,
+    };
+    assert_eq!(
+        withdraw(
+            &mut pool,
+            &auditor,
+            position,
+            &withdraw_domain,
+            1_000,
+            forged
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+
+    // An honest withdrawal of all of it goes through.
+    let debit = prove_debit(&position.owner, &withdraw_domain, &position.balance, 10, 10).unwrap();
+    withdraw(&mut pool, &auditor, position, &withdraw_domain, 10, debit).unwrap();
+
+    // Both totals still match the 5,000 staked and not withdrawn.
+    assert_eq!(decrypt_balance(&auditor, &pool.auditor_total), Some(5_000));
+    assert_eq!(pool.total_commitment, weighted_total(&positions));
+    let opening = &positions[0].opening + &positions[1].opening;
+    assert_eq!(
+        pool.total_commitment,
+        Pedersen::with(5_000_u64, &opening).to_bytes()
+    );
+}
+
+#[test]
+fn auditor_amounts_must_match_the_pool_and_the_amount() {
+    let auditor = ElGamalKeypair::new_rand();
+    let auditor_pubkey = auditor.pubkey().to_bytes();
+    let domain = zk_utils::domain_separator("stake", &Pubkey::new_unique());
+    let auditor_amount = prove_auditor_amount(&auditor_pubkey, &domain, 50).unwrap();
+
+    let unaudited = pool(Pubkey::new_unique(), 255);
+    assert_eq!(unaudited.verify_auditor_amount(None, &domain, 50), Ok(None));
+    assert_eq!(
+        unaudited
+            .verify_auditor_amount(Some(&auditor_amount), &domain, 50)
+            .unwrap_err(),
+        ErrorCode::AuditorAmountMismatch.into()
+    );
+
+    let mut audited = pool(Pubkey::new_unique(), 255);
+    audited.auditor_elgamal_pubkey = Some(auditor_pubkey);
+    assert_eq!(
+        audited
+            .verify_auditor_amount(None, &domain, 50)
+            .unwrap_err(),
+        ErrorCode::AuditorAmountMismatch.into()
+    );
+    assert_eq!(
+        audited
+            .verify_auditor_amount(Some(&auditor_amount), &domain, 51)
+            .unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    // Re-framing the proof for a larger amount leaves one that does not verify.
+    let mut reframed = auditor_amount.clone();
+    reframed.proof.public_input_hash =
+        zk_utils::auditor_amount_input_hash(&auditor_pubkey, &reframed.ciphertext, 51);
+    assert_eq!(
+        audited
+            .verify_auditor_amount(Some(&reframed), &domain, 51)
+            .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+
+    // So does an encryption to a key other than the auditor's.
+    let other =
+        prove_auditor_amount(&ElGamalKeypair::new_rand().pubkey().to_bytes(), &domain, 50).unwrap();
+    assert_eq!(
+        audited
+            .verify_auditor_amount(Some(&other), &domain, 50)
+            .unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+}
+
+fn position(stake_timestamp: i64) -> UserStake {
+    UserStake {
+        confidential_balance: [0u8; 64],
+        encrypted_data: Vec::new(),
+        stake_timestamp,
+        asset_mint: Pubkey::new_unique(),
+        elgamal_pubkey: [0u8; 32],
+        balance_updated_slot: 0,
+        prior_balance: [0u8; 64],
+        prior_balance_slot: 0,
+        unbonding_amount: 0,
+        unbonding_penalty: 0,
+        unbond_timestamp: 0,
+        reward_debt: [0u8; 64],
+        reward_timestamp: 0,
+        reward_acc: 0,
+        balance_commitment: [0u8; 32],
+    }
+}
+
+#[test]
+fn lockup_policies_are_validated() {
+    let policy = LockupPolicy {
+        min_lock_seconds: 60,
+        unbonding_seconds: 3_600,
+        early_withdraw_penalty_bps: 10_000,
+    };
+    policy.validate().unwrap();
+    assert_eq!(policy.try_to_vec().unwrap().len(), LockupPolicy::LEN);
+    for invalid in [
+        LockupPolicy {
+            early_withdraw_penalty_bps: 10_001,
+            ..policy
+        },
+        LockupPolicy {
+            min_lock_seconds: -1,
+            ..policy
+        },
+        LockupPolicy {
+            unbonding_seconds: -1,
+            ..policy
+        },
+    ] {
+        assert_eq!(
+            invalid.validate().unwrap_err(),
+            ErrorCode::InvalidLockupPolicy.into()
+        );
+    }
+}
+
+#[test]
+fn early_withdrawals_pay_the_penalty_or_are_rejected() {
+    let strict = LockupPolicy {
+        min_lock_seconds: 60,
+        ..LockupPolicy::default()
+    };
+    let mut stake = position(1_000);
+    assert_eq!(
+        stake.take_withdrawal(&strict, 500, 1_059).unwrap_err(),
+        ErrorCode::StakeDurationNotMet.into()
+    );
+    assert_eq!(stake.take_withdrawal(&strict, 500, 1_060).unwrap(), 0);
+
+    let penalised = LockupPolicy {
+        early_withdraw_penalty_bps: 250,
+        ..strict
+    };
+    assert_eq!(stake.take_withdrawal(&penalised, 500, 1_059).unwrap(), 12);
+    assert_eq!(stake.take_withdrawal(&penalised, 500, 1_060).unwrap(), 0);
+    assert_eq!(
+        stake.start_unbonding(&penalised, 500, 1_000).unwrap_err(),
+        ErrorCode::UnbondingNotEnabled.into()
+    );
+}
+
+#[test]
+fn unbonding_pools_pay_out_after_the_cooldown() {
+    let lockup = LockupPolicy {
+        min_lock_seconds: 60,
+        unbonding_seconds: 100,
+        early_withdraw_penalty_bps: 1_000,
+    };
+    let mut stake = position(1_000);
+    assert_eq!(
+        stake.take_withdrawal(&lockup, 500, 5_000).unwrap_err(),
+        ErrorCode::UnbondingRequired.into()
+    );
+
+    // The penalty is settled when unbonding starts, not when the withdrawal lands.
+    stake.start_unbonding(&lockup, 500, 1_030).unwrap();
+    assert_eq!(
+        stake.start_unbonding(&lockup, 100, 1_031).unwrap_err(),
+        ErrorCode::AlreadyUnbonding.into()
+    );
+    assert_eq!(
+        stake.take_withdrawal(&lockup, 500, 1_129).unwrap_err(),
+        ErrorCode::UnbondingNotComplete.into()
+    );
+    assert_eq!(
+        stake.take_withdrawal(&lockup, 400, 1_130).unwrap_err(),
+        ErrorCode::UnbondingRequired.into()
+    );
+    assert_eq!(stake.take_withdrawal(&lockup, 500, 1_130).unwrap(), 50);
+
+    // The request is used up by the withdrawal.
+    assert_eq!(
+        stake.take_withdrawal(&lockup, 500, 1_200).unwrap_err(),
+        ErrorCode::UnbondingRequired.into()
+    );
+    stake.start_unbonding(&lockup, 200, 1_200).unwrap();
+    assert_eq!(stake.take_withdrawal(&lockup, 200, 1_300).unwrap(), 0);
+}
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn tiers(tiers: &[(i64, u16)]) -> RewardTiers {
+    RewardTiers {
+        tiers: tiers
+            .iter()
+            .map(|&(min_stake_seconds, multiplier_bps)| RewardTier {
+                min_stake_seconds,
+                multiplier_bps,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn reward_multipliers_follow_the_tier_boundaries() {
+    let tiers = tiers(&[(30 * DAY, 10_000), (90 * DAY, 12_500), (180 * DAY, 15_000)]);
+    tiers.validate().unwrap();
+    let staked_at = 1_000;
+    for (staked_for, multiplier) in [
+        (0, BASE_REWARD_MULTIPLIER_BPS),
+        (30 * DAY, 10_000),
+        (90 * DAY - 1, 10_000),
+        (90 * DAY, 12_500),
+        (180 * DAY - 1, 12_500),
+        (180 * DAY, 15_000),
+        (1_000 * DAY, 15_000),
+    ] {
+        assert_eq!(
+            tiers.multiplier_bps(staked_at, staked_at + staked_for),
+            multiplier
+        );
+    }
+    // Staking again restarts the clock, and a clock behind the stake counts as no time.
+    assert_eq!(tiers.multiplier_bps(staked_at, staked_at - DAY), 10_000);
+    assert_eq!(
+        RewardTiers::default().multiplier_bps(0, 1_000 * DAY),
+        10_000
+    );
+}
+
+#[test]
+fn reward_tiers_are_validated() {
+    let mut largest = tiers(&[(0, 10_000); MAX_REWARD_TIERS]);
+    for (i, tier) in largest.tiers.iter_mut().enumerate() {
+        tier.min_stake_seconds = i as i64 * DAY;
+    }
+    largest.validate().unwrap();
+    let mut pool = pool(Pubkey::new_unique(), 255);
+    pool.auditor_elgamal_pubkey = Some([1u8; 32]);
+    pool.reward_tiers = largest.clone();
+    for day in 0..MAX_REWARD_RATE_HISTORY as i64 {
+        pool.set_reward_rate(1, day * DAY);
+    }
+    assert_eq!(pool.try_to_vec().unwrap().len(), LiquidityPool::LEN);
+
+    largest.tiers.push(RewardTier {
+        min_stake_seconds: 365 * DAY,
+        multiplier_bps: 20_000,
+    });
+    for invalid in [
+        largest,
+        tiers(&[(90 * DAY, 12_500), (30 * DAY, 15_000)]),
+        tiers(&[(30 * DAY, 12_500), (30 * DAY, 15_000)]),
+        tiers(&[(30 * DAY, 15_000), (90 * DAY, 12_500)]),
+        tiers(&[(30 * DAY, 9_000)]),
+        tiers(&[(-1, 12_500)]),
+    ] {
+        assert_eq!(
+            invalid.validate().unwrap_err(),
+            ErrorCode::InvalidRewardTiers.into()
+        );
+    }
+}
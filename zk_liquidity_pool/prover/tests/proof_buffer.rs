@@ -0,0 +1,467 @@
+use std::collections::BTreeSet;
+
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::{
+    AccountSerialize, Accounts, AnchorDeserialize, AnchorSerialize, InstructionData,
+};
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::SUCCESS;
+use solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
+use solana_program::system_program;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{
+    instruction, CastVote, CastVoteBumps, DelegateVotes, DelegateVotesBumps, DelegateWeight,
+    Delegation, ErrorCode, Governance, IdentityRegistry, ProofBuffer, ProofEnvelope, ProofSource,
+    Proposal, RevokeDelegation, RevokeDelegationBumps, VerifyingKeyRegistry, VoteWeighting,
+    WriteProofChunk, WriteProofChunkBumps,
+};
+use zk_liquidity_pool_prover::*;
+
+mod common;
+
+use common::{account, anchor_data, proposal};
+
+const CHUNK: usize = 64;
+
+/// An opened, empty buffer committed to `contents`.
+fn open(owner: Pubkey, contents: &[u8]) -> ProofBuffer {
+    ProofBuffer {
+        owner,
+        buffer_id: 7,
+        hash: hashv(&[contents]).to_bytes(),
+        written: 0,
+        sealed: false,
+        bump: 255,
+        data: vec![0u8; contents.len()],
+    }
+}
+
+fn upload(buffer: &mut ProofBuffer, contents: &[u8]) {
+    for (i, chunk) in contents.chunks(CHUNK).enumerate() {
+        buffer.write((i * CHUNK) as u32, chunk).unwrap();
+    }
+}
+
+#[test]
+fn a_buffered_proof_verifies_like_an_inline_one() {
+    let keypair = ElGamalKeypair::new_rand();
+    let domain = zk_utils::domain_separator("withdraw", &Pubkey::new_unique());
+    let old_balance = encrypt_balance(&keypair, 900);
+    let debit = prove_debit(&keypair, &domain, &old_balance, 900, 300).unwrap();
+    let contents = debit.proof.try_to_vec().unwrap();
+    assert!(contents.len() > CHUNK);
+
+    let mut buffer = open(Pubkey::new_unique(), &contents);
+    upload(&mut buffer, &contents);
+    assert!(buffer.sealed);
+
+    let address = Pubkey::new_unique();
+    let accounts = [
+        account(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Vec::new(),
+            false,
+            false,
+        ),
+        account(
+            address,
+            zk_liquidity_pool::ID,
+            anchor_data(&buffer),
+            false,
+            false,
+        ),
+    ];
+    let source = ProofSource::Buffer(address);
+    let envelope = source.resolve(&accounts).unwrap();
+    assert_eq!(*envelope, debit.proof);
+    zk_utils::verify_confidential_balance(
+        &envelope,
+        &domain,
+        300,
+        BalanceUpdate::Debit,
+        keypair.pubkey().to_bytes(),
+        old_balance,
+        debit.new_balance,
+    )
+    .unwrap();
+
+    let inline = ProofSource::Inline(debit.proof.clone());
+    assert_eq!(*inline.resolve(&[]).unwrap(), debit.proof);
+    assert_eq!(
+        ProofSource::Buffer(Pubkey::new_unique())
+            .resolve(&accounts)
+            .unwrap_err(),
+        ErrorCode::ProofBufferMissing.into()
+    );
+    // A look-alike account owned by another program is not a buffer.
+    let forged = [account(
+        address,
+        Pubkey::new_unique(),
+        anchor_data(&buffer),
+        false,
+        false,
+    )];
+    assert_eq!(
+        ProofSource::Buffer(address).resolve(&forged).unwrap_err(),
+        anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram.into()
+    );
+}
+
+#[test]
+fn only_a_complete_buffer_matching_its_hash_seals() {
+    let contents: Vec<u8> = (0..200u8).collect();
+    let mut buffer = open(Pubkey::new_unique(), &contents);
+    buffer.write(0, &contents[..CHUNK]).unwrap();
+    // Chunks go in order: a repeat or a gap is refused, as is writing past the end.
+    for (offset, chunk) in [
+        (0, &contents[..CHUNK]),
+        (2 * CHUNK, &contents[2 * CHUNK..3 * CHUNK]),
+        (CHUNK, &[0u8; 200][..]),
+        (CHUNK, &[][..]),
+    ] {
+        assert_eq!(
+            buffer.write(offset as u32, chunk).unwrap_err(),
+            ErrorCode::InvalidProofChunk.into()
+        );
+    }
+
+    let unsealed = [account(
+        Pubkey::new_unique(),
+        zk_liquidity_pool::ID,
+        anchor_data(&buffer),
+        false,
+        false,
+    )];
+    assert_eq!(
+        ProofSource::Buffer(*unsealed[0].key)
+            .resolve(&unsealed)
+            .unwrap_err(),
+        ErrorCode::ProofBufferNotSealed.into()
+    );
+
+    let mut tampered = buffer.clone();
+    let mut last = contents[CHUNK..].to_vec();
+    last[0] ^= 1;
+    assert_eq!(
+        tampered.write(CHUNK as u32, &last).unwrap_err(),
+        ErrorCode::ProofBufferHashMismatch.into()
+    );
+
+    buffer.write(CHUNK as u32, &contents[CHUNK..]).unwrap();
+    assert!(buffer.sealed);
+    assert_eq!(
+        buffer.write(contents.len() as u32, &[0]).unwrap_err(),
+        ErrorCode::ProofBufferSealed.into()
+    );
+    // Garbage that matches its hash seals, but is not an envelope.
+    assert_eq!(
+        buffer.envelope().unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+    assert_eq!(
+        8 + ProofBuffer::space(ProofBuffer::MAX_DATA_LEN),
+        solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+    );
+    assert_eq!(
+        anchor_data(&buffer).len(),
+        8 + ProofBuffer::space(contents.len())
+    );
+}
+
+#[test]
+fn only_the_owner_can_write_chunks() {
+    let owner = Pubkey::new_unique();
+    let buffer = open(owner, &[1, 2, 3]);
+    let (address, bump) = Pubkey::find_program_address(
+        &[ProofBuffer::SEED, owner.as_ref(), &7u64.to_le_bytes()],
+        &zk_liquidity_pool::ID,
+    );
+    let load = |signer: Pubkey| {
+        let buffer = ProofBuffer {
+            bump,
+            ..buffer.clone()
+        };
+        let accounts: &'static [AccountInfo<'static>] = Box::leak(Box::new([
+            account(
+                address,
+                zk_liquidity_pool::ID,
+                anchor_data(&buffer),
+                false,
+                false,
+            ),
+            account(signer, Pubkey::default(), Vec::new(), true, false),
+        ]));
+        let mut accounts = accounts;
+        WriteProofChunk::try_accounts(
+            &zk_liquidity_pool::ID,
+            &mut accounts,
+            &[],
+            &mut WriteProofChunkBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(drop)
+    };
+    load(owner).unwrap();
+    // The buffer's address is derived from its owner, so another signer fails the seeds check.
+    assert_eq!(
+        load(Pubkey::new_unique()).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+    );
+}
+
+/// Answers the rent sysvar syscall that `init_if_needed` makes, which has no default off-chain.
+struct RentSysvar;
+
+impl SyscallStubs for RentSysvar {
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+}
+
+fn program_account<T: AccountSerialize>(key: Pubkey, state: &T) -> AccountInfo<'static> {
+    account(key, zk_liquidity_pool::ID, anchor_data(state), false, false)
+}
+
+/// A program-owned account at `key` with `data` sized to `space` and enough lamports to be
+/// rent-exempt, as `init_if_needed` expects of an account it does not create.
+fn rent_exempt_account(key: Pubkey, mut data: Vec<u8>, space: usize) -> AccountInfo<'static> {
+    data.resize(space, 0);
+    let info = account(key, zk_liquidity_pool::ID, data, false, false);
+    **info.try_borrow_mut_lamports().unwrap() = Rent::default().minimum_balance(space);
+    info
+}
+
+/// A sealed buffer holding `envelope`.
+fn sealed_buffer(envelope: &ProofEnvelope) -> AccountInfo<'static> {
+    let contents = envelope.try_to_vec().unwrap();
+    let mut buffer = open(Pubkey::new_unique(), &contents);
+    upload(&mut buffer, &contents);
+    program_account(Pubkey::new_unique(), &buffer)
+}
+
+fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &zk_liquidity_pool::ID)
+}
+
+/// The governance, identity registry and verifying key registry of a fresh pool's
+/// identity-weighted governance.
+fn governance_accounts() -> (Pubkey, [AccountInfo<'static>; 3]) {
+    let pool = Pubkey::new_unique();
+    let find = |seed: &[u8]| pda(&[seed, pool.as_ref()]);
+    let (governance_key, bump) = find(b"governance");
+    let governance = Governance {
+        authority: Pubkey::new_unique(),
+        pool,
+        quorum: 1,
+        threshold_bps: 5_000,
+        voting_period_slots: 1_000,
+        vote_weighting: VoteWeighting::Identity,
+        proposal_count: 1,
+        bump,
+    };
+    let (registry_key, bump) = find(b"identity_registry");
+    let registry = IdentityRegistry {
+        pool,
+        bump,
+        ..IdentityRegistry::default()
+    };
+    let (vk_registry_key, bump) = find(b"vk_registry");
+    let vk_registry = VerifyingKeyRegistry {
+        pool,
+        bump,
+        keys: Vec::new(),
+    };
+    (
+        governance_key,
+        [
+            program_account(governance_key, &governance),
+            program_account(registry_key, &registry),
+            program_account(vk_registry_key, &vk_registry),
+        ],
+    )
+}
+
+/// Parses `accounts` as `T` with the arguments in `data`, as the program's dispatcher does, and
+/// returns the accounts left over for proof buffers.
+fn parse<T, B>(
+    accounts: Vec<AccountInfo<'static>>,
+    data: &[u8],
+) -> anchor_lang::Result<&'static [AccountInfo<'static>]>
+where
+    T: Accounts<'static, B>,
+    B: Default,
+{
+    let mut accounts: &'static [AccountInfo<'static>] = Box::leak(accounts.into_boxed_slice());
+    T::try_accounts(
+        &zk_liquidity_pool::ID,
+        &mut accounts,
+        &data[8..],
+        &mut B::default(),
+        &mut BTreeSet::new(),
+    )?;
+    Ok(accounts)
+}
+
+#[test]
+fn vote_instructions_take_buffered_proofs() {
+    set_syscall_stubs(Box::new(RentSysvar));
+    let keypair = ElGamalKeypair::new_rand();
+    let domain = zk_utils::domain_separator("withdraw", &Pubkey::new_unique());
+    let old_balance = encrypt_balance(&keypair, 900);
+    let envelope = prove_debit(&keypair, &domain, &old_balance, 900, 300)
+        .unwrap()
+        .proof;
+    let buffer = sealed_buffer(&envelope);
+    let buffered = ProofSource::Buffer(*buffer.key);
+    let (governance_key, [governance, identity_registry, vk_registry]) = governance_accounts();
+    let nullifier_hash = [7u8; 32];
+    let delegate = [9u8; 32];
+    let system_program = account(
+        system_program::ID,
+        Pubkey::default(),
+        Vec::new(),
+        false,
+        true,
+    );
+    let signer = account(
+        Pubkey::new_unique(),
+        system_program::ID,
+        Vec::new(),
+        true,
+        false,
+    );
+    let (delegation_key, delegation_bump) = pda(&[
+        b"delegation",
+        governance_key.as_ref(),
+        nullifier_hash.as_ref(),
+    ]);
+    let (weight_key, weight_bump) = pda(&[
+        b"delegate_weight",
+        governance_key.as_ref(),
+        delegate.as_ref(),
+    ]);
+    let delegation = rent_exempt_account(
+        delegation_key,
+        anchor_data(&Delegation {
+            governance: governance_key,
+            delegate,
+            delegated_slot: 10,
+            revoked_slot: 0,
+            active: true,
+            bump: delegation_bump,
+        }),
+        8 + Delegation::LEN,
+    );
+    let delegate_weight = rent_exempt_account(
+        weight_key,
+        anchor_data(&DelegateWeight {
+            governance: governance_key,
+            delegate,
+            checkpoints: Vec::new(),
+            bump: weight_bump,
+        }),
+        8 + DelegateWeight::LEN,
+    );
+
+    // Each instruction's arguments decode as they were encoded, and the accounts' seeds, which
+    // read the arguments after the proofs, check out with the buffer in place of the proof.
+    let data = instruction::DelegateVotes {
+        delegate,
+        zk_identity_proof: buffered.clone(),
+        identity_root: [1u8; 32],
+        nullifier_hash,
+    }
+    .data();
+    let args = instruction::DelegateVotes::try_from_slice(&data[8..]).unwrap();
+    assert_eq!(args.zk_identity_proof, buffered);
+    assert_eq!(args.nullifier_hash, nullifier_hash);
+    let remaining = parse::<DelegateVotes, DelegateVotesBumps>(
+        vec![
+            governance.clone(),
+            delegation.clone(),
+            delegate_weight.clone(),
+            identity_registry.clone(),
+            vk_registry.clone(),
+            signer.clone(),
+            system_program.clone(),
+            buffer.clone(),
+        ],
+        &data,
+    )
+    .unwrap();
+    assert_eq!(
+        *args.zk_identity_proof.resolve(remaining).unwrap(),
+        envelope
+    );
+
+    let data = instruction::RevokeDelegation {
+        zk_identity_proof: buffered.clone(),
+        identity_root: [1u8; 32],
+        nullifier_hash,
+    }
+    .data();
+    let args = instruction::RevokeDelegation::try_from_slice(&data[8..]).unwrap();
+    assert_eq!(args.zk_identity_proof, buffered);
+    let remaining = parse::<RevokeDelegation, RevokeDelegationBumps>(
+        vec![
+            governance.clone(),
+            delegation,
+            delegate_weight,
+            identity_registry.clone(),
+            vk_registry.clone(),
+            buffer.clone(),
+        ],
+        &data,
+    )
+    .unwrap();
+    assert_eq!(
+        *args.zk_identity_proof.resolve(remaining).unwrap(),
+        envelope
+    );
+
+    let proposal_key = Pubkey::new_unique();
+    let voter_seeds =
+        |seed: &[u8], parent: &Pubkey| pda(&[seed, parent.as_ref(), &nullifier_hash]).0;
+    let unchecked = |key: Pubkey| account(key, system_program::ID, Vec::new(), false, false);
+    let data = instruction::CastVote {
+        ballot: [3u8; 64],
+        ballot_proof: buffered.clone(),
+        zk_vote_proof: buffered.clone(),
+        identity_root: [1u8; 32],
+        nullifier_hash,
+    }
+    .data();
+    let args = instruction::CastVote::try_from_slice(&data[8..]).unwrap();
+    assert_eq!(args.ballot_proof, buffered);
+    assert_eq!(args.zk_vote_proof, buffered);
+    assert_eq!(args.nullifier_hash, nullifier_hash);
+    let remaining = parse::<CastVote, CastVoteBumps>(
+        vec![
+            governance,
+            program_account(
+                proposal_key,
+                &Proposal {
+                    governance: governance_key,
+                    ..proposal(1, 5_000)
+                },
+            ),
+            signer,
+            identity_registry,
+            vk_registry,
+            unchecked(voter_seeds(b"nullifier", &proposal_key)),
+            unchecked(voter_seeds(b"delegation", &governance_key)),
+            unchecked(voter_seeds(b"delegate_weight", &governance_key)),
+            system_program,
+            buffer,
+        ],
+        &data,
+    )
+    .unwrap();
+    assert_eq!(*args.ballot_proof.resolve(remaining).unwrap(), envelope);
+    assert_eq!(*args.zk_vote_proof.resolve(remaining).unwrap(), envelope);
+}
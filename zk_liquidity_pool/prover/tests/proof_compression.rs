@@ -0,0 +1,161 @@
+use std::time::Instant;
+
+use anchor_lang::AnchorSerialize;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate, MAX_INFLATED_PROOF_LEN};
+use zk_liquidity_pool::{Circuit, ErrorCode, ProofCompression, ProofEnvelope};
+use zk_liquidity_pool_prover::groth16::{self, RewardClaimCircuit};
+use zk_liquidity_pool_prover::*;
+
+struct Withdrawal {
+    keypair: ElGamalKeypair,
+    domain: [u8; 32],
+    old_balance: [u8; 64],
+    debit: DebitProof,
+}
+
+impl Withdrawal {
+    fn new() -> Self {
+        let keypair = ElGamalKeypair::new_rand();
+        let domain = zk_utils::domain_separator("withdraw", &Pubkey::new_unique());
+        let old_balance = encrypt_balance(&keypair, 900);
+        let debit = prove_debit(&keypair, &domain, &old_balance, 900, 300).unwrap();
+        Withdrawal {
+            keypair,
+            domain,
+            old_balance,
+            debit,
+        }
+    }
+
+    fn verify(&self, envelope: &ProofEnvelope, amount: u64) -> anchor_lang::Result<()> {
+        zk_utils::verify_confidential_balance(
+            envelope,
+            &self.domain,
+            amount,
+            BalanceUpdate::Debit,
+            self.keypair.pubkey().to_bytes(),
+            self.old_balance,
+            self.debit.new_balance,
+        )
+    }
+}
+
+/// `envelope` with its proof zlib-compressed whether or not that makes it smaller.
+fn zlib(envelope: &ProofEnvelope) -> ProofEnvelope {
+    ProofEnvelope {
+        compression: ProofCompression::Zlib,
+        proof: miniz_oxide::deflate::compress_to_vec_zlib(&envelope.proof, 6),
+        ..envelope.clone()
+    }
+}
+
+#[test]
+fn compressed_proofs_verify_like_raw_ones() {
+    let withdrawal = Withdrawal::new();
+    let compressed = zlib(&withdrawal.debit.proof);
+    withdrawal.verify(&compressed, 300).unwrap();
+    // Compression changes the encoding, not the statement the proof is bound to.
+    assert_eq!(
+        withdrawal.verify(&compressed, 301).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    let mut truncated = compressed.clone();
+    truncated.proof.pop();
+    assert_eq!(
+        withdrawal.verify(&truncated, 300).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+    let mislabelled = ProofEnvelope {
+        compression: ProofCompression::Zlib,
+        ..withdrawal.debit.proof.clone()
+    };
+    assert_eq!(
+        withdrawal.verify(&mislabelled, 300).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+}
+
+#[test]
+fn decompression_is_capped() {
+    let inflate = |len: usize| {
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&vec![0u8; len], 10);
+        zk_utils::decompress_proof(&compressed)
+    };
+    assert_eq!(
+        inflate(MAX_INFLATED_PROOF_LEN).unwrap().len(),
+        MAX_INFLATED_PROOF_LEN
+    );
+    assert_eq!(
+        inflate(MAX_INFLATED_PROOF_LEN + 1).unwrap_err(),
+        ErrorCode::ProofTooLarge.into()
+    );
+
+    // A megabyte of zeros deflates to about a kilobyte and is refused all the same.
+    let bomb = miniz_oxide::deflate::compress_to_vec_zlib(&vec![0u8; 1 << 20], 10);
+    assert!(bomb.len() < 1_100);
+    assert_eq!(
+        zk_utils::decompress_proof(&bomb).unwrap_err(),
+        ErrorCode::ProofTooLarge.into()
+    );
+}
+
+#[test]
+fn compress_only_keeps_smaller_encodings() {
+    // Sigma proofs are scalars and curve points, which deflate cannot shrink.
+    let withdrawal = Withdrawal::new();
+    let debit = compress(withdrawal.debit.proof.clone());
+    assert_eq!(debit, withdrawal.debit.proof);
+
+    let padded = ProofEnvelope {
+        proof: vec![0u8; 512],
+        ..withdrawal.debit.proof.clone()
+    };
+    let compressed = compress(padded.clone());
+    assert_eq!(compressed.compression, ProofCompression::Zlib);
+    assert!(compressed.proof.len() < 32);
+    assert_eq!(compress(compressed.clone()), compressed);
+    assert_eq!(
+        zk_utils::decompress_proof(&compressed.proof).unwrap(),
+        padded.proof
+    );
+}
+
+/// Prints what compressing each proof format does to the envelope an instruction carries,
+/// and how long inflating it takes on the host. Run with
+/// `cargo test --test proof_compression -- --ignored --nocapture`.
+///
+/// Compute units can only be measured on a validator; the host timing is a relative guide
+/// to what `decompress_proof` adds to verification, not an estimate of units.
+#[test]
+#[ignore]
+fn benchmark_proof_compression() {
+    let withdrawal = Withdrawal::new();
+
+    let mut rng = StdRng::seed_from_u64(29);
+    let domain = zk_utils::domain_separator("claim_epoch_reward", &Pubkey::new_unique());
+    let inputs = [1, 2, 3, 4, 5].map(zk_utils::u64_to_field);
+    let (pk, _) = groth16::setup(RewardClaimCircuit::new(&inputs), &mut rng).unwrap();
+    let proof = groth16::prove(&pk, RewardClaimCircuit::new(&inputs), &mut rng).unwrap();
+    let groth16 = groth16::envelope(Circuit::RewardClaim, &domain, &inputs, proof);
+
+    println!("proof       raw  zlib  compress()  inflate");
+    for (name, envelope) in [("balance", &withdrawal.debit.proof), ("groth16", &groth16)] {
+        let raw = envelope.try_to_vec().unwrap().len();
+        let compressed = zlib(envelope);
+        let start = Instant::now();
+        for _ in 0..1_000 {
+            zk_utils::decompress_proof(&compressed.proof).unwrap();
+        }
+        println!(
+            "{name:<9} {raw:>5} {:>5} {:>11} {:>6.1}µs",
+            compressed.try_to_vec().unwrap().len(),
+            compress(envelope.clone()).try_to_vec().unwrap().len(),
+            start.elapsed().as_secs_f64() * 1_000.0,
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{Circuit, ErrorCode, ProofEnvelope, ProofSystem, VerifyingKeyRegistry};
+use zk_liquidity_pool_prover::*;
+
+struct Withdrawal {
+    keypair: ElGamalKeypair,
+    pool: Pubkey,
+    old_balance: [u8; 64],
+    debit: DebitProof,
+}
+
+impl Withdrawal {
+    fn new() -> Self {
+        let keypair = ElGamalKeypair::new_rand();
+        let pool = Pubkey::new_unique();
+        let old_balance = encrypt_balance(&keypair, 900);
+        let domain = zk_utils::domain_separator("withdraw", &pool);
+        let debit = prove_debit(&keypair, &domain, &old_balance, 900, 300).unwrap();
+        Withdrawal {
+            keypair,
+            pool,
+            old_balance,
+            debit,
+        }
+    }
+
+    fn domain(&self) -> [u8; 32] {
+        zk_utils::domain_separator("withdraw", &self.pool)
+    }
+
+    fn verify(
+        &self,
+        envelope: &ProofEnvelope,
+        domain: &[u8; 32],
+        amount: u64,
+    ) -> anchor_lang::Result<()> {
+        zk_utils::verify_confidential_balance(
+            envelope,
+            domain,
+            amount,
+            BalanceUpdate::Debit,
+            self.keypair.pubkey().to_bytes(),
+            self.old_balance,
+            self.debit.new_balance,
+        )
+    }
+}
+
+#[test]
+fn accepts_a_correctly_framed_proof() {
+    let withdrawal = Withdrawal::new();
+    withdrawal
+        .verify(&withdrawal.debit.proof, &withdrawal.domain(), 300)
+        .unwrap();
+}
+
+#[test]
+fn domain_separates_instructions_pools_and_programs() {
+    let pool = Pubkey::new_unique();
+    let withdraw = zk_utils::domain_separator("withdraw", &pool);
+    assert_ne!(withdraw, zk_utils::domain_separator("zk_exit", &pool));
+    assert_ne!(
+        withdraw,
+        zk_utils::domain_separator("withdraw", &Pubkey::new_unique())
+    );
+    // Length prefixes keep different splits of the same bytes apart.
+    assert_ne!(
+        zk_utils::public_input_hash(&[b"ab", b"c"]),
+        zk_utils::public_input_hash(&[b"a", b"bc"])
+    );
+}
+
+#[test]
+fn each_framing_failure_has_its_own_error() {
+    let withdrawal = Withdrawal::new();
+    let domain = withdrawal.domain();
+    let verify = |envelope: &ProofEnvelope| withdrawal.verify(envelope, &domain, 300);
+
+    let mut envelope = withdrawal.debit.proof.clone();
+    envelope.version = zk_utils::PROOF_ENVELOPE_VERSION + 1;
+    assert_eq!(
+        verify(&envelope).unwrap_err(),
+        ErrorCode::UnsupportedProofVersion.into()
+    );
+
+    let mut envelope = withdrawal.debit.proof.clone();
+    envelope.system = ProofSystem::Groth16;
+    assert_eq!(
+        verify(&envelope).unwrap_err(),
+        ErrorCode::ProofSystemMismatch.into()
+    );
+
+    let mut envelope = withdrawal.debit.proof.clone();
+    envelope.circuit = Circuit::Reward;
+    assert_eq!(
+        verify(&envelope).unwrap_err(),
+        ErrorCode::ProofCircuitMismatch.into()
+    );
+
+    let mut envelope = withdrawal.debit.proof.clone();
+    envelope.proof.pop();
+    assert_eq!(
+        verify(&envelope).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+
+    assert_eq!(
+        withdrawal
+            .verify(&withdrawal.debit.proof, &domain, 301)
+            .unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+}
+
+#[test]
+fn proofs_cannot_be_replayed_in_another_instruction() {
+    let withdrawal = Withdrawal::new();
+    let exit_domain = zk_utils::domain_separator("zk_exit", &withdrawal.pool);
+    assert_eq!(
+        withdrawal
+            .verify(&withdrawal.debit.proof, &exit_domain, 300)
+            .unwrap_err(),
+        ErrorCode::ProofDomainMismatch.into()
+    );
+
+    // Re-framing the envelope for the other instruction does not help: the domain is part of
+    // the Fiat–Shamir challenge, so the proof itself no longer verifies.
+    let mut reframed = withdrawal.debit.proof.clone();
+    reframed.domain = exit_domain;
+    assert_eq!(
+        withdrawal.verify(&reframed, &exit_domain, 300).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn range_and_snark_verifiers_check_the_envelope() {
+    let withdrawal = Withdrawal::new();
+    let domain = withdrawal.domain();
+    let (commitment, _) = zk_utils::split_ciphertext(&withdrawal.debit.new_balance);
+
+    // A balance proof is not a range proof; the framing is rejected before any account is read.
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    let mut data = [];
+    let account = solana_program::account_info::AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &key,
+        false,
+        0,
+    );
+    assert_eq!(
+        zk_utils::verify_transfer_proof(
+            &account,
+            &withdrawal.debit.proof,
+            &domain,
+            &[commitment.0]
+        )
+        .unwrap_err(),
+        ErrorCode::ProofSystemMismatch.into()
+    );
+
+    // A reward proof cannot be passed off as a vote proof.
+    let registry = VerifyingKeyRegistry {
+        pool: withdrawal.pool,
+        bump: 255,
+        keys: vec![],
+    };
+    let inputs = [zk_utils::u64_to_field(1)];
+    let reward = groth16::envelope(Circuit::Reward, &domain, &inputs, vec![0; 256]);
+    assert_eq!(
+        zk_utils::verify_groth16(&registry, Circuit::Vote, &reward, &domain, &inputs).unwrap_err(),
+        ErrorCode::ProofCircuitMismatch.into()
+    );
+}
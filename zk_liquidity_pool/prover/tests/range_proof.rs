@@ -0,0 +1,267 @@
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::instructions::{
+    self, construct_instructions_data, store_current_index, BorrowedAccountMeta,
+    BorrowedInstruction,
+};
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::encryption::pedersen::PedersenOpening;
+use solana_zk_token_sdk::instruction::{BatchedRangeProofContext, ProofType};
+use solana_zk_token_sdk::zk_token_proof_program;
+use solana_zk_token_sdk::zk_token_proof_state::ProofContextState;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{ErrorCode, ProofEnvelope};
+use zk_liquidity_pool_prover::*;
+
+mod common;
+
+fn domain() -> [u8; 32] {
+    zk_utils::domain_separator("withdraw", &Pubkey::new_from_array([9; 32]))
+}
+
+fn context_state_data(proof: &RangeProofData, proof_type: ProofType) -> Vec<u8> {
+    ProofContextState::<BatchedRangeProofContext>::encode(
+        &Pubkey::new_unique(),
+        proof_type,
+        proof.context(),
+    )
+}
+
+fn verify_with_context_state(
+    data: &mut [u8],
+    owner: &Pubkey,
+    expected_commitments: &[[u8; 32]],
+) -> bool {
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    let account = AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0);
+    let envelope = range_proof_envelope(&domain(), 0, expected_commitments);
+    zk_utils::verify_transfer_proof(&account, &envelope, &domain(), expected_commitments).is_ok()
+}
+
+#[test]
+fn valid_proofs_verify_for_each_batch_size() {
+    let openings: Vec<PedersenOpening> = (0..4).map(|_| PedersenOpening::new_rand()).collect();
+    for count in 1..=4 {
+        let values: Vec<(u64, &PedersenOpening)> = openings
+            .iter()
+            .take(count)
+            .enumerate()
+            .map(|(i, opening)| (u64::MAX - i as u64, opening))
+            .collect();
+        let proof = prove_range(&values).expect("range proof");
+        assert!(proof.verify(), "batch of {count} should verify");
+    }
+}
+
+#[test]
+fn tampered_proofs_fail_verification() {
+    let opening = PedersenOpening::new_rand();
+    let mut proof = prove_range(&[(42, &opening)]).unwrap();
+    proof.proof_bytes_mut()[100] ^= 1;
+    assert!(!proof.verify());
+
+    let mut proof = prove_range(&[(42, &opening)]).unwrap();
+    proof.context_mut().commitments[0] = prove_range(&[(43, &opening)])
+        .unwrap()
+        .context()
+        .commitments[0];
+    assert!(!proof.verify());
+}
+
+/// Runs the bound range check `withdraw` and `zk_exit` make against a context-state account
+/// holding the debit's range proof.
+fn verify_debit(keypair: &ElGamalKeypair, debit: &DebitProof) -> anchor_lang::Result<()> {
+    let mut data = context_state_data(&debit.range_proof, ProofType::BatchedRangeProofU64);
+    let key = Pubkey::new_unique();
+    let owner = zk_token_proof_program::id();
+    let mut lamports = 0;
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    let commitments = [debit.balance_commitment];
+    zk_utils::verify_bounded_ciphertexts(
+        &account,
+        &debit.equality_proof,
+        &range_proof_envelope(&domain(), 0, &commitments),
+        &domain(),
+        &keypair.pubkey().to_bytes(),
+        &[debit.new_balance],
+        &commitments,
+    )
+}
+
+#[test]
+fn context_state_binds_the_new_balance() {
+    let keypair = ElGamalKeypair::new_rand();
+    let old_balance = encrypt_balance(&keypair, 900);
+    let debit = prove_debit(&keypair, &domain(), &old_balance, 900, 300).unwrap();
+    assert!(debit.range_proof.verify());
+    assert!(zk_utils::verify_confidential_balance(
+        &debit.proof,
+        &domain(),
+        300,
+        BalanceUpdate::Debit,
+        keypair.pubkey().to_bytes(),
+        old_balance,
+        debit.new_balance,
+    )
+    .is_ok());
+    verify_debit(&keypair, &debit).unwrap();
+
+    let mut data = context_state_data(&debit.range_proof, ProofType::BatchedRangeProofU64);
+    assert!(verify_with_context_state(
+        &mut data,
+        &zk_token_proof_program::id(),
+        &[debit.balance_commitment],
+    ));
+
+    // A proof over some other commitment does not authorize this balance.
+    let (new_commitment, _) = zk_utils::split_ciphertext(&debit.new_balance);
+    assert!(!verify_with_context_state(
+        &mut data,
+        &zk_token_proof_program::id(),
+        &[new_commitment.0],
+    ));
+    // Nor does a valid proof checked against no commitments at all.
+    assert!(!verify_with_context_state(
+        &mut data,
+        &zk_token_proof_program::id(),
+        &[],
+    ));
+
+    // Context state must be owned by the proof program and hold a batched range proof.
+    assert!(!verify_with_context_state(
+        &mut data,
+        &Pubkey::new_unique(),
+        &[debit.balance_commitment],
+    ));
+    let mut wrong_type = context_state_data(&debit.range_proof, ProofType::ZeroBalance);
+    assert!(!verify_with_context_state(
+        &mut wrong_type,
+        &zk_token_proof_program::id(),
+        &[debit.balance_commitment],
+    ));
+}
+
+#[test]
+fn debits_past_the_balance_are_rejected() {
+    let keypair = ElGamalKeypair::new_rand();
+    let opening = PedersenOpening::new_rand();
+    let old_balance = keypair.pubkey().encrypt_with(10_u64, &opening).to_bytes();
+    assert!(prove_debit(&keypair, &domain(), &old_balance, 10, 1_000_000).is_none());
+
+    // Debit 1,000,000 from 10 by moving the handle only, and range-prove the untouched `C`.
+    let target = zk_utils::subtract_public_value(&old_balance, 1_000_000).unwrap();
+    let old_commitment: [u8; 32] = old_balance[..32].try_into().unwrap();
+    let new_balance = common::forge_ciphertext(&keypair, &target, &old_commitment);
+    let proof = prove_zero_delta(
+        &keypair,
+        &domain(),
+        &old_balance,
+        &new_balance,
+        1_000_000,
+        BalanceUpdate::Debit,
+    )
+    .unwrap();
+    let range_proof = prove_range(&[(10, &opening)]).unwrap();
+    let mut equality = prove_equality(&keypair, &domain(), &[(new_balance, 10)]).unwrap();
+    let forged = DebitProof {
+        new_balance,
+        proof,
+        balance_commitment: old_commitment,
+        opening,
+        equality_proof: equality.proof,
+        range_proof,
+    };
+    assert!(zk_utils::verify_confidential_balance(
+        &forged.proof,
+        &domain(),
+        1_000_000,
+        BalanceUpdate::Debit,
+        keypair.pubkey().to_bytes(),
+        old_balance,
+        forged.new_balance,
+    )
+    .is_ok());
+    // `C` is not the commitment the equality proof binds.
+    assert_eq!(
+        verify_debit(&keypair, &forged).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    // A fresh commitment to 10 is range-provable but does not match the plaintext.
+    let opening = equality.openings.remove(0);
+    let bound = DebitProof {
+        balance_commitment: equality.commitments[0],
+        range_proof: prove_range(&[(10, &opening)]).unwrap(),
+        opening,
+        ..forged
+    };
+    assert_eq!(
+        verify_debit(&keypair, &bound).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn sibling_proof_instruction_is_read_from_the_sysvar() {
+    let opening = PedersenOpening::new_rand();
+    let proof = prove_range(&[(7, &opening), (8, &opening)]).unwrap();
+    let commitment = proof.context().commitments[1].0;
+    let proof_ix = proof.instruction(None);
+    let program_data = [0u8; 8];
+
+    let mut data = construct_instructions_data(&[
+        BorrowedInstruction {
+            program_id: &proof_ix.program_id,
+            accounts: vec![],
+            data: &proof_ix.data,
+        },
+        BorrowedInstruction {
+            program_id: &zk_liquidity_pool::ID,
+            accounts: vec![BorrowedAccountMeta {
+                pubkey: &instructions::ID,
+                is_signer: false,
+                is_writable: false,
+            }],
+            data: &program_data,
+        },
+    ]);
+    store_current_index(&mut data, 1);
+
+    let key = instructions::ID;
+    let owner = solana_program::sysvar::ID;
+    let mut lamports = 0;
+    let sysvar = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+
+    let verify = |envelope: &ProofEnvelope| {
+        zk_utils::verify_transfer_proof(&sysvar, envelope, &domain(), &[commitment])
+    };
+    assert!(verify(&range_proof_envelope(&domain(), -1, &[commitment])).is_ok());
+    // An offset past the end of the transaction is rejected.
+    assert!(verify(&range_proof_envelope(&domain(), 1, &[commitment])).is_err());
+    // Only the one-byte location encoding is accepted.
+    let mut empty = range_proof_envelope(&domain(), -1, &[commitment]);
+    empty.proof.clear();
+    assert_eq!(
+        verify(&empty).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+}
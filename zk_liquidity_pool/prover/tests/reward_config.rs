@@ -0,0 +1,260 @@
+use std::collections::BTreeSet;
+
+use anchor_lang::{Accounts, AnchorSerialize};
+use anchor_spl::token_2022::spl_token_2022;
+use solana_program::account_info::AccountInfo;
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use spl_token_2022::state::{Account, AccountState, Mint};
+use zk_liquidity_pool::{
+    DistributeRewards, DistributeRewardsBumps, ErrorCode, LiquidityPool, LockupPolicy,
+    RewardConfig, RewardTiers, VerifyingKeyRegistry,
+};
+
+mod common;
+
+use common::{account, anchor_data};
+
+const EPOCH: i64 = 100;
+
+fn reward_config(pool: Pubkey, bump: u8) -> RewardConfig {
+    RewardConfig {
+        pool,
+        distributor: Pubkey::new_unique(),
+        epoch_seconds: EPOCH,
+        emission_cap: 1_000,
+        epoch: 0,
+        epoch_start: 1_000,
+        epoch_emitted: 0,
+        bump,
+    }
+}
+
+/// The accounts of a `distribute_rewards` call by the configured distributor, in
+/// `DistributeRewards` order.
+struct Distribution {
+    pool: LiquidityPool,
+    pool_key: Pubkey,
+    reward_config: RewardConfig,
+    reward_config_key: Pubkey,
+    reward_vault_key: Pubkey,
+    mint_authority: Pubkey,
+    distributor_signs: bool,
+}
+
+impl Distribution {
+    fn new() -> Self {
+        let token_mint = Pubkey::new_unique();
+        let (pool_key, bump) = LiquidityPool::find_address(&token_mint);
+        let (reward_config_key, config_bump) = Pubkey::find_program_address(
+            &[RewardConfig::SEED, pool_key.as_ref()],
+            &zk_liquidity_pool::ID,
+        );
+        let (reward_vault_key, _) = Pubkey::find_program_address(
+            &[RewardConfig::VAULT_SEED, pool_key.as_ref()],
+            &zk_liquidity_pool::ID,
+        );
+        Distribution {
+            pool: LiquidityPool {
+                authority: Pubkey::new_unique(),
+                token_mint,
+                total_commitment: [0u8; 32],
+                auditor_elgamal_pubkey: None,
+                auditor_total: [0u8; 64],
+                lockup: LockupPolicy::default(),
+                treasury: Pubkey::new_unique(),
+                reward_tiers: RewardTiers::default(),
+                reward_rate: 0,
+                acc_reward_per_share: 0,
+                last_reward_timestamp: 0,
+                reward_rate_history: Vec::new(),
+                bump,
+            },
+            pool_key,
+            reward_config: reward_config(pool_key, config_bump),
+            reward_config_key,
+            reward_vault_key,
+            mint_authority: reward_config_key,
+            distributor_signs: true,
+        }
+    }
+
+    fn accounts(&self, distributor: Pubkey) -> &'static [AccountInfo<'static>] {
+        let program = zk_liquidity_pool::ID;
+        let token = spl_token_2022::ID;
+        let token_mint = self.pool.token_mint;
+
+        let mut mint = vec![0u8; Mint::LEN];
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(self.mint_authority),
+                decimals: 6,
+                is_initialized: true,
+                ..Mint::default()
+            },
+            &mut mint,
+        )
+        .unwrap();
+        let mut reward_vault = vec![0u8; Account::LEN];
+        Account::pack(
+            Account {
+                mint: token_mint,
+                owner: self.pool_key,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut reward_vault,
+        )
+        .unwrap();
+        let (registry_key, registry_bump) =
+            Pubkey::find_program_address(&[b"vk_registry", self.pool_key.as_ref()], &program);
+        let registry = VerifyingKeyRegistry {
+            pool: self.pool_key,
+            bump: registry_bump,
+            keys: Vec::new(),
+        };
+
+        let accounts = vec![
+            account(
+                self.pool_key,
+                program,
+                anchor_data(&self.pool),
+                false,
+                false,
+            ),
+            account(
+                self.reward_config_key,
+                program,
+                anchor_data(&self.reward_config),
+                false,
+                false,
+            ),
+            account(
+                distributor,
+                Pubkey::default(),
+                Vec::new(),
+                self.distributor_signs,
+                false,
+            ),
+            account(self.reward_vault_key, token, reward_vault, false, false),
+            account(token_mint, token, mint, false, false),
+            account(token, Pubkey::default(), Vec::new(), false, true),
+            account(registry_key, program, anchor_data(&registry), false, false),
+        ];
+        Box::leak(accounts.into_boxed_slice())
+    }
+
+    fn load(&self, distributor: Pubkey) -> anchor_lang::Result<()> {
+        let mut accounts = self.accounts(distributor);
+        DistributeRewards::try_accounts(
+            &zk_liquidity_pool::ID,
+            &mut accounts,
+            &[],
+            &mut DistributeRewardsBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(drop)
+    }
+}
+
+#[test]
+fn only_the_distributor_can_distribute_rewards() {
+    let distribution = Distribution::new();
+    let distributor = distribution.reward_config.distributor;
+    distribution.load(distributor).unwrap();
+
+    assert_eq!(
+        distribution.load(Pubkey::new_unique()).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintHasOne.into()
+    );
+    // Not even the pool authority.
+    assert_eq!(
+        distribution.load(distribution.pool.authority).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintHasOne.into()
+    );
+
+    let mut unsigned = Distribution::new();
+    unsigned.distributor_signs = false;
+    assert_eq!(
+        unsigned
+            .load(unsigned.reward_config.distributor)
+            .unwrap_err(),
+        anchor_lang::error::ErrorCode::AccountNotSigner.into()
+    );
+}
+
+#[test]
+fn rewards_are_minted_by_the_pools_reward_config() {
+    // A mint the configuration PDA is not the authority of is rejected, even the pool's.
+    let mut pool_minted = Distribution::new();
+    pool_minted.mint_authority = pool_minted.pool_key;
+    assert_eq!(
+        pool_minted
+            .load(pool_minted.reward_config.distributor)
+            .unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintMintMintAuthority.into()
+    );
+
+    // So is another pool's configuration, even one naming the same distributor.
+    let other = Distribution::new();
+    let mut crossed = Distribution::new();
+    crossed.reward_config = RewardConfig {
+        distributor: crossed.reward_config.distributor,
+        ..other.reward_config.clone()
+    };
+    crossed.reward_config_key = other.reward_config_key;
+    assert_eq!(
+        crossed.load(crossed.reward_config.distributor).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+    );
+}
+
+#[test]
+fn rewards_are_minted_into_the_pools_reward_vault() {
+    // Any other account the pool owns, such as a stake vault, is rejected.
+    let mut stake_vault = Distribution::new();
+    stake_vault.reward_vault_key = Pubkey::new_unique();
+    assert_eq!(
+        stake_vault
+            .load(stake_vault.reward_config.distributor)
+            .unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+    );
+}
+
+#[test]
+fn emissions_are_capped_per_epoch() {
+    let mut config = reward_config(Pubkey::new_unique(), 255);
+    config.record_emission(600, 1_000).unwrap();
+    config.record_emission(400, 1_099).unwrap();
+    assert_eq!(
+        config.record_emission(1, 1_099).unwrap_err(),
+        ErrorCode::EmissionCapExceeded.into()
+    );
+    assert_eq!(config.epoch_emitted, 1_000);
+
+    // The next epoch starts on the epoch boundary, however late the first call in it is.
+    config.record_emission(1_000, 1_250).unwrap();
+    assert_eq!(
+        (config.epoch, config.epoch_start, config.epoch_emitted),
+        (2, 1_200, 1_000)
+    );
+    assert_eq!(
+        config.record_emission(1, 1_299).unwrap_err(),
+        ErrorCode::EmissionCapExceeded.into()
+    );
+    assert_eq!(
+        config.record_emission(u64::MAX, 1_300).unwrap_err(),
+        ErrorCode::EmissionCapExceeded.into()
+    );
+    assert_eq!(config.epoch, 3);
+
+    assert_eq!(
+        config
+            .configure(Pubkey::new_unique(), 0, 1_000)
+            .unwrap_err(),
+        ErrorCode::InvalidRewardConfig.into()
+    );
+    assert_eq!(config.try_to_vec().unwrap().len(), RewardConfig::LEN);
+}
@@ -0,0 +1,178 @@
+use anchor_lang::AnchorSerialize;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::instruction::{
+    ProofType, TransferData, TransferProofContext, ZkProofData,
+};
+use solana_zk_token_sdk::zk_token_proof_program;
+use solana_zk_token_sdk::zk_token_proof_state::ProofContextState;
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{
+    Circuit, ErrorCode, RegisteredVerifyingKey, RewardEpoch, VerifyingKeyRegistry,
+};
+use zk_liquidity_pool_prover::groth16::{self, RewardClaimCircuit};
+
+fn reward_epoch(total_amount: u64, expiry: i64) -> RewardEpoch {
+    RewardEpoch {
+        pool: Pubkey::new_unique(),
+        epoch: 3,
+        merkle_root: [0u8; 32],
+        leaf_count: 4,
+        total_amount,
+        expiry,
+        swept: false,
+        bump: 255,
+    }
+}
+
+/// The context of the pool authority's proof for a confidential payout of `amount` from an
+/// epoch vault holding 1,000 under `vault` to `recipient`.
+fn payout(vault: &ElGamalKeypair, recipient: &ElGamalKeypair, amount: u64) -> TransferProofContext {
+    let balance = vault.pubkey().encrypt(1_000_u64);
+    let auditor = ElGamalKeypair::new_rand();
+    let proof = TransferData::new(
+        amount,
+        (1_000, &balance),
+        vault,
+        (recipient.pubkey(), auditor.pubkey()),
+    )
+    .unwrap();
+    *proof.context_data()
+}
+
+/// Loads `context` from a context-state account owned by `owner` holding a `proof_type` proof.
+fn load_context(
+    context: &TransferProofContext,
+    owner: &Pubkey,
+    proof_type: ProofType,
+) -> anchor_lang::Result<TransferProofContext> {
+    let mut data = ProofContextState::<TransferProofContext>::encode(
+        &Pubkey::new_unique(),
+        proof_type,
+        context,
+    );
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        owner,
+        false,
+        0,
+    );
+    zk_utils::load_transfer_proof_context(&account)
+}
+
+#[test]
+fn claims_are_bound_to_the_nullifier_payout_and_recipient() {
+    let mut rng = StdRng::seed_from_u64(23);
+    let domain = zk_utils::domain_separator("claim_epoch_reward", &Pubkey::new_unique());
+    let leaf =
+        zk_utils::reward_leaf(&zk_utils::hash_to_field(&[b"commitment"]), &[7u8; 64]).unwrap();
+    let (vault, claimant) = (ElGamalKeypair::new_rand(), ElGamalKeypair::new_rand());
+    let transfer = load_context(
+        &payout(&vault, &claimant, 500),
+        &zk_token_proof_program::id(),
+        ProofType::Transfer,
+    )
+    .unwrap();
+    let nullifier_hash = zk_utils::hash_to_field(&[b"nullifier"]);
+    let recipient = Pubkey::new_unique();
+    let inputs =
+        zk_utils::reward_claim_public_inputs(&domain, leaf, nullifier_hash, &transfer, &recipient);
+    let (pk, vk) = groth16::setup(RewardClaimCircuit::new(&inputs), &mut rng).unwrap();
+    let proof = groth16::prove(&pk, RewardClaimCircuit::new(&inputs), &mut rng).unwrap();
+    let envelope = groth16::envelope(Circuit::RewardClaim, &domain, &inputs, proof);
+
+    let vk_registry = VerifyingKeyRegistry {
+        pool: Pubkey::new_unique(),
+        bump: 255,
+        keys: vec![RegisteredVerifyingKey {
+            circuit: Circuit::RewardClaim,
+            version: 1,
+            key: groth16::verifying_key(&vk),
+        }],
+    };
+    let verify = |nullifier_hash: [u8; 32], transfer: &TransferProofContext, recipient: &Pubkey| {
+        zk_utils::verify_groth16(
+            &vk_registry,
+            Circuit::RewardClaim,
+            &envelope,
+            &domain,
+            &zk_utils::reward_claim_public_inputs(
+                &domain,
+                leaf,
+                nullifier_hash,
+                transfer,
+                recipient,
+            ),
+        )
+    };
+
+    verify(nullifier_hash, &transfer, &recipient).unwrap();
+    // Another payout, even of the same amount, is not the one the proof was made for.
+    for other in [
+        payout(&vault, &claimant, 501),
+        payout(&vault, &claimant, 500),
+    ] {
+        assert_eq!(
+            verify(nullifier_hash, &other, &recipient).unwrap_err(),
+            ErrorCode::PublicInputHashMismatch.into()
+        );
+    }
+    assert_eq!(
+        verify([1u8; 32], &transfer, &recipient).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+    assert_eq!(
+        verify(nullifier_hash, &transfer, &Pubkey::new_unique()).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+}
+
+#[test]
+fn payouts_are_read_from_verified_transfer_proofs() {
+    let context = payout(
+        &ElGamalKeypair::new_rand(),
+        &ElGamalKeypair::new_rand(),
+        500,
+    );
+    let loaded =
+        load_context(&context, &zk_token_proof_program::id(), ProofType::Transfer).unwrap();
+    assert_eq!(loaded.ciphertext_lo.0 .0, context.ciphertext_lo.0 .0);
+    assert_eq!(loaded.ciphertext_hi.0 .0, context.ciphertext_hi.0 .0);
+
+    for (owner, proof_type) in [
+        (Pubkey::new_unique(), ProofType::Transfer),
+        (zk_token_proof_program::id(), ProofType::Withdraw),
+    ] {
+        assert_eq!(
+            load_context(&context, &owner, proof_type)
+                .map(drop)
+                .unwrap_err(),
+            ErrorCode::InvalidProofAccount.into()
+        );
+    }
+}
+
+#[test]
+fn unclaimed_rewards_are_swept_once_after_expiry() {
+    let mut epoch = reward_epoch(1_000, 500);
+    assert_eq!(
+        epoch.sweep(499).unwrap_err(),
+        ErrorCode::RewardEpochOpen.into()
+    );
+    epoch.sweep(500).unwrap();
+    assert!(epoch.swept);
+    assert_eq!(
+        epoch.sweep(600).unwrap_err(),
+        ErrorCode::RewardEpochSwept.into()
+    );
+    assert_eq!(epoch.try_to_vec().unwrap().len(), RewardEpoch::LEN);
+}
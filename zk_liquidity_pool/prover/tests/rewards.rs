@@ -0,0 +1,338 @@
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{
+    AssetVault, ErrorCode, LiquidityPool, LockupPolicy, RewardRatePeriod, RewardTier, RewardTiers,
+    UserStake, BASE_REWARD_MULTIPLIER_BPS, MAX_REWARD_RATE_HISTORY,
+};
+use zk_liquidity_pool_prover::{
+    encrypt_balance, prove_equality, prove_range, prove_reward_claim, prove_zero_delta,
+    RewardClaimProof, ZERO_BALANCE,
+};
+
+mod common;
+
+const BALANCE: u64 = 1_000;
+const DAY: i64 = 86_400;
+
+fn pool(reward_rate: u64, last_reward_timestamp: i64) -> LiquidityPool {
+    LiquidityPool {
+        authority: Pubkey::new_unique(),
+        token_mint: Pubkey::new_unique(),
+        total_commitment: [0u8; 32],
+        auditor_elgamal_pubkey: None,
+        auditor_total: [0u8; 64],
+        lockup: LockupPolicy::default(),
+        treasury: Pubkey::new_unique(),
+        reward_tiers: RewardTiers::default(),
+        reward_rate,
+        acc_reward_per_share: 0,
+        last_reward_timestamp,
+        reward_rate_history: vec![RewardRatePeriod {
+            start_timestamp: last_reward_timestamp,
+            start_acc_reward_per_share: 0,
+            reward_rate,
+        }],
+        bump: 255,
+    }
+}
+
+fn vault(reward_weight: u64) -> AssetVault {
+    AssetVault {
+        pool: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        token_account: Pubkey::new_unique(),
+        reward_weight,
+        bump: 255,
+    }
+}
+
+/// A position holding `BALANCE` under `keypair`, staked at time zero, whose rewards were
+/// last settled at `reward_timestamp` with the accumulator at `reward_acc`.
+fn position(keypair: &ElGamalKeypair, reward_timestamp: i64, reward_acc: u128) -> UserStake {
+    let mut position = UserStake {
+        confidential_balance: encrypt_balance(keypair, BALANCE),
+        encrypted_data: Vec::new(),
+        stake_timestamp: 0,
+        asset_mint: Pubkey::new_unique(),
+        elgamal_pubkey: keypair.pubkey().to_bytes(),
+        balance_updated_slot: 0,
+        prior_balance: [0u8; 64],
+        prior_balance_slot: 0,
+        unbonding_amount: 0,
+        unbonding_penalty: 0,
+        unbond_timestamp: 0,
+        reward_debt: [0u8; 64],
+        reward_timestamp: 0,
+        reward_acc: 0,
+        balance_commitment: [0u8; 32],
+    };
+    position
+        .settle_rewards(reward_timestamp, reward_acc, &ZERO_BALANCE)
+        .unwrap();
+    position
+}
+
+/// Runs the checks `claim_rewards` makes, except the range proof which is checked by the
+/// ZK Token Proof program; the commitment it must cover is returned for the caller to compare.
+fn verify_claim(
+    position: &UserStake,
+    domain: &[u8; 32],
+    reward_per_unit: u128,
+    claim: &RewardClaimProof,
+) -> anchor_lang::Result<[u8; 32]> {
+    let pending = position.pending_rewards(reward_per_unit)?;
+    let unclaimed =
+        zk_utils::subtract_public_value(&pending, (claim.amount as u128) << 64).unwrap();
+    zk_utils::verify_confidential_balance(
+        &claim.proof,
+        domain,
+        0,
+        BalanceUpdate::Debit,
+        position.elgamal_pubkey,
+        unclaimed,
+        claim.remainder,
+    )?;
+    zk_utils::verify_ciphertext_commitment_equality(
+        &claim.equality_proof,
+        domain,
+        &position.elgamal_pubkey,
+        &[claim.remainder],
+        &[claim.remainder_commitment],
+    )?;
+    Ok(claim.remainder_commitment)
+}
+
+fn claim(
+    keypair: &ElGamalKeypair,
+    position: &UserStake,
+    domain: &[u8; 32],
+    reward_per_unit: u128,
+    pending_value: u128,
+) -> RewardClaimProof {
+    let pending = position.pending_rewards(reward_per_unit).unwrap();
+    prove_reward_claim(keypair, domain, &pending, pending_value).unwrap()
+}
+
+#[test]
+fn rewards_accrue_at_the_rate_per_second() {
+    let mut pool = pool(3, 100);
+    pool.accrue_rewards(90).unwrap();
+    assert_eq!(
+        (pool.acc_reward_per_share, pool.last_reward_timestamp),
+        (0, 100)
+    );
+    pool.accrue_rewards(110).unwrap();
+    assert_eq!(
+        (pool.acc_reward_per_share, pool.last_reward_timestamp),
+        (30, 110)
+    );
+
+    pool.reward_rate = u64::MAX;
+    pool.acc_reward_per_share = u128::MAX - 1;
+    assert_eq!(
+        pool.accrue_rewards(111).unwrap_err(),
+        ErrorCode::MathOverflow.into()
+    );
+}
+
+#[test]
+fn claims_pay_exactly_the_whole_tokens_earned() {
+    let keypair = ElGamalKeypair::new_rand();
+    let factor = vault(1).reward_factor(BASE_REWARD_MULTIPLIER_BPS).unwrap();
+    let mut position = position(&keypair, 0, 0);
+    let domain = zk_utils::domain_separator("claim_rewards", &Pubkey::new_unique());
+
+    // 2^50 accrued per unit of factor: 10^7 · 2^50 / 2^64 = 610.35 tokens on the balance.
+    let per_unit = (1u128 << 50) * factor as u128;
+    let earned = per_unit * BALANCE as u128;
+    let honest = claim(&keypair, &position, &domain, per_unit, earned);
+    assert_eq!(honest.amount, 610);
+    let remainder_commitment = verify_claim(&position, &domain, per_unit, &honest).unwrap();
+    assert!(honest.range_proof.verify());
+    assert_eq!(
+        honest.range_proof.context().commitments[0].0,
+        remainder_commitment
+    );
+
+    // A token more or less leaves a remainder that does not match what is owed.
+    for value in [earned + (1 << 64), earned - (1 << 64)] {
+        let dishonest = claim(&keypair, &position, &domain, per_unit, value);
+        assert_eq!(
+            verify_claim(&position, &domain, per_unit, &dishonest).unwrap_err(),
+            ErrorCode::InvalidZKProof.into()
+        );
+    }
+    // The claimed amount is bound into the proof.
+    let inflated = RewardClaimProof {
+        amount: honest.amount + 1,
+        ..honest
+    };
+    assert_eq!(
+        verify_claim(&position, &domain, per_unit, &inflated).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    // After the claim only the fraction is carried, and rewards keep accruing on top of it.
+    let pending = position.pending_rewards(per_unit).unwrap();
+    let carried = zk_utils::subtract_public_value(&pending, 610 << 64).unwrap();
+    position.settle_rewards(1, 1 << 50, &carried).unwrap();
+    assert_eq!(position.pending_rewards(0).unwrap(), carried);
+    let later = (1u128 << 52) * factor as u128;
+    let owed = (earned - (610 << 64)) + later * BALANCE as u128;
+    let second = claim(&keypair, &position, &domain, later, owed);
+    assert_eq!(second.amount, (owed >> 64) as u64);
+    verify_claim(&position, &domain, later, &second).unwrap();
+}
+
+#[test]
+fn over_claims_cannot_hide_a_negative_remainder() {
+    let keypair = ElGamalKeypair::new_rand();
+    let factor = vault(1).reward_factor(BASE_REWARD_MULTIPLIER_BPS).unwrap();
+    let position = position(&keypair, 0, 0);
+    let domain = zk_utils::domain_separator("claim_rewards", &Pubkey::new_unique());
+    let per_unit = (1u128 << 50) * factor as u128;
+    let earned = per_unit * BALANCE as u128;
+    let honest = claim(&keypair, &position, &domain, per_unit, earned);
+
+    // Claim a million tokens: the remainder then decrypts to a negative value, so give it
+    // the honest remainder's commitment, which opens to a 64-bit value.
+    let amount = 1_000_000;
+    let pending = position.pending_rewards(per_unit).unwrap();
+    let unclaimed = zk_utils::subtract_public_value(&pending, (amount as u128) << 64).unwrap();
+    let honest_commitment: [u8; 32] = honest.remainder[..32].try_into().unwrap();
+    let remainder = common::forge_ciphertext(&keypair, &unclaimed, &honest_commitment);
+    let proof = prove_zero_delta(
+        &keypair,
+        &domain,
+        &unclaimed,
+        &remainder,
+        0,
+        BalanceUpdate::Debit,
+    )
+    .unwrap();
+    let equality = prove_equality(&keypair, &domain, &[(remainder, earned as u64)]).unwrap();
+    let over_claim = RewardClaimProof {
+        amount,
+        remainder,
+        proof,
+        remainder_commitment: equality.commitments[0],
+        equality_proof: equality.proof,
+        range_proof: prove_range(&[(earned as u64, &equality.openings[0])]).unwrap(),
+    };
+    assert!(over_claim.range_proof.verify());
+    assert_eq!(
+        verify_claim(&position, &domain, per_unit, &over_claim).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+fn tiered_pool(reward_rate: u64) -> LiquidityPool {
+    LiquidityPool {
+        reward_tiers: RewardTiers {
+            tiers: vec![
+                RewardTier {
+                    min_stake_seconds: 30 * DAY,
+                    multiplier_bps: 12_500,
+                },
+                RewardTier {
+                    min_stake_seconds: 90 * DAY,
+                    multiplier_bps: 15_000,
+                },
+            ],
+        },
+        ..pool(reward_rate, 0)
+    }
+}
+
+#[test]
+fn tier_crossings_between_checkpoints_split_the_accrual() {
+    let keypair = ElGamalKeypair::new_rand();
+    let vault = vault(2);
+    assert_eq!(
+        AssetVault {
+            reward_weight: u64::MAX,
+            ..vault
+        }
+        .reward_factor(2)
+        .unwrap_err(),
+        ErrorCode::MathOverflow.into()
+    );
+
+    // Settled at day 10, untouched through both tiers, checkpointed at day 100.
+    let mut pool = tiered_pool(1 << 20);
+    let position = position(&keypair, 10 * DAY, (10 * DAY as u128) << 20);
+    pool.accrue_rewards(100 * DAY).unwrap();
+    let per_unit = pool.reward_per_unit(&vault, &position, 100 * DAY).unwrap();
+    let day = (DAY as u128) << 20;
+    let segmented = day * (20 * 20_000 + 60 * 25_000 + 10 * 30_000);
+    assert_eq!(per_unit, segmented);
+    // Neither the multiplier reached at the checkpoint nor the one held at the last
+    // settlement applies to the whole stretch.
+    assert_ne!(per_unit, day * 90 * 30_000);
+    assert_ne!(per_unit, day * 90 * 20_000);
+
+    let domain = zk_utils::domain_separator("claim_rewards", &Pubkey::new_unique());
+    let owed = segmented * BALANCE as u128;
+    let proof = claim(&keypair, &position, &domain, per_unit, owed);
+    verify_claim(&position, &domain, per_unit, &proof).unwrap();
+    // Claiming as if the 90-day boost had applied throughout does not verify.
+    let retroactive = day * 90 * 30_000 * BALANCE as u128;
+    let proof = claim(&keypair, &position, &domain, per_unit, retroactive);
+    assert_eq!(
+        verify_claim(&position, &domain, per_unit, &proof).unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn tier_crossings_use_the_rate_in_force_at_the_time() {
+    let keypair = ElGamalKeypair::new_rand();
+    let vault = vault(1);
+    let position = position(&keypair, 0, 0);
+
+    // The rate doubles at day 60, between the 30- and 90-day crossings.
+    let mut pool = tiered_pool(1);
+    pool.accrue_rewards(60 * DAY).unwrap();
+    pool.set_reward_rate(2, 60 * DAY);
+    pool.accrue_rewards(100 * DAY).unwrap();
+    assert_eq!(
+        pool.acc_reward_per_share_at(30 * DAY),
+        Some(30 * DAY as u128)
+    );
+    assert_eq!(
+        pool.acc_reward_per_share_at(90 * DAY),
+        Some(120 * DAY as u128)
+    );
+    // Past the last accrual the current rate continues.
+    assert_eq!(
+        pool.acc_reward_per_share_at(110 * DAY),
+        Some(160 * DAY as u128)
+    );
+
+    let day = DAY as u128;
+    let expected = day * (30 * 10_000 + (30 + 2 * 30) * 12_500 + 2 * 10 * 15_000);
+    assert_eq!(
+        pool.reward_per_unit(&vault, &position, 100 * DAY).unwrap(),
+        expected
+    );
+
+    // Once the starting rate falls out of the history the 30-day crossing cannot be placed,
+    // so the position keeps the base multiplier up to the 90-day one.
+    for day in 0..MAX_REWARD_RATE_HISTORY as i64 - 1 {
+        pool.accrue_rewards((101 + day) * DAY).unwrap();
+        pool.set_reward_rate(2, (101 + day) * DAY);
+    }
+    assert_eq!(pool.reward_rate_history.len(), MAX_REWARD_RATE_HISTORY);
+    assert_eq!(pool.acc_reward_per_share_at(30 * DAY), None);
+    assert_eq!(
+        pool.reward_per_unit(&vault, &position, 100 * DAY).unwrap(),
+        day * (120 * 10_000 + 2 * 10 * 15_000)
+    );
+    // Nor can rewards be computed up to a time before the history.
+    assert_eq!(
+        pool.reward_per_unit(&vault, &position, 50 * DAY)
+            .unwrap_err(),
+        ErrorCode::InvalidRewardCheckpoint.into()
+    );
+}
@@ -0,0 +1,387 @@
+use anchor_lang::AnchorSerialize;
+use solana_program::pubkey::Pubkey;
+use solana_zk_token_sdk::encryption::elgamal::ElGamalKeypair;
+use solana_zk_token_sdk::encryption::pedersen::{Pedersen, PedersenOpening};
+use zk_liquidity_pool::zk_utils::{self, BalanceUpdate};
+use zk_liquidity_pool::{
+    ErrorCode, Proposal, ProposalPayload, ProposalStatus, StakeWeight, UserStake, VoteWeighting,
+    MAX_COMMITTEE_SIZE,
+};
+use zk_liquidity_pool_prover::tally::{self, CommitteeKeys, StakeWeightProof, Weight};
+use zk_liquidity_pool_prover::{encrypt_balance, prove_equality, prove_range, prove_zero_delta};
+
+mod common;
+
+const STAKE_PER_VOTE: u64 = 100;
+
+fn user_stake(balance: [u8; 64]) -> UserStake {
+    UserStake {
+        confidential_balance: balance,
+        encrypted_data: vec![0u8; 64],
+        stake_timestamp: 0,
+        asset_mint: Pubkey::new_unique(),
+        elgamal_pubkey: [0u8; 32],
+        balance_updated_slot: 0,
+        prior_balance: [0u8; 64],
+        prior_balance_slot: 0,
+        unbonding_amount: 0,
+        unbonding_penalty: 0,
+        unbond_timestamp: 0,
+        reward_debt: [0u8; 64],
+        reward_timestamp: 0,
+        reward_acc: 0,
+        balance_commitment: [0u8; 32],
+    }
+}
+
+fn proposal(election_pubkey: [u8; 32]) -> Proposal {
+    Proposal {
+        governance: Pubkey::new_unique(),
+        id: 0,
+        proposer: Pubkey::new_unique(),
+        payload: ProposalPayload::UpdateRewardParams { new_reward_rate: 5 },
+        start_slot: 100,
+        end_slot: 200,
+        quorum: 1,
+        threshold_bps: 5_000,
+        vote_weighting: VoteWeighting::Stake {
+            stake_per_vote: STAKE_PER_VOTE,
+        },
+        votes_for: 0,
+        votes_against: 0,
+        election_pubkey,
+        encrypted_tally: [0u8; 64],
+        encrypted_turnout: [0u8; 64],
+        votes_cast: 0,
+        ballots_cast: 0,
+        decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        turnout_decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        decryption_share_mask: 0,
+        status: ProposalStatus::Active,
+        bump: 255,
+    }
+}
+
+/// Runs the checks `register_stake_weight` makes, except the range proof which is checked by
+/// the ZK Token Proof program; the commitments it must cover are returned for the caller to
+/// compare.
+fn verify_stake_weight(
+    keypair: &ElGamalKeypair,
+    election_pubkey: &[u8; 32],
+    domain: &[u8; 32],
+    balance: &[u8; 64],
+    proof: &StakeWeightProof,
+) -> anchor_lang::Result<[[u8; 32]; 2]> {
+    let mut weight_part = [0u8; 64];
+    let mut remainder = [0u8; 64];
+    weight_part.copy_from_slice(&proof.stake_split[..64]);
+    remainder.copy_from_slice(&proof.stake_split[64..]);
+    let recombined =
+        zk_utils::scale_ciphertext(&zk_utils::amount_scalar(STAKE_PER_VOTE), &weight_part)
+            .and_then(|scaled| zk_utils::add_ciphertexts(&scaled, &remainder))
+            .unwrap();
+    zk_utils::verify_confidential_balance(
+        &proof.split_proof,
+        domain,
+        0,
+        BalanceUpdate::Debit,
+        keypair.pubkey().to_bytes(),
+        *balance,
+        recombined,
+    )?;
+    let (weight_commitment, remainder_commitment) =
+        zk_utils::split_ciphertext(&proof.split_commitments);
+    zk_utils::verify_ciphertext_commitment_equality(
+        &proof.equality_proof,
+        domain,
+        &keypair.pubkey().to_bytes(),
+        &[weight_part, remainder],
+        &[weight_commitment.0, remainder_commitment.0],
+    )?;
+    zk_utils::verify_weight_commitment(
+        &proof.weight_proof,
+        domain,
+        election_pubkey,
+        &weight_commitment.0,
+        &proof.weight.ciphertext,
+    )?;
+    Ok([weight_commitment.0, remainder_commitment.0])
+}
+
+#[test]
+fn stake_balance_is_read_at_the_snapshot() {
+    let (first, second, third) = ([1u8; 64], [2u8; 64], [3u8; 64]);
+    let mut stake = user_stake([0u8; 64]);
+    stake.set_balance(first, [0u8; 32], 10);
+    stake.set_balance(second, [0u8; 32], 20);
+    // A proposal created in the slot of a change sees the balance from before it.
+    assert_eq!(stake.balance_at(20), Some(first));
+    assert_eq!(stake.balance_at(21), Some(second));
+
+    // Further changes in the same slot keep the balance from before the first.
+    stake.set_balance(third, [0u8; 32], 20);
+    assert_eq!(stake.balance_at(20), Some(first));
+    assert_eq!(stake.balance_at(21), Some(third));
+
+    stake.set_balance(first, [0u8; 32], 30);
+    assert_eq!(stake.balance_at(25), Some(third));
+    assert_eq!(stake.balance_at(15), None);
+
+    assert_eq!(stake.try_to_vec().unwrap().len(), UserStake::LEN);
+    let stake_weight = StakeWeight {
+        proposal: Pubkey::new_unique(),
+        owner: Pubkey::new_unique(),
+        weight: [0u8; 64],
+        voted: false,
+        bump: 255,
+    };
+    assert_eq!(stake_weight.try_to_vec().unwrap().len(), StakeWeight::LEN);
+}
+
+#[test]
+fn stake_weight_must_be_covered_by_the_snapshot_balance() {
+    let keypair = ElGamalKeypair::new_rand();
+    let keys = CommitteeKeys::generate(1, 1);
+    let domain = zk_utils::domain_separator("register_stake_weight", &Pubkey::new_unique());
+    let balance = encrypt_balance(&keypair, 750);
+    let prove = |balance_value: u64, weight: u64| {
+        tally::prove_stake_weight(
+            &keypair,
+            &keys.election_pubkey,
+            &domain,
+            STAKE_PER_VOTE,
+            &balance,
+            balance_value,
+            weight,
+        )
+    };
+
+    for weight in [0, 7] {
+        let proof = prove(750, weight).unwrap();
+        let commitments =
+            verify_stake_weight(&keypair, &keys.election_pubkey, &domain, &balance, &proof)
+                .unwrap();
+        assert!(proof.range_proof.verify());
+        let context = proof.range_proof.context();
+        assert_eq!(context.commitments[0].0, commitments[0]);
+        assert_eq!(context.commitments[1].0, commitments[1]);
+    }
+    assert!(prove(750, 8).is_none());
+
+    // Claiming a larger balance leaves a split that does not add up to the real one.
+    let inflated = prove(800, 8).unwrap();
+    assert_eq!(
+        verify_stake_weight(
+            &keypair,
+            &keys.election_pubkey,
+            &domain,
+            &balance,
+            &inflated
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+
+    // The election-key weight must hide the range-checked value.
+    let mut swapped = prove(750, 7).unwrap();
+    let larger = Weight::encrypted(&keys.election_pubkey, 70).unwrap();
+    let weight_commitment: [u8; 32] = swapped.split_commitments[..32].try_into().unwrap();
+    swapped.weight_proof.public_input_hash = zk_utils::weight_commitment_input_hash(
+        &keys.election_pubkey,
+        &weight_commitment,
+        &larger.ciphertext,
+    );
+    swapped.weight = larger;
+    assert_eq!(
+        verify_stake_weight(&keypair, &keys.election_pubkey, &domain, &balance, &swapped)
+            .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn inflated_weights_are_not_bound_to_the_split() {
+    let keypair = ElGamalKeypair::new_rand();
+    let keys = CommitteeKeys::generate(1, 1);
+    let domain = zk_utils::domain_separator("register_stake_weight", &Pubkey::new_unique());
+    let balance = encrypt_balance(&keypair, 750);
+    let honest = tally::prove_stake_weight(
+        &keypair,
+        &keys.election_pubkey,
+        &domain,
+        STAKE_PER_VOTE,
+        &balance,
+        750,
+        7,
+    )
+    .unwrap();
+
+    // Keep the split decrypting to 7 + 50 but let `E_w`'s own commitment open to 70, which
+    // is range-provable and can be tied to an election-key weight of 70.
+    let opening = PedersenOpening::new_rand();
+    let inflated_commitment = Pedersen::with(70_u64, &opening).to_bytes();
+    let weight_part: [u8; 64] = honest.stake_split[..64].try_into().unwrap();
+    let remainder: [u8; 64] = honest.stake_split[64..].try_into().unwrap();
+    let forged_part = common::forge_ciphertext(&keypair, &weight_part, &inflated_commitment);
+    let mut stake_split = honest.stake_split;
+    stake_split[..64].copy_from_slice(&forged_part);
+    let split_proof = {
+        let scaled =
+            zk_utils::scale_ciphertext(&zk_utils::amount_scalar(STAKE_PER_VOTE), &forged_part)
+                .and_then(|scaled| zk_utils::add_ciphertexts(&scaled, &remainder))
+                .unwrap();
+        prove_zero_delta(
+            &keypair,
+            &domain,
+            &balance,
+            &scaled,
+            0,
+            BalanceUpdate::Debit,
+        )
+        .unwrap()
+    };
+    let weight = Weight::encrypted(&keys.election_pubkey, 70).unwrap();
+    let weight_proof = tally::prove_weight_commitment(
+        &keys.election_pubkey,
+        &domain,
+        &inflated_commitment,
+        opening.get_scalar(),
+        &weight,
+    )
+    .unwrap();
+    assert!(prove_range(&[(70, &opening)]).unwrap().verify());
+
+    // The only commitment to 70 the voter can bind to `E_w` fails the equality proof.
+    let equality =
+        prove_equality(&keypair, &domain, &[(forged_part, 70), (remainder, 50)]).unwrap();
+    let mut split_commitments = [0u8; 64];
+    split_commitments[..32].copy_from_slice(&equality.commitments[0]);
+    split_commitments[32..].copy_from_slice(&equality.commitments[1]);
+    let inflated = StakeWeightProof {
+        weight,
+        stake_split,
+        split_commitments,
+        split_proof,
+        equality_proof: equality.proof,
+        range_proof: honest.range_proof,
+        weight_proof,
+    };
+    assert_eq!(
+        verify_stake_weight(
+            &keypair,
+            &keys.election_pubkey,
+            &domain,
+            &balance,
+            &inflated
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn stake_weighted_tally_decrypts_votes_and_turnout() {
+    let keys = CommitteeKeys::generate(2, 3);
+    let proposal_key = Pubkey::new_unique();
+    let mut proposal = proposal(keys.election_pubkey);
+    let vote_domain = zk_utils::domain_separator("cast_stake_vote", &proposal_key);
+    for (weight, in_favour) in [(7, true), (3, false), (5, true)] {
+        let weight = Weight::encrypted(&keys.election_pubkey, weight).unwrap();
+        let ballot =
+            tally::cast_ballot(&keys.election_pubkey, &vote_domain, in_favour, &weight).unwrap();
+        zk_utils::verify_ballot(
+            &ballot.proof,
+            &vote_domain,
+            &keys.election_pubkey,
+            &ballot.ciphertext,
+            &weight.ciphertext,
+        )
+        .unwrap();
+        proposal
+            .add_stake_ballot(&ballot.ciphertext, &weight.ciphertext)
+            .unwrap();
+    }
+    assert_eq!(proposal.ballots_cast, 3);
+    assert_eq!(proposal.votes_cast, 0);
+
+    // A ballot for more than the registered weight is rejected.
+    let registered = Weight::encrypted(&keys.election_pubkey, 7).unwrap();
+    let overweight = tally::cast_ballot(
+        &keys.election_pubkey,
+        &vote_domain,
+        true,
+        &Weight {
+            value: 8,
+            ..registered.clone()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        zk_utils::verify_ballot(
+            &overweight.proof,
+            &vote_domain,
+            &keys.election_pubkey,
+            &overweight.ciphertext,
+            &registered.ciphertext,
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+
+    let share_domain = zk_utils::domain_separator("submit_decryption_share", &proposal_key);
+    let totals = proposal.encrypted_totals();
+    let ciphertexts_c1: Vec<[u8; 32]> = totals
+        .iter()
+        .map(|total| zk_utils::split_ciphertext(total).0 .0)
+        .collect();
+    for position in [1, 2] {
+        let share_pubkey = keys.share_pubkey(position);
+        let share = tally::decrypt_share(&keys.shares[position], &share_domain, &totals).unwrap();
+        zk_utils::verify_decryption_share(
+            &share.proof,
+            &share_domain,
+            &share_pubkey,
+            &ciphertexts_c1,
+            &share.shares,
+        )
+        .unwrap();
+        // Both totals must be decrypted together.
+        assert_eq!(
+            zk_utils::verify_decryption_share(
+                &share.proof,
+                &share_domain,
+                &share_pubkey,
+                &ciphertexts_c1,
+                &share.shares[..1],
+            )
+            .unwrap_err(),
+            ErrorCode::InvalidPublicInputs.into()
+        );
+        proposal.decryption_shares[position] = share.shares[0];
+        proposal.turnout_decryption_shares[position] = share.shares[1];
+        proposal.decryption_share_mask |= 1 << position;
+    }
+
+    let shares = proposal.decryption_shares();
+    let turnout_shares = proposal.turnout_decryption_shares();
+    let votes_for = tally::decrypt_tally(&proposal.encrypted_tally, &shares, 100).unwrap();
+    let votes_cast =
+        tally::decrypt_tally(&proposal.encrypted_turnout, &turnout_shares, 100).unwrap();
+    assert_eq!((votes_for, votes_cast), (12, 15));
+    zk_utils::verify_tally(&proposal.encrypted_tally, &shares, votes_for).unwrap();
+    zk_utils::verify_tally(&proposal.encrypted_turnout, &turnout_shares, votes_cast).unwrap();
+    assert_eq!(
+        zk_utils::verify_tally(&proposal.encrypted_turnout, &turnout_shares, 16).unwrap_err(),
+        ErrorCode::InvalidTally.into()
+    );
+}
+
+#[test]
+fn public_weights_match_the_program() {
+    for weight in [0, 1, 42] {
+        assert_eq!(
+            zk_utils::public_weight(weight).unwrap(),
+            Weight::public(weight).ciphertext
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use anchor_lang::AnchorSerialize;
+use curve25519_dalek::constants::BASEPOINT_ORDER;
+use curve25519_dalek::scalar::Scalar;
+use rand::rngs::OsRng;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::zk_utils;
+use zk_liquidity_pool::{
+    CommitteeMember, ErrorCode, ProofEnvelope, Proposal, ProposalPayload, ProposalStatus,
+    TallyCommittee, VoteWeighting, MAX_COMMITTEE_SIZE,
+};
+use zk_liquidity_pool_prover::tally::{self, CommitteeKeys, Weight};
+
+fn committee(keys: &CommitteeKeys, size: usize) -> TallyCommittee {
+    let authorities: Vec<Pubkey> = (0..size).map(|_| Pubkey::new_unique()).collect();
+    TallyCommittee {
+        governance: Pubkey::new_unique(),
+        threshold: keys.threshold,
+        election_pubkey: keys.election_pubkey,
+        members: keys.members(&authorities),
+        bump: 255,
+    }
+}
+
+fn proposal(election_pubkey: [u8; 32]) -> Proposal {
+    Proposal {
+        governance: Pubkey::new_unique(),
+        id: 0,
+        proposer: Pubkey::new_unique(),
+        payload: ProposalPayload::UpdateRewardParams { new_reward_rate: 5 },
+        start_slot: 100,
+        end_slot: 200,
+        quorum: 1,
+        threshold_bps: 5_000,
+        vote_weighting: VoteWeighting::Identity,
+        votes_for: 0,
+        votes_against: 0,
+        election_pubkey,
+        encrypted_tally: [0u8; 64],
+        encrypted_turnout: [0u8; 64],
+        votes_cast: 0,
+        ballots_cast: 0,
+        decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        turnout_decryption_shares: [[0u8; 32]; MAX_COMMITTEE_SIZE],
+        decryption_share_mask: 0,
+        status: ProposalStatus::Active,
+        bump: 255,
+    }
+}
+
+#[test]
+fn threshold_of_the_committee_decrypts_the_tally() {
+    let keys = CommitteeKeys::generate(2, 3);
+    let committee = committee(&keys, 3);
+    committee.validate().unwrap();
+
+    let proposal_key = Pubkey::new_unique();
+    let mut proposal = proposal(keys.election_pubkey);
+    let vote_domain = zk_utils::domain_separator("cast_vote", &proposal_key);
+    let one = Weight::public(1);
+    let mut ballots = Vec::new();
+    for in_favour in [true, true, false, true] {
+        let ballot =
+            tally::cast_ballot(&keys.election_pubkey, &vote_domain, in_favour, &one).unwrap();
+        zk_utils::verify_ballot(
+            &ballot.proof,
+            &vote_domain,
+            &keys.election_pubkey,
+            &ballot.ciphertext,
+            &zk_utils::public_weight(1).unwrap(),
+        )
+        .unwrap();
+        proposal.add_ballot(&ballot.ciphertext, 1).unwrap();
+        ballots.push(ballot.ciphertext);
+    }
+    assert_eq!(proposal.votes_cast, 4);
+    assert_eq!(proposal.ballots_cast, 4);
+    assert_eq!(
+        proposal.encrypted_tally,
+        tally::sum_ballots(&ballots).unwrap()
+    );
+
+    // Members 1 and 3 decrypt; member 2 stays offline.
+    let share_domain = zk_utils::domain_separator("submit_decryption_share", &proposal_key);
+    let (ciphertext_c1, _) = zk_utils::split_ciphertext(&proposal.encrypted_tally);
+    for position in [0, 2] {
+        let share = tally::decrypt_share(
+            &keys.shares[position],
+            &share_domain,
+            &proposal.encrypted_totals(),
+        )
+        .unwrap();
+        zk_utils::verify_decryption_share(
+            &share.proof,
+            &share_domain,
+            &committee.members[position].share_pubkey,
+            &[ciphertext_c1.0],
+            &share.shares,
+        )
+        .unwrap();
+        proposal.decryption_shares[position] = share.shares[0];
+        proposal.decryption_share_mask |= 1 << position;
+    }
+
+    let shares = proposal.decryption_shares();
+    assert_eq!(
+        shares.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        vec![1, 3]
+    );
+    let votes_for = tally::decrypt_tally(&proposal.encrypted_tally, &shares, 4).unwrap();
+    assert_eq!(votes_for, 3);
+    zk_utils::verify_tally(&proposal.encrypted_tally, &shares, votes_for).unwrap();
+    for wrong in [0, 2, 4] {
+        assert_eq!(
+            zk_utils::verify_tally(&proposal.encrypted_tally, &shares, wrong).unwrap_err(),
+            ErrorCode::InvalidTally.into()
+        );
+    }
+    // One share is not enough.
+    assert_eq!(
+        zk_utils::verify_tally(&proposal.encrypted_tally, &shares[..1], votes_for).unwrap_err(),
+        ErrorCode::InvalidTally.into()
+    );
+}
+
+#[test]
+fn ballots_must_encrypt_zero_or_one() {
+    let keys = CommitteeKeys::generate(2, 3);
+    let proposal_key = Pubkey::new_unique();
+    let domain = zk_utils::domain_separator("cast_vote", &proposal_key);
+    let one = Weight::public(1);
+    let verify = |proof: &ProofEnvelope, ciphertext: &[u8; 64]| {
+        zk_utils::verify_ballot(
+            proof,
+            &domain,
+            &keys.election_pubkey,
+            ciphertext,
+            &one.ciphertext,
+        )
+    };
+
+    let (ciphertext, randomness) = tally::encrypt_vote(&keys.election_pubkey, 2).unwrap();
+    for claimed in [false, true] {
+        let proof = tally::prove_ballot(
+            &keys.election_pubkey,
+            &domain,
+            &ciphertext,
+            &randomness,
+            &one,
+            claimed,
+        )
+        .unwrap();
+        assert_eq!(
+            verify(&proof, &ciphertext).unwrap_err(),
+            ErrorCode::InvalidZKProof.into()
+        );
+    }
+
+    let ballot = tally::cast_ballot(&keys.election_pubkey, &domain, true, &one).unwrap();
+    let mut doubled = ballot.ciphertext;
+    doubled[32..].copy_from_slice(&tally::sum_ballots(&[ballot.ciphertext; 2]).unwrap()[32..]);
+    assert_eq!(
+        verify(&ballot.proof, &doubled).unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+    let mut truncated = ballot.proof.clone();
+    truncated.proof.pop();
+    assert_eq!(
+        verify(&truncated, &ballot.ciphertext).unwrap_err(),
+        ErrorCode::MalformedProof.into()
+    );
+
+    // A ballot cannot be replayed on another proposal.
+    let other = zk_utils::domain_separator("cast_vote", &Pubkey::new_unique());
+    assert_eq!(
+        zk_utils::verify_ballot(
+            &ballot.proof,
+            &other,
+            &keys.election_pubkey,
+            &ballot.ciphertext,
+            &one.ciphertext,
+        )
+        .unwrap_err(),
+        ErrorCode::ProofDomainMismatch.into()
+    );
+}
+
+#[test]
+fn decryption_shares_must_match_the_registered_key() {
+    let keys = CommitteeKeys::generate(2, 3);
+    let domain = zk_utils::domain_separator("submit_decryption_share", &Pubkey::new_unique());
+    let ballot =
+        tally::cast_ballot(&keys.election_pubkey, &domain, true, &Weight::public(1)).unwrap();
+    let ciphertexts_c1 = [zk_utils::split_ciphertext(&ballot.ciphertext).0 .0];
+    let share_pubkey = keys.share_pubkey(0);
+
+    // An honest DLEQ proof for someone else's secret does not match member 1's key.
+    let impostor = Scalar::random(&mut OsRng);
+    let forged = tally::decrypt_share(&impostor, &domain, &[ballot.ciphertext]).unwrap();
+    assert_eq!(
+        zk_utils::verify_decryption_share(
+            &forged.proof,
+            &domain,
+            &share_pubkey,
+            &ciphertexts_c1,
+            &forged.shares,
+        )
+        .unwrap_err(),
+        ErrorCode::PublicInputHashMismatch.into()
+    );
+
+    // Re-framing it for member 1's key leaves a proof that does not verify.
+    let mut reframed = forged.proof.clone();
+    reframed.public_input_hash =
+        zk_utils::decryption_share_input_hash(&share_pubkey, &ciphertexts_c1, &forged.shares);
+    assert_eq!(
+        zk_utils::verify_decryption_share(
+            &reframed,
+            &domain,
+            &share_pubkey,
+            &ciphertexts_c1,
+            &forged.shares,
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidZKProof.into()
+    );
+}
+
+#[test]
+fn inconsistent_committees_are_rejected() {
+    let keys = CommitteeKeys::generate(2, 3);
+    let invalid = |committee: TallyCommittee| {
+        assert_eq!(
+            committee.validate().unwrap_err(),
+            ErrorCode::InvalidTallyCommittee.into()
+        );
+    };
+
+    let mut off_polynomial = committee(&keys, 3);
+    off_polynomial.members[2].share_pubkey = CommitteeKeys::generate(2, 1).share_pubkey(0);
+    invalid(off_polynomial);
+
+    let mut wrong_election_key = committee(&keys, 3);
+    wrong_election_key.election_pubkey = CommitteeKeys::generate(2, 1).election_pubkey;
+    invalid(wrong_election_key);
+
+    let mut duplicate_member = committee(&keys, 3);
+    duplicate_member.members[1].authority = duplicate_member.members[0].authority;
+    invalid(duplicate_member);
+
+    let mut zero_threshold = committee(&keys, 3);
+    zero_threshold.threshold = 0;
+    invalid(zero_threshold);
+
+    let mut threshold_above_size = committee(&keys, 3);
+    threshold_above_size.threshold = 4;
+    invalid(threshold_above_size);
+
+    let full = CommitteeKeys::generate(7, MAX_COMMITTEE_SIZE);
+    let mut largest = committee(&full, MAX_COMMITTEE_SIZE);
+    largest.validate().unwrap();
+    assert_eq!(largest.try_to_vec().unwrap().len(), TallyCommittee::LEN);
+    largest.members.push(CommitteeMember {
+        authority: Pubkey::new_unique(),
+        share_pubkey: largest.members[0].share_pubkey,
+    });
+    invalid(largest);
+}
+
+#[test]
+fn scalar_helpers_match_dalek() {
+    assert_eq!(zk_utils::RISTRETTO_GROUP_ORDER, BASEPOINT_ORDER.to_bytes());
+    assert!(!zk_utils::is_canonical_scalar(&BASEPOINT_ORDER.to_bytes()));
+
+    let a = Scalar::random(&mut OsRng);
+    let b = Scalar::random(&mut OsRng);
+    assert!(zk_utils::is_canonical_scalar(a.as_bytes()));
+    assert_eq!(
+        zk_utils::scalar_sub(a.as_bytes(), b.as_bytes()),
+        (a - b).to_bytes()
+    );
+    assert_eq!(
+        zk_utils::scalar_sub(b.as_bytes(), a.as_bytes()),
+        (b - a).to_bytes()
+    );
+
+    // For shares 1, 2 and 4 the coefficients at 0 are 8/3, -2 and 1/3; the denominators
+    // are 3, -2 and 6, so everything is scaled by 6.
+    assert_eq!(
+        zk_utils::lagrange_coefficients(&[1, 2, 4], 0),
+        Some((vec![16, -12, 2], 6))
+    );
+    assert_eq!(zk_utils::lagrange_coefficients(&[1, 1], 0), None);
+}
@@ -0,0 +1,560 @@
+//! Runs the pool's Token-2022 CPIs against the Token-2022 processor in `solana-program-test`,
+//! with the ZK Token Proof program verifying the context-state proofs.
+
+use anchor_lang::{AccountDeserialize, AnchorSerialize, InstructionData, ToAccountMetas};
+use anchor_spl::token_2022::spl_token_2022;
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::hash::hashv;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_instruction, system_program, sysvar};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use solana_zk_token_sdk::encryption::auth_encryption::AeKey;
+use solana_zk_token_sdk::encryption::elgamal::{ElGamalCiphertext, ElGamalKeypair};
+use solana_zk_token_sdk::instruction::{
+    BatchedRangeProofContext, PubkeyValidityData, WithdrawData, WithdrawProofContext,
+};
+use solana_zk_token_sdk::zk_token_elgamal::pod;
+use solana_zk_token_sdk::zk_token_proof_instruction::{ContextStateInfo, ProofInstruction};
+use solana_zk_token_sdk::zk_token_proof_program;
+use solana_zk_token_sdk::zk_token_proof_state::ProofContextState;
+use spl_token_2022::extension::confidential_transfer::instruction as confidential;
+use spl_token_2022::extension::ExtensionType;
+use spl_token_2022::instruction::AuthorityType;
+use spl_token_2022::proof::ProofLocation;
+use spl_token_2022::state::{Account, Mint};
+use zk_liquidity_pool::confidential_token::ConfidentialTokenAccount;
+use zk_liquidity_pool::{
+    accounts, instruction, zk_utils, AssetVault, LiquidityPool, LockupPolicy, ProofBuffer,
+    ProofEnvelope, ProofSource, RewardTiers, UserStake,
+};
+use zk_liquidity_pool_prover::*;
+
+const DECIMALS: u8 = 6;
+const MINTED: u64 = 1_000;
+const STAKED: u64 = 500;
+const WITHDRAWN: u64 = 200;
+
+/// Anchor's entrypoint wants the accounts borrowed for as long as the `AccountInfo`s they
+/// hold, which a native processor's arguments are not.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    zk_liquidity_pool::entry(program_id, accounts, data)
+}
+
+async fn send(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        context.last_blockhash,
+    );
+    // The banks client takes transactions a validator would refuse as too large.
+    assert!(bincode::serialized_size(&transaction).unwrap() as usize <= PACKET_DATA_SIZE);
+    context.banks_client.process_transaction(transaction).await
+}
+
+async fn create_account(
+    context: &mut ProgramTestContext,
+    account: &Keypair,
+    space: usize,
+    owner: &Pubkey,
+) {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instruction = system_instruction::create_account(
+        &context.payer.pubkey(),
+        &account.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        owner,
+    );
+    send(context, &[instruction], &[account]).await.unwrap();
+}
+
+/// Creates a token account for `mint` with room for the confidential-transfer extension.
+async fn create_token_account(
+    context: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let space = ExtensionType::try_calculate_account_len::<Account>(&[
+        ExtensionType::ConfidentialTransferAccount,
+    ])
+    .unwrap();
+    create_account(context, &account, space, &spl_token_2022::id()).await;
+    let initialize = spl_token_2022::instruction::initialize_account3(
+        &spl_token_2022::id(),
+        &account.pubkey(),
+        mint,
+        owner,
+    )
+    .unwrap();
+    send(context, &[initialize], &[]).await.unwrap();
+    account.pubkey()
+}
+
+/// Configures `account` for confidential transfers under `elgamal`, proving the key valid in
+/// the same transaction.
+async fn configure_token_account(
+    context: &mut ProgramTestContext,
+    account: &Pubkey,
+    mint: &Pubkey,
+    owner: &Keypair,
+    elgamal: &ElGamalKeypair,
+    ae_key: &AeKey,
+) {
+    let proof = PubkeyValidityData::new(elgamal).unwrap();
+    let instructions = confidential::configure_account(
+        &spl_token_2022::id(),
+        account,
+        mint,
+        ae_key.encrypt(0),
+        u64::MAX,
+        &owner.pubkey(),
+        &[],
+        ProofLocation::InstructionOffset(1.try_into().unwrap(), &proof),
+    )
+    .unwrap();
+    send(context, &instructions, &[owner]).await.unwrap();
+}
+
+/// Creates a context-state account and verifies `instruction`'s proof into it.
+async fn verify_into_context(
+    context: &mut ProgramTestContext,
+    space: usize,
+    instruction: impl FnOnce(ContextStateInfo) -> Instruction,
+) -> Pubkey {
+    let account = Keypair::new();
+    create_account(context, &account, space, &zk_token_proof_program::id()).await;
+    let authority = context.payer.pubkey();
+    let verify = instruction(ContextStateInfo {
+        context_state_account: &account.pubkey(),
+        context_state_authority: &authority,
+    });
+    send(context, &[verify], &[]).await.unwrap();
+    account.pubkey()
+}
+
+/// Uploads `envelope` to the owner's proof buffer `buffer_id` in transaction-sized chunks.
+async fn upload_proof(
+    context: &mut ProgramTestContext,
+    owner: &Keypair,
+    buffer_id: u64,
+    envelope: &ProofEnvelope,
+) -> Pubkey {
+    const CHUNK: usize = 900;
+    let contents = envelope.try_to_vec().unwrap();
+    let (buffer, _) = Pubkey::find_program_address(
+        &[
+            ProofBuffer::SEED,
+            owner.pubkey().as_ref(),
+            &buffer_id.to_le_bytes(),
+        ],
+        &zk_liquidity_pool::ID,
+    );
+    let open = Instruction {
+        program_id: zk_liquidity_pool::ID,
+        accounts: accounts::OpenProofBuffer {
+            proof_buffer: buffer,
+            owner: owner.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::OpenProofBuffer {
+            buffer_id,
+            len: contents.len() as u32,
+            hash: hashv(&[&contents]).to_bytes(),
+        }
+        .data(),
+    };
+    send(context, &[open], &[owner]).await.unwrap();
+    for (i, chunk) in contents.chunks(CHUNK).enumerate() {
+        let write = Instruction {
+            program_id: zk_liquidity_pool::ID,
+            accounts: accounts::WriteProofChunk {
+                proof_buffer: buffer,
+                owner: owner.pubkey(),
+            }
+            .to_account_metas(None),
+            data: instruction::WriteProofChunk {
+                offset: (i * CHUNK) as u32,
+                chunk: chunk.to_vec(),
+            }
+            .data(),
+        };
+        send(context, &[write], &[owner]).await.unwrap();
+    }
+    buffer
+}
+
+async fn account_data(context: &mut ProgramTestContext, address: &Pubkey) -> Vec<u8> {
+    context
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .unwrap()
+        .data
+}
+
+async fn token_amount(context: &mut ProgramTestContext, address: &Pubkey) -> u64 {
+    let data = account_data(context, address).await;
+    Account::unpack_from_slice(&data[..Account::LEN])
+        .unwrap()
+        .amount
+}
+
+async fn confidential_account(
+    context: &mut ProgramTestContext,
+    address: &Pubkey,
+) -> ConfidentialTokenAccount {
+    ConfidentialTokenAccount::unpack(&account_data(context, address).await).unwrap()
+}
+
+async fn user_stake(context: &mut ProgramTestContext, address: &Pubkey) -> UserStake {
+    UserStake::try_deserialize(&mut &account_data(context, address).await[..]).unwrap()
+}
+
+#[tokio::test]
+async fn stake_apply_and_withdraw_move_tokens_through_token_2022() {
+    let mut program_test = ProgramTest::new(
+        "zk_liquidity_pool",
+        zk_liquidity_pool::ID,
+        processor!(process_instruction),
+    );
+    // The Token-2022 build bundled with `solana-program-test` leaves out `zk-ops`, so its
+    // confidential deposits and withdrawals always fail; run the version the program links.
+    program_test.add_program(
+        "spl_token_2022",
+        spl_token_2022::id(),
+        processor!(spl_token_2022::processor::Processor::process),
+    );
+    let mut context = program_test.start_with_context().await;
+    let authority = context.payer.insecure_clone();
+    let user = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    let fund = system_instruction::transfer(&authority.pubkey(), &user.pubkey(), 1_000_000_000);
+    send(&mut context, &[fund], &[]).await.unwrap();
+
+    // A mint that approves confidential accounts as they are configured.
+    let mint = Keypair::new();
+    let space = ExtensionType::try_calculate_account_len::<Mint>(&[
+        ExtensionType::ConfidentialTransferMint,
+    ])
+    .unwrap();
+    create_account(&mut context, &mint, space, &spl_token_2022::id()).await;
+    let mint_key = mint.pubkey();
+    let initialize = [
+        confidential::initialize_mint(&spl_token_2022::id(), &mint_key, None, true, None).unwrap(),
+        spl_token_2022::instruction::initialize_mint2(
+            &spl_token_2022::id(),
+            &mint_key,
+            &authority.pubkey(),
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+    ];
+    send(&mut context, &initialize, &[]).await.unwrap();
+
+    // The user's account, with a public balance to stake and the ElGamal key the position
+    // is encrypted under.
+    let user_elgamal = ElGamalKeypair::new_rand();
+    let user_token_account = create_token_account(&mut context, &mint_key, &user.pubkey()).await;
+    configure_token_account(
+        &mut context,
+        &user_token_account,
+        &mint_key,
+        &user,
+        &user_elgamal,
+        &AeKey::new_rand(),
+    )
+    .await;
+    let mint_to = spl_token_2022::instruction::mint_to(
+        &spl_token_2022::id(),
+        &mint_key,
+        &user_token_account,
+        &authority.pubkey(),
+        &[],
+        MINTED,
+    )
+    .unwrap();
+    send(&mut context, &[mint_to], &[]).await.unwrap();
+
+    // The pool's account is configured by the pool authority, who keeps its keys, and then
+    // handed over to the pool PDA, which cannot sign the configuration itself.
+    let (pool, _) = LiquidityPool::find_address(&mint_key);
+    let pool_elgamal = ElGamalKeypair::new_rand();
+    let pool_ae_key = AeKey::new_rand();
+    let pool_token_account =
+        create_token_account(&mut context, &mint_key, &authority.pubkey()).await;
+    configure_token_account(
+        &mut context,
+        &pool_token_account,
+        &mint_key,
+        &authority,
+        &pool_elgamal,
+        &pool_ae_key,
+    )
+    .await;
+    let hand_over = spl_token_2022::instruction::set_authority(
+        &spl_token_2022::id(),
+        &pool_token_account,
+        Some(&pool),
+        AuthorityType::AccountOwner,
+        &authority.pubkey(),
+        &[],
+    )
+    .unwrap();
+    send(&mut context, &[hand_over], &[]).await.unwrap();
+    let treasury_token_account = create_token_account(&mut context, &mint_key, &treasury).await;
+
+    // Set up the pool, its vault for the mint and the user's position.
+    let (vault, _) = Pubkey::find_program_address(
+        &[AssetVault::SEED, pool.as_ref(), mint_key.as_ref()],
+        &zk_liquidity_pool::ID,
+    );
+    let (position, _) = Pubkey::find_program_address(
+        &[
+            b"user_stake",
+            user.pubkey().as_ref(),
+            pool.as_ref(),
+            mint_key.as_ref(),
+        ],
+        &zk_liquidity_pool::ID,
+    );
+    let setup = [
+        Instruction {
+            program_id: zk_liquidity_pool::ID,
+            accounts: accounts::InitializePool {
+                pool,
+                token_mint: mint_key,
+                treasury,
+                authority: authority.pubkey(),
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::InitializePool {
+                auditor_elgamal_pubkey: None,
+                lockup: LockupPolicy::default(),
+                reward_tiers: RewardTiers::default(),
+            }
+            .data(),
+        },
+        Instruction {
+            program_id: zk_liquidity_pool::ID,
+            accounts: accounts::AddAsset {
+                pool,
+                vault,
+                vault_token_account: pool_token_account,
+                token_mint: mint_key,
+                authority: authority.pubkey(),
+                confidential_token_program: spl_token_2022::id(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::AddAsset { reward_weight: 1 }.data(),
+        },
+    ];
+    send(&mut context, &setup, &[]).await.unwrap();
+    let open = Instruction {
+        program_id: zk_liquidity_pool::ID,
+        accounts: accounts::OpenPosition {
+            pool,
+            vault,
+            user: user.pubkey(),
+            user_stake: position,
+            user_token_account,
+            token_mint: mint_key,
+            confidential_token_program: spl_token_2022::id(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::OpenPosition {}.data(),
+    };
+    send(&mut context, &[open], &[&user]).await.unwrap();
+
+    // Stake: the tokens move into the pool's account and on into its pending balance.
+    let stake_domain = zk_utils::domain_separator("stake", &pool);
+    let staked = prove_credit(&user_elgamal, &stake_domain, &ZERO_BALANCE, 0, STAKED).unwrap();
+    let stake = Instruction {
+        program_id: zk_liquidity_pool::ID,
+        accounts: accounts::StakeAccounts {
+            pool,
+            vault,
+            user: user.pubkey(),
+            user_stake: position,
+            user_token_account,
+            pool_token_account,
+            token_mint: mint_key,
+            confidential_token_program: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: instruction::Stake {
+            amount: STAKED,
+            zk_proof: ProofSource::Inline(staked.proof),
+            new_confidential_balance: staked.new_balance,
+            balance_commitment: staked.balance_commitment,
+            equality_proof: ProofSource::Inline(staked.equality_proof),
+            auditor_amount: None,
+        }
+        .data(),
+    };
+    send(&mut context, &[stake], &[&user]).await.unwrap();
+    assert_eq!(
+        token_amount(&mut context, &user_token_account).await,
+        MINTED - STAKED
+    );
+    assert_eq!(token_amount(&mut context, &pool_token_account).await, 0);
+    let pool_account = confidential_account(&mut context, &pool_token_account).await;
+    assert_eq!(pool_account.pending_balance_credit_counter, 1);
+    assert_eq!(
+        decrypt_balance(&pool_elgamal, &pool_account.pending_balance_lo),
+        Some(STAKED)
+    );
+    let staked_position = user_stake(&mut context, &position).await;
+    assert_eq!(staked_position.confidential_balance, staked.new_balance);
+    assert_eq!(
+        decrypt_balance(&user_elgamal, &staked_position.confidential_balance),
+        Some(STAKED)
+    );
+
+    // The pool authority credits the deposit to the pool's available balance.
+    let apply = Instruction {
+        program_id: zk_liquidity_pool::ID,
+        accounts: accounts::ApplyPoolPendingBalance {
+            pool,
+            pool_token_account,
+            authority: authority.pubkey(),
+            confidential_token_program: spl_token_2022::id(),
+        }
+        .to_account_metas(None),
+        data: instruction::ApplyPoolPendingBalance {
+            expected_pending_balance_credit_counter: 1,
+            new_decryptable_available_balance: pool_ae_key.encrypt(STAKED).to_bytes(),
+        }
+        .data(),
+    };
+    send(&mut context, &[apply], &[]).await.unwrap();
+    let pool_account = confidential_account(&mut context, &pool_token_account).await;
+    assert_eq!(
+        decrypt_balance(&pool_elgamal, &pool_account.available_balance),
+        Some(STAKED)
+    );
+    assert_eq!(
+        decrypt_balance(&pool_elgamal, &pool_account.pending_balance_lo),
+        Some(0)
+    );
+
+    // Withdraw: the user's range proof and the pool's `WithdrawData` proof are verified into
+    // context-state accounts and the user's proofs uploaded to buffers first, as none of them
+    // fit in the withdrawal's transaction.
+    let withdraw_domain = zk_utils::domain_separator("withdraw", &pool);
+    let debit = prove_debit(
+        &user_elgamal,
+        &withdraw_domain,
+        &staked.new_balance,
+        STAKED,
+        WITHDRAWN,
+    )
+    .unwrap();
+    let range_context = verify_into_context(
+        &mut context,
+        std::mem::size_of::<ProofContextState<BatchedRangeProofContext>>(),
+        |info| debit.range_proof.instruction(Some(info)),
+    )
+    .await;
+    let available = ElGamalCiphertext::from_bytes(&pool_account.available_balance).unwrap();
+    let pool_proof = WithdrawData::new(WITHDRAWN, &pool_elgamal, STAKED, &available).unwrap();
+    let withdraw_context = verify_into_context(
+        &mut context,
+        std::mem::size_of::<ProofContextState<WithdrawProofContext>>(),
+        |info| ProofInstruction::VerifyWithdraw.encode_verify_proof(Some(info), &pool_proof),
+    )
+    .await;
+    let proof_buffer = upload_proof(&mut context, &user, 0, &debit.proof).await;
+    let equality_buffer = upload_proof(&mut context, &user, 1, &debit.equality_proof).await;
+    let mut withdraw_accounts = accounts::Withdraw {
+        pool,
+        vault,
+        user_stake: position,
+        user: user.pubkey(),
+        pool_token_account,
+        user_token_account,
+        token_mint: mint_key,
+        confidential_token_program: spl_token_2022::id(),
+        proof_account: range_context,
+        pool_withdraw_proof: withdraw_context,
+        treasury: treasury_token_account,
+    }
+    .to_account_metas(None);
+    withdraw_accounts.push(AccountMeta::new_readonly(proof_buffer, false));
+    withdraw_accounts.push(AccountMeta::new_readonly(equality_buffer, false));
+    let withdraw = Instruction {
+        program_id: zk_liquidity_pool::ID,
+        accounts: withdraw_accounts,
+        data: instruction::Withdraw {
+            amount: WITHDRAWN,
+            zk_proof: ProofSource::Buffer(proof_buffer),
+            new_confidential_balance: debit.new_balance,
+            balance_commitment: debit.balance_commitment,
+            equality_proof: ProofSource::Buffer(equality_buffer),
+            range_proof: ProofSource::Inline(range_proof_envelope(
+                &withdraw_domain,
+                0,
+                &[debit.balance_commitment],
+            )),
+            pool_decryptable_balance: pool_ae_key.encrypt(STAKED - WITHDRAWN).to_bytes(),
+            auditor_amount: None,
+        }
+        .data(),
+    };
+    send(&mut context, &[withdraw], &[&user]).await.unwrap();
+
+    // The tokens are back in the user's public balance, and both balances are down by the
+    // amount withdrawn.
+    assert_eq!(
+        token_amount(&mut context, &user_token_account).await,
+        MINTED - STAKED + WITHDRAWN
+    );
+    assert_eq!(token_amount(&mut context, &pool_token_account).await, 0);
+    let pool_account = confidential_account(&mut context, &pool_token_account).await;
+    assert_eq!(
+        decrypt_balance(&pool_elgamal, &pool_account.available_balance),
+        Some(STAKED - WITHDRAWN)
+    );
+    assert_eq!(
+        pool_ae_key.decrypt(
+            &pod::AeCiphertext(pool_account.decryptable_available_balance)
+                .try_into()
+                .unwrap()
+        ),
+        Some(STAKED - WITHDRAWN)
+    );
+    let position = user_stake(&mut context, &position).await;
+    assert_eq!(
+        decrypt_balance(&user_elgamal, &position.confidential_balance),
+        Some(STAKED - WITHDRAWN)
+    );
+    assert_eq!(position.balance_commitment, debit.balance_commitment);
+    let pool =
+        LiquidityPool::try_deserialize(&mut &account_data(&mut context, &pool).await[..]).unwrap();
+    assert_eq!(pool.total_commitment, debit.balance_commitment);
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeSet;
+
+use anchor_lang::{Accounts, AnchorSerialize};
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use zk_liquidity_pool::{
+    CloseTradeOrder, CloseTradeOrderBumps, ErrorCode, LiquidityPool, LockupPolicy, RewardTiers,
+    TradeOrder,
+};
+
+mod common;
+
+use common::{account, anchor_data};
+
+const ORDER: &[u8] = b"buy 100 at 1.25";
+const NONCE: [u8; 32] = [9u8; 32];
+const COMMIT_SLOT: u64 = 1_000;
+const COMMITTED_AT: i64 = 50_000;
+
+fn order(trader: Pubkey, bump: u8) -> TradeOrder {
+    TradeOrder {
+        trader,
+        commitment: TradeOrder::commitment_of(ORDER, &NONCE, &trader),
+        encrypted_order: [0u8; 64],
+        trade_timestamp: COMMITTED_AT,
+        commit_slot: COMMIT_SLOT,
+        revealed: false,
+        bump,
+        revealed_order: Vec::new(),
+    }
+}
+
+#[test]
+fn reveals_must_open_the_commitment_within_the_window() {
+    let trader = Pubkey::new_unique();
+    let earliest = COMMIT_SLOT + TradeOrder::MIN_REVEAL_DELAY_SLOTS;
+    let deadline = COMMITTED_AT + TradeOrder::REVEAL_WINDOW_SECONDS;
+    let reveal = |details: &[u8], nonce: &[u8; 32], slot: u64, now: i64| {
+        order(trader, 255).reveal(details.to_vec(), nonce, slot, now)
+    };
+
+    assert_eq!(
+        reveal(ORDER, &NONCE, earliest - 1, COMMITTED_AT).unwrap_err(),
+        ErrorCode::RevealTooEarly.into()
+    );
+    assert_eq!(
+        reveal(ORDER, &NONCE, earliest, deadline + 1).unwrap_err(),
+        ErrorCode::RevealDeadlinePassed.into()
+    );
+    for (details, nonce) in [(&b"buy 101 at 1.25"[..], NONCE), (ORDER, [8u8; 32])] {
+        assert_eq!(
+            reveal(details, &nonce, earliest, deadline).unwrap_err(),
+            ErrorCode::OrderCommitmentMismatch.into()
+        );
+    }
+    // The commitment binds the trader, so another trader's order cannot be replayed.
+    let mut copied = order(Pubkey::new_unique(), 255);
+    copied.commitment = order(trader, 255).commitment;
+    assert_eq!(
+        copied
+            .reveal(ORDER.to_vec(), &NONCE, earliest, deadline)
+            .unwrap_err(),
+        ErrorCode::OrderCommitmentMismatch.into()
+    );
+    assert_eq!(
+        reveal(
+            &[0u8; TradeOrder::MAX_ORDER_LEN + 1],
+            &NONCE,
+            earliest,
+            deadline
+        )
+        .unwrap_err(),
+        ErrorCode::InvalidOrderDetails.into()
+    );
+
+    let mut order = order(trader, 255);
+    order
+        .reveal(ORDER.to_vec(), &NONCE, earliest, deadline)
+        .unwrap();
+    assert!(order.revealed);
+    assert_eq!(order.revealed_order, ORDER);
+    assert_eq!(
+        order
+            .reveal(ORDER.to_vec(), &NONCE, earliest, deadline)
+            .unwrap_err(),
+        ErrorCode::OrderAlreadyRevealed.into()
+    );
+
+    order.revealed_order = vec![0u8; TradeOrder::MAX_ORDER_LEN];
+    assert_eq!(order.try_to_vec().unwrap().len(), TradeOrder::LEN);
+}
+
+#[test]
+fn orders_close_once_they_can_no_longer_be_revealed() {
+    let deadline = COMMITTED_AT + TradeOrder::REVEAL_WINDOW_SECONDS;
+    let mut order = order(Pubkey::new_unique(), 255);
+    assert!(!order.is_closable(deadline));
+    assert!(order.is_closable(deadline + 1));
+    order.revealed = true;
+    assert!(order.is_closable(COMMITTED_AT));
+}
+
+#[test]
+fn closed_orders_refund_their_trader() {
+    let trader = Pubkey::new_unique();
+    let token_mint = Pubkey::new_unique();
+    let (pool_key, pool_bump) = LiquidityPool::find_address(&token_mint);
+    let pool = LiquidityPool {
+        authority: Pubkey::new_unique(),
+        token_mint,
+        total_commitment: [0u8; 32],
+        auditor_elgamal_pubkey: None,
+        auditor_total: [0u8; 64],
+        lockup: LockupPolicy::default(),
+        treasury: Pubkey::new_unique(),
+        reward_tiers: RewardTiers::default(),
+        reward_rate: 0,
+        acc_reward_per_share: 0,
+        last_reward_timestamp: 0,
+        reward_rate_history: Vec::new(),
+        bump: pool_bump,
+    };
+    let (order_key, bump) = Pubkey::find_program_address(
+        &[b"trade_order", trader.as_ref(), pool_key.as_ref()],
+        &zk_liquidity_pool::ID,
+    );
+    let program = zk_liquidity_pool::ID;
+    let load = |refund_to: Pubkey| {
+        let accounts: &'static [AccountInfo<'static>] = Box::leak(Box::new([
+            account(
+                order_key,
+                program,
+                anchor_data(&order(trader, bump)),
+                false,
+                false,
+            ),
+            account(refund_to, Pubkey::default(), Vec::new(), false, false),
+            account(pool_key, program, anchor_data(&pool), false, false),
+        ]));
+        let mut accounts = accounts;
+        CloseTradeOrder::try_accounts(
+            &program,
+            &mut accounts,
+            &[],
+            &mut CloseTradeOrderBumps::default(),
+            &mut BTreeSet::new(),
+        )
+        .map(drop)
+    };
+
+    // Anyone may close an order, without signing, but the rent only goes to its trader.
+    load(trader).unwrap();
+    assert_eq!(
+        load(Pubkey::new_unique()).unwrap_err(),
+        anchor_lang::error::ErrorCode::ConstraintSeeds.into()
+    );
+}
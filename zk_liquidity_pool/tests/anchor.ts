@@ -2,7 +2,7 @@
 //This test file needs to be edited 
 
 import * as anchor from "@coral-xyz/anchor";
-import { Program, web3, BN } from "@coral-xyz/anchor";
+import { Program, web3 } from "@coral-xyz/anchor";
 import assert from "assert";
 import type { ZkLiquidityPool } from "../target/types/zk_liquidity_pool";
 
@@ -15,6 +15,8 @@ describe("zk-liquidity-pool", () => {
   it("initializes the liquidity pool", async () => {
     // Generate a dummy token mint
     const tokenMint = web3.Keypair.generate();
+    // Owner of the token accounts that receive early-withdraw penalties
+    const treasury = web3.Keypair.generate();
 
     // Derive the pool PDA using seed ["pool", tokenMint]
     const [poolPDA, bump] = await web3.PublicKey.findProgramAddress(
@@ -22,11 +24,25 @@ describe("zk-liquidity-pool", () => {
       program.programId
     );
 
-    // Call initialize_pool with bump and proper accounts.
-    const tx = await program.methods.initializePool(new BN(bump))
+    // Call initialize_pool without an auditor; the program stores the canonical bump itself.
+    const lockup = {
+      minLockSeconds: new anchor.BN(60),
+      unbondingSeconds: new anchor.BN(0),
+      earlyWithdrawPenaltyBps: 0,
+    };
+    const day = 24 * 60 * 60;
+    const rewardTiers = {
+      tiers: [
+        { minStakeSeconds: new anchor.BN(30 * day), multiplierBps: 10_000 },
+        { minStakeSeconds: new anchor.BN(90 * day), multiplierBps: 12_500 },
+        { minStakeSeconds: new anchor.BN(180 * day), multiplierBps: 15_000 },
+      ],
+    };
+    const tx = await program.methods.initializePool(null, lockup, rewardTiers)
       .accounts({
         pool: poolPDA,
         tokenMint: tokenMint.publicKey,
+        treasury: treasury.publicKey,
         authority: provider.publicKey,
         systemProgram: web3.SystemProgram.programId,
         rent: web3.SYSVAR_RENT_PUBKEY,
@@ -37,7 +53,13 @@ describe("zk-liquidity-pool", () => {
 
     // Fetch the pool account and verify initialization
     const poolAccount = await program.account.liquidityPool.fetch(poolPDA);
-    assert.ok(poolAccount.totalStaked.eq(new BN(0)));
+    assert.deepStrictEqual(poolAccount.totalCommitment, new Array(32).fill(0));
+    assert.ok(poolAccount.auditorElgamalPubkey === null);
+    assert.ok(poolAccount.lockup.minLockSeconds.eq(lockup.minLockSeconds));
+    assert.ok(poolAccount.treasury.equals(treasury.publicKey));
+    assert.ok(poolAccount.rewardTiers.tiers.length === 3);
+    assert.ok(poolAccount.rewardRate.isZero());
+    assert.ok(poolAccount.accRewardPerShare.isZero());
     assert.ok(poolAccount.bump === bump);
     assert.ok(poolAccount.tokenMint.equals(tokenMint.publicKey));
     assert.ok(poolAccount.authority.equals(provider.publicKey));